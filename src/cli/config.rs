@@ -0,0 +1,13 @@
+use crab_toolchain::parser::Parser;
+use crab_toolchain::parser::ast::Crabfile;
+use super::{Error, Format};
+
+pub fn run(parser: &Parser, format: Format) -> Result<(), Error> {
+    let crabfile = Crabfile::from(parser);
+    match format {
+        Format::Text => print!("{}", crabfile),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&crabfile).expect("the model serializes to JSON")),
+        Format::Yaml => print!("{}", serde_yaml::to_string(&crabfile).expect("the model serializes to YAML")),
+    }
+    Ok(())
+}
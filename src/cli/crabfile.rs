@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+use crab_toolchain::parser::{Parser, ParseError, Diagnostic, Expanded, Sources};
+use super::Error;
+
+const DEFAULT_CRABFILE: &str = "Crabfile";
+
+pub struct Crabfile {
+    path: PathBuf,
+    sources: Sources,
+}
+
+/// The Crabfile given with `-f`, or the one in the current directory.
+pub fn path_or_default(path: Option<&Path>) -> PathBuf {
+    path.map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(DEFAULT_CRABFILE))
+}
+
+impl Crabfile {
+    /// Reads the Crabfile and the files it includes.
+    pub fn open(path: Option<&Path>) -> Result<Self, Error> {
+        let path = path_or_default(path);
+        let sources = Sources::load(&path).map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound(path.clone()),
+            _ => Error::Io(path.clone(), err),
+        })?;
+
+        Ok(Crabfile {
+            path,
+            sources
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The directory the Crabfile is in, the one relative volume sources are resolved against.
+    pub fn directory(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
+    }
+
+    /// The Crabfile itself, without the files it includes.
+    pub fn source(&self) -> &[u8] {
+        self.sources.root()
+    }
+
+    /// Expands the `${NAME}` references of the Crabfile and the files it includes, from the environment
+    /// and the `@vars` blocks.
+    pub fn expand(&self) -> Result<Expanded<'_>, Error> {
+        self.sources.validate().map_err(|errors| self.invalid(&errors))?;
+        Expanded::from_env(self.sources.text()).map_err(|errors| self.invalid(&errors))
+    }
+
+    /// Replaces the `${NAME}` references of the Crabfile itself with placeholders, for looking at it as written.
+    pub fn placeholders(&self) -> Result<Expanded<'_>, Error> {
+        Expanded::placeholders(self.source()).map_err(|errors| self.invalid(&errors))
+    }
+
+    /// Parses the expanded Crabfile with the files it includes and checks the containers against each other.
+    pub fn parse<'a>(&'a self, expanded: &'a Expanded<'a>) -> Result<Parser<'a>, Error> {
+        let parser = Parser::parse_sources(&self.sources, expanded).map_err(|err| self.invalid(&[err]))?;
+        parser.validate().map_err(|errors| self.invalid(&errors))?;
+        Ok(parser)
+    }
+
+    /// Parses the Crabfile itself, expanded by [`Crabfile::placeholders`], without following its includes
+    /// or looking at how its containers refer to each other.
+    pub fn parse_syntax<'a>(&self, expanded: &'a Expanded<'a>) -> Result<Parser<'a>, Error> {
+        Parser::parse_expanded(expanded).map_err(|err| self.invalid(&[err]))
+    }
+
+    fn invalid(&self, errors: &[ParseError]) -> Error {
+        Error::Invalid(errors.iter()
+            .map(|err| Diagnostic::from_sources(&self.sources, err))
+            .collect())
+    }
+}
@@ -0,0 +1,26 @@
+use crab_toolchain::parser::Parser;
+use crab_toolchain::runtime::ContainerRuntime;
+use super::Error;
+
+pub fn run(parser: &Parser, runtime: &dyn ContainerRuntime) -> Result<(), Error> {
+    for container in parser.containers() {
+        let name = String::from_utf8_lossy(container.name());
+        if let Some(state) = runtime.inspect(&name)? {
+            if state.running {
+                runtime.stop(&name)?;
+            }
+            runtime.remove(&name)?;
+            println!("{}: removed", name);
+        }
+    }
+
+    for network in parser.networks() {
+        let name = String::from_utf8_lossy(network.name());
+        if runtime.has_network(&name)? {
+            runtime.remove_network(&name)?;
+            println!("network {}: removed", name);
+        }
+    }
+
+    Ok(())
+}
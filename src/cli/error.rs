@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+use crab_toolchain::parser::{Diagnostic, Cycle};
+use crab_toolchain::runtime::EnvError;
+
+pub enum Error {
+    Usage(String),
+    NotFound(PathBuf),
+    Io(PathBuf, std::io::Error),
+    Write(PathBuf, std::io::Error),
+    Invalid(Vec<Diagnostic>),
+    Cycle(Cycle),
+    Environment(EnvError),
+    Compose(PathBuf, crab_toolchain::compose::Error),
+    Exists(PathBuf),
+    UnknownContainer(String),
+    UnknownProfile(String),
+    NoRuntime,
+    Runtime(crab_toolchain::runtime::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoRuntime | Error::Runtime(_) | Error::Write(..) => 1,
+            Error::Usage(_) | Error::UnknownContainer(_) | Error::UnknownProfile(_) | Error::Exists(_) => 2,
+            Error::NotFound(_) | Error::Io(..) => 3,
+            Error::Invalid(_) | Error::Cycle(_) | Error::Environment(_) | Error::Compose(..) => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::NotFound(path) => write!(f, "no Crabfile found at {}", path.display()),
+            Error::Io(path, err) => write!(f, "could not read {}: {}", path.display(), err),
+            Error::Write(path, err) => write!(f, "could not write {}: {}", path.display(), err),
+            Error::Invalid(diagnostics) => {
+                for (index, diagnostic) in diagnostics.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "\n\n")?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                match diagnostics.len() {
+                    1 => Ok(()),
+                    count => write!(f, "\n\n{} problems found", count),
+                }
+            },
+            Error::Environment(err) => write!(f, "{}", err),
+            Error::Compose(path, err) => write!(f, "invalid compose file {}, {}", path.display(), err),
+            Error::Exists(path) => write!(f, "{} already exists, pass `--force` to overwrite it", path.display()),
+            Error::Cycle(cycle) => write!(f, "cannot order containers, {}", cycle),
+            Error::UnknownContainer(name) => write!(f, "no container named `{}` in the Crabfile", name),
+            Error::UnknownProfile(name) => write!(f, "no container in the Crabfile has the profile `{}`", name),
+            Error::NoRuntime => write!(f, "no container runtime found, install docker or podman or pass `--runtime`"),
+            Error::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<Cycle> for Error {
+    fn from(err: Cycle) -> Self {
+        Error::Cycle(err)
+    }
+}
+
+impl From<EnvError> for Error {
+    fn from(err: EnvError) -> Self {
+        Error::Environment(err)
+    }
+}
+
+impl From<crab_toolchain::runtime::Error> for Error {
+    fn from(err: crab_toolchain::runtime::Error) -> Self {
+        match err {
+            crab_toolchain::runtime::Error::Environment(err) => Error::Environment(err),
+            err => Error::Runtime(err),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use crab_toolchain::{compose, kubernetes};
+use crab_toolchain::parser::Parser;
+use crab_toolchain::parser::ast::Crabfile;
+use super::{Error, Target};
+
+pub fn run(parser: &Parser, target: Target) -> Result<(), Error> {
+    let crabfile = Crabfile::from(parser);
+    match target {
+        Target::Compose => print!("{}", compose::export(&crabfile)),
+        Target::Kubernetes => {
+            let export = kubernetes::export(&crabfile);
+            print!("{}", export.manifests);
+            for warning in &export.warnings {
+                eprintln!("warning: {}", warning);
+            }
+        },
+    }
+    Ok(())
+}
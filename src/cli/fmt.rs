@@ -0,0 +1,23 @@
+use super::{Crabfile, Error};
+
+/// Rewrites the Crabfile in the canonical style, returning 1 when `check` finds it is not.
+///
+/// `${NAME}` references are kept as written, so formatting does not depend on the environment.
+pub fn run(crabfile: &Crabfile, check: bool) -> Result<i32, Error> {
+    let expanded = crabfile.placeholders()?;
+    let formatted = crabfile.parse_syntax(&expanded)?.to_string();
+    let path = crabfile.path();
+
+    match (formatted.as_bytes() == crabfile.source(), check) {
+        (true, _) => println!("{}: already formatted", path.display()),
+        (false, true) => {
+            eprintln!("{}: not formatted, run `crab fmt`", path.display());
+            return Ok(1)
+        },
+        (false, false) => {
+            std::fs::write(path, formatted).map_err(|err| Error::Write(path.to_path_buf(), err))?;
+            println!("{}: formatted", path.display());
+        },
+    }
+    Ok(0)
+}
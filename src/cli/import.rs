@@ -0,0 +1,23 @@
+use std::path::Path;
+use crab_toolchain::compose;
+use super::Error;
+
+/// Writes the Crabfile translated from the compose file, then lists what could not be translated.
+pub fn run(compose: &Path, crabfile: &Path, force: bool) -> Result<(), Error> {
+    let source = std::fs::read_to_string(compose).map_err(|err| Error::Io(compose.to_path_buf(), err))?;
+    let imported = compose::import(&source).map_err(|err| Error::Compose(compose.to_path_buf(), err))?;
+
+    if crabfile.exists() && !force {
+        return Err(Error::Exists(crabfile.to_path_buf()))
+    }
+    std::fs::write(crabfile, imported.crabfile.to_string()).map_err(|err| Error::Write(crabfile.to_path_buf(), err))?;
+    println!("{}: imported {} container(s) from {}", crabfile.display(), imported.crabfile.containers.len(), compose.display());
+
+    if !imported.unsupported.is_empty() {
+        eprintln!("unsupported keys, left out of the Crabfile:");
+        for unsupported in &imported.unsupported {
+            eprintln!("    {}", unsupported);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+mod options;
+mod error;
+mod crabfile;
+mod validate;
+mod config;
+mod up;
+mod down;
+mod ps;
+mod shell;
+mod plan;
+mod import;
+mod export;
+mod fmt;
+
+pub use options::{Options, Command, Format, Target};
+pub use error::Error;
+use crabfile::Crabfile;
+use crab_toolchain::parser::{Parser, Container, Network};
+use crab_toolchain::runtime::{ContainerRuntime, DryRun, Kind};
+use std::path::Path;
+
+const USAGE: &str = "\
+crab - run toolchain containers described by a Crabfile
+
+USAGE:
+    crab [-f <path>] <command> [<args>]
+
+OPTIONS:
+    -f, --file <path>    Use <path> instead of ./Crabfile
+    --runtime <name>     Use docker, podman, docker-api or dry-run instead of
+                         the first one found on this machine
+    -h, --help           Print this help
+    -V, --version        Print the version
+
+COMMANDS:
+    validate             Check the Crabfile for errors
+    fmt [--check]        Rewrite the Crabfile in the canonical style, or only
+                         check that it already is
+    config [--format <f>]
+                         Print the parsed configuration as text, json or yaml
+    plan [--json] [--profile <name>]...
+                         Print the engine commands `up` would run, in start order
+    up [--profile <name>]...
+                         Create the networks and start every container, volume
+                         sources first, or only those with one of the profiles
+                         and the containers they take volumes from
+    down                 Stop and remove every container and network
+    ps                   Show the state of every container
+    shell <container>    Open the configured shell in a container, starting it
+                         if needed, and exit with the shell's exit code
+    import compose [<path>] [--force]
+                         Write the Crabfile from a compose file, by default
+                         ./docker-compose.yml, listing the keys left out
+    export compose       Print the Crabfile as a compose file
+    export k8s           Print a Deployment per container and a Service per
+                         port, warning about what cannot be carried over
+
+EXIT CODES:
+    0    success
+    1    the command failed
+    2    invalid command-line usage
+    3    the Crabfile could not be read
+    4    the Crabfile, or the imported file, is invalid
+";
+
+pub fn run<I: IntoIterator<Item=String>>(args: I) -> i32 {
+    match Options::parse(args).and_then(execute) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("crab: {}", err);
+            if let Error::Usage(_) = err {
+                eprintln!("\nRun `crab --help` for usage.");
+            }
+            err.exit_code()
+        }
+    }
+}
+
+/// Runs the command, returning the exit code it succeeded with.
+fn execute(options: Options) -> Result<i32, Error> {
+    match options.command {
+        Command::Help => {
+            print!("{}", USAGE);
+            return Ok(0)
+        }
+        Command::Version => {
+            println!("crab {}", env!("CARGO_PKG_VERSION"));
+            return Ok(0)
+        }
+        Command::Import { compose, force } => {
+            return import::run(&compose, &crabfile::path_or_default(options.file.as_deref()), force).map(|_| 0)
+        }
+        _ => {}
+    }
+
+    let crabfile = Crabfile::open(options.file.as_deref())?;
+    if let Command::Fmt { check } = options.command {
+        return fmt::run(&crabfile, check)
+    }
+    let expanded = crabfile.expand()?;
+    let parser = crabfile.parse(&expanded)?;
+
+    match options.command {
+        Command::Validate => validate::run(&crabfile, &parser).map(|_| 0),
+        Command::Config { format } => config::run(&parser, format).map(|_| 0),
+        Command::Plan { json, profiles } => plan::run(&parser, &profiles, options.runtime, crabfile.directory(), json).map(|_| 0),
+        Command::Up { profiles } => with_runtime(options.runtime, crabfile.directory(), |runtime| up::run(&parser, &profiles, runtime).map(|_| 0)),
+        Command::Down => with_runtime(options.runtime, crabfile.directory(), |runtime| down::run(&parser, runtime).map(|_| 0)),
+        Command::Ps => with_runtime(options.runtime, crabfile.directory(), |runtime| ps::run(&parser, runtime).map(|_| 0)),
+        Command::Export { target } => export::run(&parser, target).map(|_| 0),
+        Command::Shell { container } => match parser.container(&container) {
+            Some(container) => with_runtime(options.runtime, crabfile.directory(), |runtime| shell::run(&parser, container, runtime)),
+            None => Err(Error::UnknownContainer(container)),
+        },
+        Command::Help | Command::Version | Command::Import { .. } | Command::Fmt { .. } => unreachable!(),
+    }
+}
+
+/// Runs `command` against the selected runtime, listing what would have happened for a dry run.
+/// Relative volume sources are resolved against `directory`, the one the Crabfile is in.
+fn with_runtime<F>(kind: Option<Kind>, directory: &Path, command: F) -> Result<i32, Error>
+    where F: FnOnce(&dyn ContainerRuntime) -> Result<i32, Error>
+{
+    match kind.or_else(Kind::detect) {
+        Some(Kind::DryRun) => {
+            let runtime = DryRun::default().in_directory(directory);
+            let result = command(&runtime);
+            for operation in runtime.operations() {
+                println!("dry-run: {}", operation);
+            }
+            result
+        },
+        Some(kind) => command(kind.connect(directory)?.as_ref()),
+        None => Err(Error::NoRuntime),
+    }
+}
+
+/// The containers `up` starts, only those of `profiles` and their volume sources when any is given.
+fn start_order<'p, 'a>(parser: &'p Parser<'a>, profiles: &[String]) -> Result<Vec<&'p Container<'a>>, Error> {
+    if let Some(profile) = profiles.iter().find(|profile| !parser.has_profile(profile)) {
+        return Err(Error::UnknownProfile(profile.clone()))
+    }
+    let profiles = profiles.iter().map(String::as_str).collect::<Vec<_>>();
+    Ok(parser.start_order_for(&profiles)?)
+}
+
+/// The networks of the `@network` blocks any of `containers` joins, in the order they are declared in.
+fn networks<'p, 'a>(parser: &'p Parser<'a>, containers: &[&Container]) -> Vec<&'p Network<'a>> {
+    parser.networks()
+        .filter(|network| containers.iter().any(|container| container.networks().any(|name| name == network.name())))
+        .collect()
+}
@@ -0,0 +1,293 @@
+use std::path::PathBuf;
+use crab_toolchain::runtime::Kind;
+use super::Error;
+
+const DEFAULT_COMPOSE_FILE: &str = "docker-compose.yml";
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Options {
+    pub file: Option<PathBuf>,
+    pub runtime: Option<Kind>,
+    pub command: Command,
+}
+
+/// How `crab config` prints the configuration.
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Format {
+    Text,
+    Json,
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            other => Err(Error::Usage(format!("unknown format `{}`, expected one of text, json, yaml", other))),
+        }
+    }
+}
+
+/// What `crab export` writes the Crabfile as.
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Target {
+    Compose,
+    Kubernetes,
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Command {
+    Help,
+    Version,
+    Validate,
+    Config {
+        format: Format
+    },
+    Plan {
+        json: bool,
+        profiles: Vec<String>
+    },
+    Up {
+        profiles: Vec<String>
+    },
+    Down,
+    Ps,
+    Shell {
+        container: String
+    },
+    Import {
+        compose: PathBuf,
+        force: bool
+    },
+    Export {
+        target: Target
+    },
+    Fmt {
+        check: bool
+    },
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item=String>>(args: I) -> Result<Self, Error> {
+        let mut args = args.into_iter();
+        let mut file = None;
+        let mut runtime = None;
+        let mut json = false;
+        let mut format = None;
+        let mut force = false;
+        let mut check = false;
+        let mut profiles = Vec::new();
+        let mut positional = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-f" | "--file" => match args.next() {
+                    Some(path) => file = Some(PathBuf::from(path)),
+                    None => return Err(Error::Usage(format!("`{}` requires a path", arg))),
+                },
+                "--runtime" => match args.next() {
+                    Some(name) => runtime = Some(name.parse().map_err(Error::Usage)?),
+                    None => return Err(Error::Usage(format!("`{}` requires a runtime name", arg))),
+                },
+                "--profile" => match args.next() {
+                    Some(name) => profiles.push(name),
+                    None => return Err(Error::Usage(format!("`{}` requires a profile name", arg))),
+                },
+                "--json" => json = true,
+                "--force" => force = true,
+                "--check" => check = true,
+                "--format" => match args.next() {
+                    Some(name) => format = Some(name.parse()?),
+                    None => return Err(Error::Usage(format!("`{}` requires a format", arg))),
+                },
+                "-h" | "--help" => return Ok(Options { file, runtime, command: Command::Help }),
+                "-V" | "--version" => return Ok(Options { file, runtime, command: Command::Version }),
+                flag if flag.starts_with('-') => return Err(Error::Usage(format!("unknown option `{}`", flag))),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            None | Some("help") => Command::Help,
+            Some("validate") => Command::Validate,
+            Some("config") => Command::Config { format: format.unwrap_or(Format::Text) },
+            Some("plan") => Command::Plan { json, profiles: profiles.clone() },
+            Some("up") => Command::Up { profiles: profiles.clone() },
+            Some("down") => Command::Down,
+            Some("ps") => Command::Ps,
+            Some("shell") => match positional.next() {
+                Some(container) => Command::Shell { container },
+                None => return Err(Error::Usage("`shell` requires a container name".to_string())),
+            },
+            Some("import") => match positional.next().as_deref() {
+                Some("compose") => Command::Import {
+                    compose: PathBuf::from(positional.next().as_deref().unwrap_or(DEFAULT_COMPOSE_FILE)),
+                    force
+                },
+                Some(other) => return Err(Error::Usage(format!("cannot import `{}`, only `compose` is supported", other))),
+                None => return Err(Error::Usage("`import` requires a format, `compose`".to_string())),
+            },
+            Some("fmt") => Command::Fmt { check },
+            Some("export") => match positional.next().as_deref() {
+                Some("compose") => Command::Export { target: Target::Compose },
+                Some("k8s" | "kubernetes") => Command::Export { target: Target::Kubernetes },
+                Some(other) => return Err(Error::Usage(format!("cannot export to `{}`, expected compose or k8s", other))),
+                None => return Err(Error::Usage("`export` requires a format, compose or k8s".to_string())),
+            },
+            Some(other) => return Err(Error::Usage(format!("unknown command `{}`", other))),
+        };
+
+        if json && !matches!(command, Command::Plan { .. }) {
+            return Err(Error::Usage("`--json` only applies to `plan`".to_string()))
+        }
+        if format.is_some() && !matches!(command, Command::Config { .. }) {
+            return Err(Error::Usage("`--format` only applies to `config`".to_string()))
+        }
+        if force && !matches!(command, Command::Import { .. }) {
+            return Err(Error::Usage("`--force` only applies to `import`".to_string()))
+        }
+        if check && !matches!(command, Command::Fmt { .. }) {
+            return Err(Error::Usage("`--check` only applies to `fmt`".to_string()))
+        }
+        if !profiles.is_empty() && !matches!(command, Command::Up { .. } | Command::Plan { .. }) {
+            return Err(Error::Usage("`--profile` only applies to `up` and `plan`".to_string()))
+        }
+
+        match positional.next() {
+            Some(extra) => Err(Error::Usage(format!("unexpected argument `{}`", extra))),
+            None => Ok(Options { file, runtime, command }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, Command, Format, Target};
+    use crab_toolchain::runtime::Kind;
+    use std::path::PathBuf;
+
+    fn parse(args: &[&str]) -> Option<Options> {
+        Options::parse(args.iter().map(|arg| arg.to_string())).ok()
+    }
+
+    #[test]
+    fn test_no_arguments_prints_help() {
+        assert_eq!(parse(&[]), Some(Options { file: None, runtime: None, command: Command::Help }));
+    }
+
+    #[test]
+    fn test_command_with_file() {
+        assert_eq!(parse(&["-f", "examples/Crabfile", "validate"]), Some(Options {
+            file: Some(PathBuf::from("examples/Crabfile")),
+            runtime: None,
+            command: Command::Validate
+        }));
+        assert_eq!(parse(&["up", "--file", "Crabfile.dev"]), Some(Options {
+            file: Some(PathBuf::from("Crabfile.dev")),
+            runtime: None,
+            command: Command::Up { profiles: vec![] }
+        }));
+    }
+
+    #[test]
+    fn test_shell_container() {
+        assert_eq!(parse(&["shell", "ubuntu"]), Some(Options {
+            file: None,
+            runtime: None,
+            command: Command::Shell { container: "ubuntu".to_string() }
+        }));
+    }
+
+    #[test]
+    fn test_runtime() {
+        assert_eq!(parse(&["--runtime", "podman", "up"]), Some(Options {
+            file: None,
+            runtime: Some(Kind::Podman),
+            command: Command::Up { profiles: vec![] }
+        }));
+        assert!(parse(&["--runtime", "containerd", "up"]).is_none());
+        assert!(parse(&["up", "--runtime"]).is_none());
+    }
+
+    #[test]
+    fn test_plan_json() {
+        assert_eq!(parse(&["plan", "--json"]), Some(Options {
+            file: None,
+            runtime: None,
+            command: Command::Plan { json: true, profiles: vec![] }
+        }));
+        assert!(parse(&["up", "--json"]).is_none());
+    }
+
+    #[test]
+    fn test_profiles() {
+        assert_eq!(parse(&["up", "--profile", "frontend", "--profile", "docs"]).map(|options| options.command), Some(Command::Up {
+            profiles: vec!["frontend".to_string(), "docs".to_string()]
+        }));
+        assert_eq!(parse(&["--profile", "backend", "plan"]).map(|options| options.command), Some(Command::Plan {
+            json: false,
+            profiles: vec!["backend".to_string()]
+        }));
+        assert!(parse(&["up", "--profile"]).is_none());
+        assert!(parse(&["down", "--profile", "frontend"]).is_none());
+    }
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(parse(&["config", "--format", "yaml"]), Some(Options {
+            file: None,
+            runtime: None,
+            command: Command::Config { format: Format::Yaml }
+        }));
+        assert_eq!(parse(&["config"]).map(|options| options.command), Some(Command::Config { format: Format::Text }));
+        assert!(parse(&["config", "--format", "toml"]).is_none());
+        assert!(parse(&["ps", "--format", "json"]).is_none());
+    }
+
+    #[test]
+    fn test_import_compose() {
+        assert_eq!(parse(&["import", "compose"]).map(|options| options.command), Some(Command::Import {
+            compose: PathBuf::from("docker-compose.yml"),
+            force: false
+        }));
+        assert_eq!(parse(&["-f", "Crabfile.dev", "import", "compose", "compose.yaml", "--force"]), Some(Options {
+            file: Some(PathBuf::from("Crabfile.dev")),
+            runtime: None,
+            command: Command::Import { compose: PathBuf::from("compose.yaml"), force: true }
+        }));
+        assert!(parse(&["import"]).is_none());
+        assert!(parse(&["import", "k8s"]).is_none());
+        assert!(parse(&["up", "--force"]).is_none());
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(parse(&["export", "compose"]).map(|options| options.command), Some(Command::Export { target: Target::Compose }));
+        assert_eq!(parse(&["export", "k8s"]).map(|options| options.command), Some(Command::Export { target: Target::Kubernetes }));
+        assert!(parse(&["export"]).is_none());
+        assert!(parse(&["export", "compose", "extra"]).is_none());
+    }
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!(parse(&["fmt"]).map(|options| options.command), Some(Command::Fmt { check: false }));
+        assert_eq!(parse(&["fmt", "--check"]).map(|options| options.command), Some(Command::Fmt { check: true }));
+        assert!(parse(&["validate", "--check"]).is_none());
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert!(parse(&["-f"]).is_none());
+        assert!(parse(&["shell"]).is_none());
+        assert!(parse(&["validate", "extra"]).is_none());
+        assert!(parse(&["unknown"]).is_none());
+        assert!(parse(&["--unknown", "up"]).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+use serde_json::json;
+use crab_toolchain::parser::Parser;
+use crab_toolchain::runtime::{Cli, Kind};
+use super::Error;
+
+/// Prints what `crab up` would run, without touching any engine, as if none of the networks existed yet.
+pub fn run(parser: &Parser, profiles: &[String], kind: Option<Kind>, directory: &Path, json: bool) -> Result<(), Error> {
+    let engine = match kind.or_else(Kind::detect) {
+        Some(Kind::Podman) => Cli::podman(),
+        _ => Cli::docker(),
+    }.in_directory(directory);
+    let shell = String::from_utf8_lossy(parser.shell().path());
+    let containers = super::start_order(parser, profiles)?;
+    let networks = super::networks(parser, &containers).into_iter()
+        .map(|network| (String::from_utf8_lossy(network.name()).into_owned(), quote(&engine.network_plan(network))))
+        .collect::<Vec<_>>();
+    let plans = containers.into_iter()
+        .map(|container| Ok((
+            String::from_utf8_lossy(container.name()).into_owned(),
+            engine.plan(container)?.iter().map(|command| quote(command)).collect::<Vec<_>>()
+        )))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if json {
+        let containers = plans.iter().map(|(name, commands)| json!({
+            "name": name,
+            "commands": commands,
+        })).collect::<Vec<_>>();
+        let networks = networks.iter().map(|(name, command)| json!({
+            "name": name,
+            "command": command,
+        })).collect::<Vec<_>>();
+        println!("{:#}", json!({
+            "shell": shell,
+            "networks": networks,
+            "order": plans.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            "containers": containers,
+        }));
+        return Ok(())
+    }
+
+    println!("shell: {}", shell);
+    println!();
+    println!("start order:");
+    for (index, (name, _)) in plans.iter().enumerate() {
+        println!("  {}. {}", index + 1, name);
+    }
+    println!();
+    println!("commands:");
+    for command in networks.iter().map(|(_, command)| command).chain(plans.iter().flat_map(|(_, commands)| commands)) {
+        println!("  {}", command);
+    }
+
+    Ok(())
+}
+
+/// Joins `args` into a line a POSIX shell splits back into the same arguments.
+fn quote(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty() && arg.chars().all(|chr| chr.is_ascii_alphanumeric() || "-_./:=@,+%".contains(chr));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::quote;
+
+    #[test]
+    fn test_quote() {
+        let args = ["docker", "run", "-v", "/home/my files:/data", "it's", ""].iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(quote(&args), r"docker run -v '/home/my files:/data' 'it'\''s' ''");
+    }
+}
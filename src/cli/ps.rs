@@ -0,0 +1,21 @@
+use crab_toolchain::parser::Parser;
+use crab_toolchain::runtime::ContainerRuntime;
+use super::Error;
+
+pub fn run(parser: &Parser, runtime: &dyn ContainerRuntime) -> Result<(), Error> {
+    let rows = parser.containers()
+        .map(|container| {
+            let name = String::from_utf8_lossy(container.name()).into_owned();
+            let status = runtime.inspect(&name)?.map_or_else(|| "not created".to_string(), |state| state.status);
+            Ok((name, status))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or_default().max("NAME".len());
+
+    println!("{:width$}  STATUS", "NAME", width = width);
+    for (name, status) in rows {
+        println!("{:width$}  {}", name, status, width = width);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use crab_toolchain::parser::{Parser, Container};
+use crab_toolchain::runtime::{ContainerRuntime, stdin_is_terminal};
+use super::Error;
+
+/// Opens the `@shell` path in the container, starting it first when needed, and returns the shell's exit code.
+pub fn run(parser: &Parser, container: &Container, runtime: &dyn ContainerRuntime) -> Result<i32, Error> {
+    let name = String::from_utf8_lossy(container.name());
+    match runtime.inspect(&name)? {
+        Some(state) if state.running => {},
+        Some(_) => runtime.start(&name)?,
+        None => {
+            runtime.create(container)?;
+            runtime.start(&name)?;
+        },
+    }
+
+    let shell = String::from_utf8_lossy(parser.shell().path()).into_owned();
+    Ok(runtime.exec(&name, &[shell], stdin_is_terminal())?)
+}
@@ -0,0 +1,32 @@
+use crab_toolchain::parser::Parser;
+use crab_toolchain::runtime::ContainerRuntime;
+use super::Error;
+
+pub fn run(parser: &Parser, profiles: &[String], runtime: &dyn ContainerRuntime) -> Result<(), Error> {
+    let containers = super::start_order(parser, profiles)?;
+    for network in super::networks(parser, &containers) {
+        let name = String::from_utf8_lossy(network.name());
+        if !runtime.has_network(&name)? {
+            runtime.create_network(network)?;
+            println!("network {}: created", name);
+        }
+    }
+
+    for container in containers {
+        let name = String::from_utf8_lossy(container.name());
+        match runtime.inspect(&name)? {
+            Some(state) if state.running => println!("{}: already running", name),
+            Some(_) => {
+                runtime.start(&name)?;
+                println!("{}: started", name);
+            },
+            None => {
+                runtime.create(container)?;
+                runtime.start(&name)?;
+                println!("{}: created", name);
+            },
+        }
+    }
+
+    Ok(())
+}
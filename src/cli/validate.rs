@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+use crab_toolchain::parser::Parser;
+use crab_toolchain::runtime::environment;
+use super::{Crabfile, Error};
+
+pub fn run(crabfile: &Crabfile, parser: &Parser) -> Result<(), Error> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    for container in parser.containers() {
+        environment(container, crabfile.directory(), home.as_deref())?;
+    }
+    println!("{}: ok, {} container(s)", crabfile.path().display(), parser.containers().count());
+    Ok(())
+}
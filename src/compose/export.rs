@@ -0,0 +1,304 @@
+use std::net::IpAddr;
+use serde_yaml::{Mapping, Value};
+use crate::parser::{VolumeKind, format_publish, format_expose};
+use crate::parser::ast::{Crabfile, Container, Network, Manifest, Argument, PortRange};
+
+/// Writes the containers as the services of a compose file.
+///
+/// Services and their keys keep the order of the Crabfile, so the same Crabfile always gives the same
+/// document. A Dockerfile becomes a `build` section in the current directory, `volume-from` both a
+/// `volumes_from` and a `depends_on` entry, `port: 0:<port>` a port published on a random host port,
+/// host addresses, ranges and protocols the same short syntax `docker run -p` takes, and
+/// `profiles:` the `profiles` of the service, `command` and `entrypoint` lists of arguments and `workdir` the
+/// `working_dir`. Named volumes are also declared in the top-level `volumes`, the `@network` blocks in the
+/// top-level `networks`, `network:` lines becoming the `networks` of the service with their `aliases`.
+/// The `@shell` block only matters to `crab shell` and is left out.
+pub fn export(crabfile: &Crabfile) -> String {
+    let services = crabfile.containers.iter()
+        .map(|container| (Value::from(container.name.as_str()), service(container)))
+        .collect::<Mapping>();
+
+    let mut volumes = Mapping::new();
+    for argument in crabfile.containers.iter().flat_map(|container| &container.arguments) {
+        if let Argument::Volume { source, .. } = argument {
+            if VolumeKind::of(source.as_bytes()) == VolumeKind::Named {
+                volumes.insert(source.as_str().into(), Value::Null);
+            }
+        }
+    }
+
+    let networks = crabfile.networks.iter()
+        .map(|network| (Value::from(network.name.as_str()), network_definition(network)))
+        .collect::<Mapping>();
+
+    let mut document = Mapping::new();
+    document.insert("services".into(), services.into());
+    if !networks.is_empty() {
+        document.insert("networks".into(), networks.into());
+    }
+    if !volumes.is_empty() {
+        document.insert("volumes".into(), volumes.into());
+    }
+    serde_yaml::to_string(&document).expect("a compose document serializes to YAML")
+}
+
+/// `null` for a network with the engine's defaults.
+fn network_definition(network: &Network) -> Value {
+    let mut definition = Mapping::new();
+    if let Some(driver) = &network.driver {
+        definition.insert("driver".into(), driver.as_str().into());
+    }
+    if network.internal {
+        definition.insert("internal".into(), true.into());
+    }
+    if let Some(subnet) = &network.subnet {
+        let config = vec![Value::Mapping(vec![("subnet".into(), subnet.to_string().into())].into_iter().collect())];
+        definition.insert("ipam".into(), Value::Mapping(vec![("config".into(), Value::Sequence(config))].into_iter().collect()));
+    }
+    match definition.is_empty() {
+        true => Value::Null,
+        false => Value::Mapping(definition),
+    }
+}
+
+fn service(container: &Container) -> Value {
+    let mut service = Mapping::new();
+    match &container.manifest {
+        Manifest::Image(image) => service.insert("image".into(), image.as_str().into()),
+        Manifest::File(file) => service.insert("build".into(), Value::Mapping(vec![
+            ("context".into(), ".".into()),
+            ("dockerfile".into(), file.as_str().into()),
+        ].into_iter().collect())),
+    };
+
+    let mut ports = Vec::new();
+    let mut expose = Vec::new();
+    let mut volumes = Vec::new();
+    let mut volumes_from = Vec::new();
+    let mut environment = Mapping::new();
+    let mut env_files = Vec::new();
+    let mut profiles = Vec::new();
+    let mut networks = Vec::new();
+    let mut aliases = Vec::new();
+    let mut process = Mapping::new();
+    for argument in &container.arguments {
+        match argument {
+            Argument::PublishPort { host, outer, inner, protocol } if *outer == PortRange::single(0) => ports.push(match host {
+                Some(IpAddr::V6(host)) => format!("[{}]::{}", host, format_expose(*inner, *protocol)),
+                Some(IpAddr::V4(host)) => format!("{}::{}", host, format_expose(*inner, *protocol)),
+                None => format_expose(*inner, *protocol),
+            }.into()),
+            Argument::PublishPort { host, outer, inner, protocol } => ports.push(format_publish(*host, *outer, *inner, *protocol).into()),
+            Argument::ExposePort { port, protocol } => expose.push(format_expose(*port, *protocol).into()),
+            Argument::Volume { source, mount, options } if options.is_empty() => volumes.push(format!("{}:{}", source, mount).into()),
+            Argument::Volume { source, mount, options } => volumes.push(format!("{}:{}:{}", source, mount, options.join(",")).into()),
+            Argument::VolumeFrom { name } => volumes_from.push(Value::from(name.as_str())),
+            Argument::Env { key, value } => {
+                environment.insert(key.as_str().into(), value.as_str().into());
+            },
+            Argument::EnvFile { path } => env_files.push(path.as_str().into()),
+            Argument::Profiles { names } => profiles.extend(names.iter().map(|name| Value::from(name.as_str()))),
+            Argument::Entrypoint { args } => {
+                process.insert("entrypoint".into(), args.iter().map(String::as_str).collect::<Vec<_>>().into());
+            },
+            Argument::Command { args } => {
+                process.insert("command".into(), args.iter().map(String::as_str).collect::<Vec<_>>().into());
+            },
+            Argument::Workdir { path } => {
+                process.insert("working_dir".into(), path.as_str().into());
+            },
+            Argument::User { user } => {
+                process.insert("user".into(), user.as_str().into());
+            },
+            Argument::Network { name } => networks.push(Value::from(name.as_str())),
+            Argument::Alias { name } => aliases.push(Value::from(name.as_str())),
+        }
+    }
+
+    let depends_on = volumes_from.clone();
+    for (key, values) in [("ports", ports), ("expose", expose), ("volumes", volumes), ("volumes_from", volumes_from)] {
+        if !values.is_empty() {
+            service.insert(key.into(), Value::Sequence(values));
+        }
+    }
+    if !depends_on.is_empty() {
+        service.insert("depends_on".into(), Value::Sequence(depends_on));
+    }
+    if !environment.is_empty() {
+        service.insert("environment".into(), Value::Mapping(environment));
+    }
+    if !env_files.is_empty() {
+        service.insert("env_file".into(), Value::Sequence(env_files));
+    }
+    service.extend(process);
+    if !profiles.is_empty() {
+        service.insert("profiles".into(), Value::Sequence(profiles));
+    }
+    // Compose only takes aliases per network, the same ones go to each of them.
+    match aliases.is_empty() {
+        true if !networks.is_empty() => {
+            service.insert("networks".into(), Value::Sequence(networks));
+        },
+        true => {},
+        false => {
+            let endpoint = Value::Mapping(vec![("aliases".into(), Value::Sequence(aliases))].into_iter().collect());
+            service.insert("networks".into(), Value::Mapping(networks.into_iter().map(|network| (network, endpoint.clone())).collect()));
+        },
+    }
+    Value::Mapping(service)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Parser, Sources, Expanded};
+    use crate::parser::ast::Crabfile;
+    use crate::runtime::TempDir;
+    use super::export;
+
+    const CRABFILE: &str = indoc::indoc! {"
+        @network backend:
+            driver: bridge
+            subnet: 172.20.0.0/16
+            internal: true
+
+        @network frontend:
+
+        @app:
+            from: Dockerfile.app
+            port: 80:8080
+            port: 0:9000
+            port: 127.0.0.1:5353:53/udp
+            port: [::1]:0:8443
+            port: 7000-7002:8000-8002
+            expose: 3000
+            expose: 3001/udp
+            volume: /srv/static:/var/www
+            volume: ./src:/app:ro
+            volume: assets:/var/assets
+            volume-from: cache
+            env: RUST_LOG=debug
+            env-file: .env
+            entrypoint: /docker-entrypoint.sh
+            command: nginx -g 'daemon off;'
+            workdir: /srv
+            user: 1000:1000
+            profiles: web, dev
+            network: frontend
+            network: backend
+
+        @cache:
+            from: redis:7
+            network: backend
+            alias: redis
+    "};
+
+    fn crabfile() -> Crabfile {
+        Crabfile::from(&Parser::parse(CRABFILE.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(export(&crabfile()), indoc::indoc! {"
+            services:
+              app:
+                build:
+                  context: .
+                  dockerfile: Dockerfile.app
+                ports:
+                - 80:8080
+                - '9000'
+                - 127.0.0.1:5353:53/udp
+                - '[::1]::8443'
+                - 7000-7002:8000-8002
+                expose:
+                - '3000'
+                - 3001/udp
+                volumes:
+                - /srv/static:/var/www
+                - ./src:/app:ro
+                - assets:/var/assets
+                volumes_from:
+                - cache
+                depends_on:
+                - cache
+                environment:
+                  RUST_LOG: debug
+                env_file:
+                - .env
+                entrypoint:
+                - /docker-entrypoint.sh
+                command:
+                - nginx
+                - -g
+                - daemon off;
+                working_dir: /srv
+                user: 1000:1000
+                profiles:
+                - web
+                - dev
+                networks:
+                - frontend
+                - backend
+              cache:
+                image: redis:7
+                networks:
+                  backend:
+                    aliases:
+                    - redis
+            networks:
+              backend:
+                driver: bridge
+                internal: true
+                ipam:
+                  config:
+                  - subnet: 172.20.0.0/16
+              frontend: null
+            volumes:
+              assets: null
+        "});
+    }
+
+    #[test]
+    fn test_included_paths() {
+        let directory = TempDir::new("export-include");
+        std::fs::create_dir_all(directory.path().join("shared")).unwrap();
+        std::fs::write(directory.path().join("Crabfile"), "@include: shared/Crabfile\n\n@app:\n    extends: db\n    volume: ./logs:/logs\n").unwrap();
+        std::fs::write(directory.path().join("shared/Crabfile"), "@db:\n    from: postgres:16\n    volume: ./data:/data\n    volume: ../backup:/backup\n    volume: pgdata:/pg\n    env-file: db.env\n    env-file: ~/db.env\n").unwrap();
+
+        let sources = Sources::load(&directory.path().join("Crabfile")).unwrap();
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let crabfile = Crabfile::from(&Parser::parse_sources(&sources, &expanded).unwrap());
+        assert_eq!(export(&crabfile), indoc::indoc! {"
+            services:
+              app:
+                image: postgres:16
+                volumes:
+                - ./shared/data:/data
+                - ./backup:/backup
+                - pgdata:/pg
+                - ./logs:/logs
+                env_file:
+                - shared/db.env
+                - ~/db.env
+              db:
+                image: postgres:16
+                volumes:
+                - ./shared/data:/data
+                - ./backup:/backup
+                - pgdata:/pg
+                env_file:
+                - shared/db.env
+                - ~/db.env
+            volumes:
+              pgdata: null
+        "});
+    }
+
+    #[test]
+    fn test_import_back() {
+        let imported = crate::compose::import(&export(&crabfile())).unwrap();
+
+        assert_eq!(imported.crabfile, crabfile());
+        assert_eq!(imported.unsupported, vec![]);
+    }
+}
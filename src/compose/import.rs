@@ -0,0 +1,760 @@
+use serde_yaml::{Mapping, Value};
+use crate::parser::Shell;
+use crate::parser::ast::{Crabfile, Container, Network, Manifest, Argument};
+use super::{Error, Unsupported};
+
+const NO_EQUIVALENT: &str = "no Crabfile equivalent";
+
+/// A compose file translated to a Crabfile, with everything that was left out of it.
+#[derive(Debug)]
+pub struct Import {
+    pub crabfile: Crabfile,
+    pub unsupported: Vec<Unsupported>,
+}
+
+/// Translates the services of a compose file to containers, in the order they are listed.
+///
+/// `image` and `build` become the manifest, `ports`, `expose`, `volumes`, `volumes_from`, `environment`,
+/// `env_file`, `profiles`, `command`, `entrypoint`, `working_dir` and `user` the arguments, named volumes only need to be declared in the top-level `volumes`.
+/// The top-level `networks` become `@network` blocks, the `networks` of a service its `network:` and `alias:` lines.
+/// Every other key, and every value a Crabfile cannot hold, is reported
+/// in [`Import::unsupported`] instead.
+pub fn import(source: &str) -> Result<Import, Error> {
+    let document: Value = serde_yaml::from_str(source).map_err(Error::Syntax)?;
+    let root = match &document {
+        Value::Mapping(root) => root,
+        Value::Null => return Err(invalid("", "the compose file is empty")),
+        _ => return Err(invalid("", "expected a mapping at the top of the compose file")),
+    };
+
+    // Services can come before the networks they are on.
+    let declared = root.get("networks").and_then(Value::as_mapping).into_iter().flatten()
+        .filter_map(|(name, _)| name.as_str())
+        .filter(|name| *name != "default" && is_name(name))
+        .map(str::to_string)
+        .collect();
+    let mut importer = Importer { networks: declared, ..Importer::default() };
+    let mut containers = Vec::new();
+    let mut networks = Vec::new();
+    for (key, value) in root {
+        match key.as_str().unwrap_or_default() {
+            "services" => {
+                let services = value.as_mapping().ok_or_else(|| invalid("services", "expected a mapping"))?;
+                for (name, service) in services {
+                    let name = name.as_str().unwrap_or_default();
+                    let service = service.as_mapping()
+                        .ok_or_else(|| invalid(&format!("services.{}", name), "expected a mapping"))?;
+                    containers.extend(importer.service(name, service));
+                }
+            },
+            "networks" => for (name, network) in value.as_mapping().into_iter().flatten() {
+                networks.extend(importer.network(name.as_str().unwrap_or_default(), network));
+            },
+            // Obsolete, compose ignores it as well.
+            "version" => {},
+            // The engine creates named volumes on first use, only their configuration is lost.
+            "volumes" => for (name, volume) in value.as_mapping().into_iter().flatten() {
+                let configured = volume.as_mapping().map_or(!volume.is_null(), |volume| !volume.is_empty());
+                if configured {
+                    importer.skip(format!("volumes.{}", name.as_str().unwrap_or_default()), NO_EQUIVALENT);
+                }
+            },
+            other => importer.skip(other.to_string(), NO_EQUIVALENT),
+        }
+    }
+
+    Ok(Import {
+        crabfile: Crabfile {
+            shell: String::from_utf8_lossy(Shell::default().path()).into_owned(),
+            networks,
+            containers
+        },
+        unsupported: importer.unsupported
+    })
+}
+
+fn invalid(key: &str, message: &str) -> Error {
+    Error::Invalid { key: key.to_string(), message: message.to_string() }
+}
+
+/// A string, number or boolean as the text compose would use.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|chr| chr.is_alphanumeric() || chr == '_' || chr == '-')
+}
+
+/// Whether the Crabfile accepts `user` as a user or group name or id.
+fn is_user(user: &str) -> bool {
+    user.starts_with(|chr: char| chr.is_ascii_alphanumeric() || chr == '_')
+        && user.chars().all(|chr| chr.is_ascii_alphanumeric() || "_-.".contains(chr))
+}
+
+/// Reads a compose port, `[host:][published:]target[/protocol]`. Without a published port the engine
+/// picks one, which a Crabfile writes as port 0.
+fn publish(port: &str) -> Result<Argument, String> {
+    let (ports, protocol) = port.rsplit_once('/').map_or((port, ""), |(ports, _)| (ports, &port[ports.len()..]));
+    // The host of `[::1]:80:80` has colons of its own.
+    let (host, ports) = ports.rfind(']').map_or(("", ports), |close| ports.split_at(close + 1));
+    let ports = match ports.matches(':').count() {
+        0 if ports.contains('-') => return Err("port ranges published on random host ports are not supported".to_string()),
+        0 => format!("0:{}", ports),
+        _ => ports.replacen("::", ":0:", 1),
+    };
+    crate::parser::Argument::parse_publish_at(format!("{}{}{}", host, ports, protocol).as_bytes())
+        .map(|port| Argument::from(&port))
+        .map_err(|(error, _)| error.to_string())
+}
+
+#[derive(Default)]
+struct Importer {
+    unsupported: Vec<Unsupported>,
+    /// The names of the top-level `networks` that become `@network` blocks.
+    networks: Vec<String>,
+}
+
+impl Importer {
+    fn skip<R: Into<String>>(&mut self, key: String, reason: R) {
+        self.unsupported.push(Unsupported { key, reason: reason.into() });
+    }
+
+    fn service(&mut self, name: &str, service: &Mapping) -> Option<Container> {
+        let key = format!("services.{}", name);
+        if !is_name(name) {
+            self.skip(key, "invalid container name, only letters, digits, `_` and `-` are allowed");
+            return None
+        }
+
+        let mut image = None;
+        let mut build = None;
+        let mut depends_on = None;
+        let mut arguments = Vec::new();
+        for (field, value) in service {
+            let field = field.as_str().unwrap_or_default();
+            let key = format!("{}.{}", key, field);
+            match field {
+                "image" => image = Some((key, value)),
+                "build" => build = Some((key, value)),
+                "depends_on" => depends_on = Some((key, value)),
+                "ports" => self.each(key, value, &mut arguments, Self::port),
+                "expose" => self.each(key, value, &mut arguments, Self::expose),
+                "volumes" => self.each(key, value, &mut arguments, Self::volume),
+                "volumes_from" => self.each(key, value, &mut arguments, Self::volume_from),
+                "environment" => self.environment(key, value, &mut arguments),
+                "env_file" => match value {
+                    Value::Sequence(_) => self.each(key, value, &mut arguments, Self::env_file),
+                    value => arguments.extend(self.env_file(key, value)),
+                },
+                "profiles" => arguments.extend(self.profiles(key, value)),
+                "command" => arguments.extend(self.argv(key, value).map(|args| Argument::Command { args })),
+                "entrypoint" => arguments.extend(self.argv(key, value).map(|args| Argument::Entrypoint { args })),
+                "working_dir" => match value.as_str() {
+                    Some(path) if path.starts_with('/') && path.chars().all(|chr| chr.is_alphanumeric() || "/_-.".contains(chr)) => {
+                        arguments.push(Argument::Workdir { path: path.to_string() })
+                    },
+                    _ => self.skip(key, "only absolute paths of letters, digits, `/`, `_`, `-` and `.` are supported"),
+                },
+                "networks" => self.service_networks(key, value, &mut arguments),
+                "user" => match scalar(value) {
+                    Some(user) if user.split(':').count() <= 2 && user.split(':').all(is_user) => {
+                        arguments.push(Argument::User { user })
+                    },
+                    _ => self.skip(key, "expected `user[:group]` of names or numeric ids"),
+                },
+                _ => self.skip(key, NO_EQUIVALENT),
+            }
+        }
+
+        if let Some((key, value)) = depends_on {
+            self.depends_on(key, value, &arguments);
+        }
+
+        let manifest = match (build, image) {
+            (Some((key, build)), image) => {
+                if let Some((key, _)) = image {
+                    self.skip(key, "a container is either built or pulled, `build` is used");
+                }
+                self.build(key, build)?
+            },
+            (None, Some((key, image))) => match scalar(image) {
+                Some(image) if !image.starts_with("Dockerfile") && image.chars().all(Self::is_image_char) => {
+                    Manifest::Image(image)
+                },
+                _ => {
+                    self.skip(key, "only letters, digits, `:`, `_`, `/` and `.` are allowed in an image");
+                    return None
+                },
+            },
+            (None, None) => {
+                self.skip(key, "expected `image` or `build`");
+                return None
+            },
+        };
+
+        Some(Container {
+            name: name.to_string(),
+            manifest,
+            arguments
+        })
+    }
+
+    /// A top-level network, only the first subnet of its `ipam` configuration can be kept.
+    fn network(&mut self, name: &str, network: &Value) -> Option<Network> {
+        let key = format!("networks.{}", name);
+        if name == "default" {
+            self.skip(key, "containers without a `network` are on the engine's default network");
+            return None
+        } else if !is_name(name) {
+            self.skip(key, "invalid network name, only letters, digits, `_` and `-` are allowed");
+            return None
+        }
+
+        let mut imported = Network { name: name.to_string(), driver: None, subnet: None, internal: false };
+        let fields = match network {
+            Value::Null => return Some(imported),
+            Value::Mapping(fields) => fields,
+            _ => {
+                self.skip(key, "expected a mapping");
+                return None
+            },
+        };
+        for (field, value) in fields {
+            let field = field.as_str().unwrap_or_default();
+            let key = format!("{}.{}", key, field);
+            match (field, value) {
+                ("driver", Value::String(driver)) if driver.chars().all(|chr| chr.is_ascii_alphanumeric() || "_-./:".contains(chr)) => {
+                    imported.driver = Some(driver.clone())
+                },
+                ("driver", _) => self.skip(key, "invalid network driver, expected a name like bridge or overlay"),
+                ("internal", Value::Bool(internal)) => imported.internal = *internal,
+                ("internal", _) => self.skip(key, "expected true or false"),
+                ("ipam", Value::Mapping(ipam)) => imported.subnet = self.ipam(key, ipam),
+                _ => self.skip(key, NO_EQUIVALENT),
+            }
+        }
+        Some(imported)
+    }
+
+    fn ipam(&mut self, key: String, ipam: &Mapping) -> Option<crate::parser::Subnet> {
+        let mut subnet = None;
+        for (field, value) in ipam {
+            let field = field.as_str().unwrap_or_default();
+            let key = format!("{}.{}", key, field);
+            let configs = match (field, value) {
+                ("config", Value::Sequence(configs)) => configs,
+                _ => {
+                    self.skip(key, NO_EQUIVALENT);
+                    continue
+                },
+            };
+            for (index, config) in configs.iter().enumerate() {
+                let key = format!("{}[{}]", key, index);
+                if index > 0 {
+                    self.skip(key, "a network has a single subnet");
+                    continue
+                }
+                for (field, value) in config.as_mapping().into_iter().flatten() {
+                    let key = format!("{}.{}", key, field.as_str().unwrap_or_default());
+                    match (field.as_str().unwrap_or_default(), value.as_str().map(str::parse::<crate::parser::Subnet>)) {
+                        ("subnet", Some(Ok(value))) => subnet = Some(value),
+                        ("subnet", Some(Err(error))) => self.skip(key, error.to_string()),
+                        _ => self.skip(key, NO_EQUIVALENT),
+                    }
+                }
+            }
+        }
+        subnet
+    }
+
+    /// The networks of a service as a list or a mapping, the aliases given on any of them apply to all.
+    fn service_networks(&mut self, key: String, value: &Value, arguments: &mut Vec<Argument>) {
+        let networks: Vec<(String, &str, Option<&Value>)> = match value {
+            Value::Sequence(names) => names.iter().enumerate()
+                .map(|(index, name)| (format!("{}[{}]", key, index), name.as_str().unwrap_or_default(), None))
+                .collect(),
+            Value::Mapping(names) => names.iter()
+                .map(|(name, options)| {
+                    let name = name.as_str().unwrap_or_default();
+                    (format!("{}.{}", key, name), name, Some(options))
+                })
+                .collect(),
+            _ => return self.skip(key, "expected a list or a mapping"),
+        };
+
+        let mut aliases = Vec::new();
+        for (key, name, options) in networks {
+            if !self.networks.iter().any(|network| network == name) {
+                self.skip(key, "only networks declared in the top-level `networks` are supported");
+                continue
+            }
+            arguments.push(Argument::Network { name: name.to_string() });
+            for (field, value) in options.and_then(Value::as_mapping).into_iter().flatten() {
+                let key = format!("{}.{}", key, field.as_str().unwrap_or_default());
+                match (field.as_str().unwrap_or_default(), value) {
+                    ("aliases", Value::Sequence(names)) => for (index, alias) in names.iter().enumerate() {
+                        match alias.as_str() {
+                            Some(alias) if aliases.contains(&alias) => {},
+                            Some(alias) if is_name(alias) => aliases.push(alias),
+                            _ => self.skip(format!("{}[{}]", key, index), "invalid alias, only letters, digits, `_` and `-` are allowed"),
+                        }
+                    },
+                    _ => self.skip(key, NO_EQUIVALENT),
+                }
+            }
+        }
+        arguments.extend(aliases.into_iter().map(|name| Argument::Alias { name: name.to_string() }));
+    }
+
+    fn is_image_char(chr: char) -> bool {
+        chr.is_alphanumeric() || ":_/.".contains(chr)
+    }
+
+    /// Translates every item of a list, reporting the ones `translate` cannot.
+    fn each<F>(&mut self, key: String, value: &Value, arguments: &mut Vec<Argument>, mut translate: F)
+        where F: FnMut(&mut Self, String, &Value) -> Option<Argument>
+    {
+        match value {
+            Value::Sequence(items) => for (index, item) in items.iter().enumerate() {
+                arguments.extend(translate(self, format!("{}[{}]", key, index), item));
+            },
+            _ => self.skip(key, "expected a list"),
+        }
+    }
+
+    /// Crab builds from the directory it runs in, with a Dockerfile named `Dockerfile*`.
+    fn build(&mut self, key: String, build: &Value) -> Option<Manifest> {
+        let (context, dockerfile) = match build {
+            Value::String(context) => (context.as_str(), "Dockerfile".to_string()),
+            Value::Mapping(build) => {
+                let mut dockerfile = "Dockerfile".to_string();
+                let mut context = ".";
+                for (field, value) in build {
+                    match (field.as_str().unwrap_or_default(), value) {
+                        ("context", Value::String(value)) => context = value,
+                        ("dockerfile", Value::String(value)) => dockerfile = value.clone(),
+                        (field, _) => self.skip(format!("{}.{}", key, field), NO_EQUIVALENT),
+                    }
+                }
+                (context, dockerfile)
+            },
+            _ => {
+                self.skip(key, "expected a build context or a mapping");
+                return None
+            },
+        };
+
+        if context != "." && context != "./" {
+            self.skip(key, "only the current directory can be the build context");
+            return None
+        }
+        match dockerfile.starts_with("Dockerfile") && dockerfile.chars().all(Self::is_image_char) {
+            true => Some(Manifest::File(dockerfile)),
+            false => {
+                self.skip(key, format!("the Dockerfile has to be named `Dockerfile*`, not `{}`", dockerfile));
+                None
+            },
+        }
+    }
+
+    fn port(&mut self, key: String, value: &Value) -> Option<Argument> {
+        let translated = match value {
+            Value::Mapping(port) => self.long_port(&key, port),
+            value => match scalar(value) {
+                Some(port) => publish(&port),
+                None => Err("expected a port".to_string()),
+            },
+        };
+        translated.map_err(|reason| self.skip(key, reason)).ok()
+    }
+
+    fn long_port(&mut self, key: &str, port: &Mapping) -> Result<Argument, String> {
+        let mut host = String::new();
+        let mut outer = None;
+        let mut inner = None;
+        let mut protocol = String::new();
+        for (field, value) in port {
+            match (field.as_str().unwrap_or_default(), scalar(value)) {
+                ("target", Some(value)) => inner = Some(value),
+                ("published", Some(value)) => outer = Some(value),
+                ("host_ip", Some(value)) if value.contains(':') => host = format!("[{}]:", value),
+                ("host_ip", Some(value)) => host = format!("{}:", value),
+                ("protocol", Some(value)) => protocol = format!("/{}", value),
+                (field, _) => self.skip(format!("{}.{}", key, field), NO_EQUIVALENT),
+            }
+        }
+        match inner {
+            Some(inner) => publish(&format!("{}{}:{}{}", host, outer.as_deref().unwrap_or("0"), inner, protocol)),
+            None => Err("expected a `target` port".to_string()),
+        }
+    }
+
+    fn expose(&mut self, key: String, value: &Value) -> Option<Argument> {
+        let exposed = match scalar(value) {
+            Some(port) => crate::parser::Argument::parse_expose_at(port.as_bytes())
+                .map(|port| Argument::from(&port))
+                .map_err(|(error, _)| error.to_string()),
+            None => Err("expected a port".to_string()),
+        };
+        exposed.map_err(|reason| self.skip(key, reason)).ok()
+    }
+
+    fn volume(&mut self, key: String, value: &Value) -> Option<Argument> {
+        let volume = match value {
+            Value::String(volume) if !volume.contains(':') => {
+                self.skip(key, "anonymous volumes are not supported");
+                return None
+            },
+            Value::String(volume) => volume.clone(),
+            Value::Mapping(volume) => {
+                let (mut source, mut mount, mut options) = (None, None, Vec::new());
+                for (field, value) in volume {
+                    match (field.as_str().unwrap_or_default(), value) {
+                        ("type", Value::String(kind)) if kind == "bind" || kind == "volume" => {},
+                        ("type", _) => {
+                            self.skip(key, "only bind mounts and named volumes are supported");
+                            return None
+                        },
+                        ("source", Value::String(value)) => source = Some(value.clone()),
+                        ("target", Value::String(value)) => mount = Some(value.clone()),
+                        ("read_only", Value::Bool(true)) => options.push("ro"),
+                        ("read_only", Value::Bool(false)) => {},
+                        (field, _) => self.skip(format!("{}.{}", key, field), NO_EQUIVALENT),
+                    }
+                }
+                match (source, mount) {
+                    (Some(source), Some(mount)) if options.is_empty() => format!("{}:{}", source, mount),
+                    (Some(source), Some(mount)) => format!("{}:{}:{}", source, mount, options.join(",")),
+                    _ => {
+                        self.skip(key, "expected a `source` and a `target`");
+                        return None
+                    },
+                }
+            },
+            _ => {
+                self.skip(key, "expected a volume");
+                return None
+            },
+        };
+
+        crate::parser::Argument::parse_volume_at(volume.as_bytes())
+            .map(|volume| Argument::from(&volume))
+            .map_err(|(error, _)| self.skip(key, error.to_string()))
+            .ok()
+    }
+
+    fn volume_from(&mut self, key: String, value: &Value) -> Option<Argument> {
+        match value.as_str() {
+            Some(name) if is_name(name) => Some(Argument::VolumeFrom { name: name.to_string() }),
+            Some(name) if name.contains(':') => {
+                self.skip(key, "access modes and `container:` references are not supported");
+                None
+            },
+            _ => {
+                self.skip(key, "expected the name of a service");
+                None
+            },
+        }
+    }
+
+    fn profiles(&mut self, key: String, value: &Value) -> Option<Argument> {
+        let items = match value {
+            Value::Sequence(items) => items,
+            _ => {
+                self.skip(key, "expected a list");
+                return None
+            },
+        };
+        let mut names = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            match item.as_str() {
+                Some(name) if is_name(name) => names.push(name.to_string()),
+                _ => self.skip(format!("{}[{}]", key, index), "only letters, digits, `_` and `-` are allowed in a profile"),
+            }
+        }
+        match names.is_empty() {
+            true => None,
+            false => Some(Argument::Profiles { names }),
+        }
+    }
+
+    /// A `command` or `entrypoint`, a list of arguments or a string split like a shell would.
+    /// An empty entrypoint list resets the one of the image, which a Crabfile writes as `entrypoint: ""`.
+    fn argv(&mut self, key: String, value: &Value) -> Option<Vec<String>> {
+        let args = match value {
+            Value::String(line) => crate::parser::split_argv(line.as_bytes()).map_err(|error| error.to_string()),
+            Value::Sequence(items) if items.is_empty() && key.ends_with(".entrypoint") => Ok(vec![String::new()]),
+            Value::Sequence(items) if !items.is_empty() => items.iter()
+                .map(scalar)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| "expected a list of strings".to_string()),
+            _ => Err("expected a command".to_string()),
+        };
+        args.map_err(|reason| self.skip(key, reason)).ok()
+    }
+
+    /// A Crabfile only orders containers by `volume-from`, so other dependencies cannot be kept.
+    fn depends_on(&mut self, key: String, value: &Value, arguments: &[Argument]) {
+        let dependencies: Vec<(String, &str, Option<&Value>)> = match value {
+            Value::Sequence(names) => names.iter().enumerate()
+                .map(|(index, name)| (format!("{}[{}]", key, index), name.as_str().unwrap_or_default(), None))
+                .collect(),
+            Value::Mapping(names) => names.iter()
+                .map(|(name, options)| {
+                    let name = name.as_str().unwrap_or_default();
+                    (format!("{}.{}", key, name), name, Some(options))
+                })
+                .collect(),
+            _ => return self.skip(key, "expected a list or a mapping"),
+        };
+
+        for (key, name, options) in dependencies {
+            let volume_from = arguments.iter().any(|argument| matches!(argument, Argument::VolumeFrom { name: from } if from == name));
+            let started = options.and_then(|options| options.get("condition")).is_none_or(|condition| condition == "service_started");
+            match (volume_from, started) {
+                (true, true) => {},
+                (true, false) => self.skip(format!("{}.condition", key), "containers only wait for others to start"),
+                (false, _) => self.skip(key, "only dependencies that are also in `volumes_from` are supported"),
+            }
+        }
+    }
+
+    fn environment(&mut self, key: String, value: &Value, arguments: &mut Vec<Argument>) {
+        let variables: Vec<(String, String, Option<String>)> = match value {
+            Value::Mapping(variables) => variables.iter()
+                .map(|(name, value)| {
+                    let name = scalar(name).unwrap_or_default();
+                    (format!("{}.{}", key, name), name, scalar(value))
+                })
+                .collect(),
+            Value::Sequence(variables) => variables.iter().enumerate()
+                .map(|(index, variable)| {
+                    let variable = variable.as_str().unwrap_or_default();
+                    let (name, value) = match variable.split_once('=') {
+                        Some((name, value)) => (name, Some(value.to_string())),
+                        None => (variable, None),
+                    };
+                    (format!("{}[{}]", key, index), name.to_string(), value)
+                })
+                .collect(),
+            _ => return self.skip(key, "expected a mapping or a list"),
+        };
+
+        for (key, name, value) in variables {
+            match value {
+                _ if !matches!(crate::parser::Argument::parse_env_key::<nom::error::Error<&[u8]>>(name.as_bytes()), Ok((b"", _))) => {
+                    self.skip(key, "invalid variable name")
+                },
+                None => self.skip(key, "values taken from the host environment are not supported"),
+                Some(value) if value.contains(['\n', '\r', '\0']) || (value.contains('"') && value.contains('\'')) => {
+                    self.skip(key, "the value cannot be written on a single `env:` line")
+                },
+                Some(value) => arguments.push(Argument::Env { key: name, value }),
+            }
+        }
+    }
+
+    fn env_file(&mut self, key: String, value: &Value) -> Option<Argument> {
+        let path = match value {
+            Value::Mapping(file) => file.get("path").and_then(Value::as_str),
+            value => value.as_str(),
+        };
+        match path {
+            Some(path) if !path.is_empty() && path.chars().all(|chr| chr.is_ascii_alphanumeric() || "/_-.~".contains(chr)) => {
+                Some(Argument::EnvFile { path: path.to_string() })
+            },
+            _ => {
+                self.skip(key, "only paths of letters, digits, `/`, `_`, `-`, `.` and `~` are supported");
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::parser::ast::Crabfile;
+    use super::{import, Error};
+
+    const COMPOSE: &str = indoc::indoc! {r#"
+        version: "3.8"
+        services:
+          web:
+            build:
+              context: .
+              dockerfile: Dockerfile.web
+            ports:
+              - "80:8080"
+              - 443:8443
+              - "9000"
+              - "127.0.0.1:8000:8000"
+              - target: 5000
+                published: 5001
+              - "[::1]::53/udp"
+              - "3000-3005"
+              - "8080:80/icmp"
+            expose:
+              - 3000
+              - "4000-4010"
+              - "4020-4010"
+            volumes:
+              - /srv/static:/var/www
+              - ./data:/data
+              - /var/log:/var/log:ro
+              - type: volume
+                source: cache
+                target: /cache
+                read_only: true
+              - /tmp:/tmp:delegated
+              - static
+            volumes_from:
+              - cache
+            depends_on:
+              - cache
+              - db
+            environment:
+              RUST_LOG: debug
+              DEBUG: true
+              HOME:
+            env_file: .env
+            command: ["cargo", "watch", "-x", "run --release"]
+            entrypoint: []
+            working_dir: /workspace
+            user: "1000:1000"
+            networks:
+              - frontend
+              - backend
+              - outside
+            restart: always
+          cache:
+            image: redis:7
+            environment:
+              - MAXMEMORY=64mb
+            command: redis-server "--save 60 1
+            networks:
+              backend:
+                aliases: [redis, redis, "redis.local"]
+                ipv4_address: 172.20.0.10
+          broken name:
+            image: alpine
+        networks:
+          default: {}
+          frontend:
+          backend:
+            driver: bridge
+            internal: true
+            ipam:
+              config:
+                - subnet: 172.20.0.0/16
+                  gateway: 172.20.0.1
+                - subnet: 172.21.0.0/16
+          public:
+            external: true
+        volumes:
+          cache:
+          logs:
+            driver: local
+    "#};
+
+    #[test]
+    fn test_import() {
+        let imported = import(COMPOSE).unwrap();
+
+        assert_eq!(imported.crabfile.to_string(), indoc::indoc! {"
+            @network frontend:
+
+            @network backend:
+                driver: bridge
+                subnet: 172.20.0.0/16
+                internal: true
+
+            @network public:
+
+            @web:
+                from: Dockerfile.web
+                port: 80:8080
+                port: 443:8443
+                port: 0:9000
+                port: 127.0.0.1:8000:8000
+                port: 5001:5000
+                port: [::1]:0:53/udp
+                expose: 3000
+                expose: 4000-4010
+                volume: /srv/static:/var/www
+                volume: ./data:/data
+                volume: /var/log:/var/log:ro
+                volume: cache:/cache:ro
+                volume-from: cache
+                env: RUST_LOG=debug
+                env: DEBUG=true
+                env-file: .env
+                command: cargo watch -x 'run --release'
+                entrypoint: ''
+                workdir: /workspace
+                user: 1000:1000
+                network: frontend
+                network: backend
+
+            @cache:
+                from: redis:7
+                env: MAXMEMORY=64mb
+                network: backend
+                alias: redis
+        "});
+        assert_eq!(imported.unsupported.iter().map(ToString::to_string).collect::<Vec<_>>(), vec![
+            "services.web.ports[6]: port ranges published on random host ports are not supported",
+            "services.web.ports[7]: invalid protocol, expected tcp, udp or sctp",
+            "services.web.expose[2]: invalid port range, the first port is greater than the last",
+            "services.web.volumes[4]: invalid volume option, expected one of ro, rw, z, Z, cached",
+            "services.web.volumes[5]: anonymous volumes are not supported",
+            "services.web.environment.HOME: values taken from the host environment are not supported",
+            "services.web.networks[2]: only networks declared in the top-level `networks` are supported",
+            "services.web.restart: no Crabfile equivalent",
+            "services.web.depends_on[1]: only dependencies that are also in `volumes_from` are supported",
+            "services.cache.command: unterminated quote",
+            "services.cache.networks.backend.aliases[2]: invalid alias, only letters, digits, `_` and `-` are allowed",
+            "services.cache.networks.backend.ipv4_address: no Crabfile equivalent",
+            "services.broken name: invalid container name, only letters, digits, `_` and `-` are allowed",
+            "networks.default: containers without a `network` are on the engine's default network",
+            "networks.backend.ipam.config[0].gateway: no Crabfile equivalent",
+            "networks.backend.ipam.config[1]: a network has a single subnet",
+            "networks.public.external: no Crabfile equivalent",
+            "volumes.logs: no Crabfile equivalent",
+        ]);
+    }
+
+    #[test]
+    fn test_output_parses() {
+        let imported = import(COMPOSE).unwrap();
+        let source = imported.crabfile.to_string();
+        let parser = Parser::parse(source.as_bytes()).unwrap();
+
+        assert_eq!(parser.validate(), Ok(()));
+        assert_eq!(Crabfile::from(&parser), imported.crabfile);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let imported = import("services:\n  app:\n    image: app:latest\n    build: ../app\n").unwrap();
+
+        assert!(imported.crabfile.containers.is_empty());
+        assert_eq!(imported.unsupported.iter().map(ToString::to_string).collect::<Vec<_>>(), vec![
+            "services.app.image: a container is either built or pulled, `build` is used",
+            "services.app.build: only the current directory can be the build context",
+        ]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(import("services: [web]"), Err(Error::Invalid { .. })));
+        assert!(matches!(import("services: {web: "), Err(Error::Syntax(_))));
+        assert_eq!(import("").unwrap_err().to_string(), "the compose file is empty");
+    }
+}
@@ -0,0 +1,42 @@
+//! Translation between docker compose files and Crabfiles.
+
+mod import;
+mod export;
+
+pub use import::{import, Import};
+pub use export::export;
+
+/// A part of a compose file the Crabfile has no way to express.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    /// Path of the key, like `services.web.ports[1]`.
+    pub key: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Syntax(serde_yaml::Error),
+    Invalid {
+        key: String,
+        message: String
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(err) => write!(f, "invalid YAML, {}", err),
+            Error::Invalid { key, message } if key.is_empty() => write!(f, "{}", message),
+            Error::Invalid { key, message } => write!(f, "`{}`: {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
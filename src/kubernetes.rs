@@ -0,0 +1,452 @@
+//! Kubernetes manifests for the containers of a Crabfile.
+
+use serde_yaml::{Mapping, Value};
+use crate::parser::VolumeKind;
+use crate::parser::ast::{Crabfile, Container, Manifest, Argument, PortRange, Protocol};
+
+const NAME_LABEL: &str = "app.kubernetes.io/name";
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+
+/// The manifests for a Crabfile, with what could not be carried over to the cluster.
+#[derive(Debug)]
+pub struct Export {
+    /// Multi-document YAML, one resource per document.
+    pub manifests: String,
+    pub warnings: Vec<String>,
+}
+
+/// Writes a Deployment per container and a Service per published or exposed port, every port of a range
+/// getting its own. A Service listens on every address of a node, the host address of a port is left out.
+///
+/// Volumes with an absolute source become `hostPath` volumes, named ones a PersistentVolumeClaim
+/// of that name, `ro` a read-only mount. Paths relative to the Crabfile or the home directory do not
+/// exist on the nodes, they are left out and warned about. A pod cannot mount the volumes of another one, so `volume-from` mounts the volumes
+/// the other container declares in the Crabfile, the same host paths and claims, into this pod as well;
+/// volumes that only come from its image cannot be shared and are warned about. Containers built
+/// from a Dockerfile use the image `crab-<name>` `crab up` tags, which has to be loaded into the cluster.
+/// `env-file` is not inlined, its variables may be secrets. `entrypoint` becomes the `command` of the
+/// container and `command` its `args`; `user` only carries over as numeric ids. Every pod shares the
+/// cluster network, so `@network` blocks with their `network` and `alias` lines are left out and warned about.
+pub fn export(crabfile: &Crabfile) -> Export {
+    let mut warnings = Vec::new();
+    let mut claims = Vec::new();
+    let mut resources = Vec::new();
+
+    for container in &crabfile.containers {
+        let volumes = volumes(crabfile, container, &mut warnings);
+        for (source, ..) in &volumes {
+            if !source.starts_with('/') && !claims.contains(source) {
+                claims.push(source.clone());
+            }
+        }
+
+        resources.push(deployment(container, &volumes, &mut warnings));
+        resources.extend(services(container));
+    }
+
+    let documents = claims.iter().map(|claim| claim_resource(claim)).chain(resources)
+        .map(|resource| serde_yaml::to_string(&resource).expect("a manifest serializes to YAML"))
+        .collect::<Vec<_>>();
+
+    Export {
+        manifests: documents.join("---\n"),
+        warnings
+    }
+}
+
+/// A DNS label Kubernetes accepts as the name of a resource.
+fn resource_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-").trim_matches('-').to_string()
+}
+
+fn mapping<K: Into<Value>, I: IntoIterator<Item=(&'static str, K)>>(entries: I) -> Value {
+    Value::Mapping(entries.into_iter().map(|(key, value)| (Value::from(key), value.into())).collect())
+}
+
+fn metadata(name: &str, container: &str) -> Value {
+    mapping([
+        ("name", Value::from(name)),
+        ("labels", labels(container)),
+    ])
+}
+
+fn labels(container: &str) -> Value {
+    mapping([
+        (NAME_LABEL, resource_name(container)),
+        (MANAGED_BY_LABEL, "crab".to_string()),
+    ])
+}
+
+/// The `(source, mount, read-only)` volumes of the container, followed by those it takes from others.
+fn volumes(crabfile: &Crabfile, container: &Container, warnings: &mut Vec<String>) -> Vec<(String, String, bool)> {
+    let mut volumes = Vec::new();
+    let mut visited = vec![container.name.as_str()];
+    let mut queue = vec![container];
+    let mut next = 0;
+    while let Some(current) = queue.get(next).copied() {
+        next += 1;
+        for argument in &current.arguments {
+            match argument {
+                Argument::Volume { mount, .. } if volumes.iter().any(|(_, other, _)| other == mount) => {},
+                Argument::Volume { source, .. } if is_relative(source) && current.name == container.name => warnings.push(format!(
+                    "`{}` of container `{}` is left out, paths relative to the Crabfile or the home directory do not exist on the nodes",
+                    argument, current.name
+                )),
+                Argument::Volume { source, .. } if is_relative(source) => warnings.push(format!(
+                    "`{}`, which container `{}` takes from `{}` through `volume-from`, is left out, paths relative to \
+                    the Crabfile or the home directory do not exist on the nodes",
+                    argument, container.name, current.name
+                )),
+                Argument::Volume { source, mount, options } => {
+                    volumes.push((source.clone(), mount.clone(), options.iter().any(|option| option == "ro")));
+                },
+                Argument::VolumeFrom { name } if !visited.contains(&name.as_str()) => {
+                    visited.push(name);
+                    match crabfile.containers.iter().find(|other| other.name == *name) {
+                        Some(other) if other.arguments.iter().any(|argument| matches!(argument, Argument::Volume { .. })) => {
+                            queue.push(other)
+                        },
+                        _ => warnings.push(format!(
+                            "container `{}` takes volumes from `{}`, which declares none in the Crabfile, so nothing is shared",
+                            container.name, name
+                        )),
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+    volumes
+}
+
+/// `runAsUser` and `runAsGroup` of a `user:` of numeric ids, `None` when it names a user or group.
+fn security_context(user: &str) -> Option<Value> {
+    let mut ids = user.split(':').map(str::parse::<u32>);
+    match (ids.next()?.ok()?, ids.next()) {
+        (user, None) => Some(mapping([("runAsUser", user)])),
+        (user, Some(Ok(group))) => Some(mapping([("runAsUser", user), ("runAsGroup", group)])),
+        (_, Some(Err(_))) => None,
+    }
+}
+
+/// Whether `source` is a path relative to the Crabfile or the home directory.
+fn is_relative(source: &str) -> bool {
+    VolumeKind::of(source.as_bytes()) == VolumeKind::Bind && !source.starts_with('/')
+}
+
+fn volume_name(index: usize) -> String {
+    format!("volume-{}", index)
+}
+
+fn deployment(container: &Container, volumes: &[(String, String, bool)], warnings: &mut Vec<String>) -> Value {
+    let name = resource_name(&container.name);
+    let image = match &container.manifest {
+        Manifest::Image(image) => image.clone(),
+        Manifest::File(file) => {
+            warnings.push(format!(
+                "container `{}` is built from {}, build it as `crab-{}` and load it into the cluster",
+                container.name, file, container.name
+            ));
+            format!("crab-{}", container.name)
+        },
+    };
+
+    let mut spec = Mapping::new();
+    spec.insert("name".into(), name.clone().into());
+    spec.insert("image".into(), image.into());
+    if let Manifest::File(_) = container.manifest {
+        spec.insert("imagePullPolicy".into(), "Never".into());
+    }
+
+    let ports = ports(container).into_iter()
+        .map(|(_, port, protocol, _)| match protocol {
+            Protocol::Tcp => mapping([("containerPort", Value::from(port))]),
+            protocol => mapping([("containerPort", Value::from(port)), ("protocol", protocol_name(protocol).into())]),
+        })
+        .collect::<Vec<_>>();
+    if !ports.is_empty() {
+        spec.insert("ports".into(), Value::Sequence(ports));
+    }
+
+    let mut env = Vec::new();
+    for argument in &container.arguments {
+        match argument {
+            Argument::Env { key, value } => env.push(mapping([("name", key.as_str()), ("value", value.as_str())])),
+            Argument::EnvFile { path } => warnings.push(format!(
+                "`env-file: {}` of container `{}` is left out, create a ConfigMap or Secret from it", path, container.name
+            )),
+            Argument::PublishPort { host: Some(_), .. } => warnings.push(format!(
+                "the host address of `{}` of container `{}` is left out, the Service listens on every node address",
+                argument, container.name
+            )),
+            Argument::Entrypoint { args } if args.iter().all(String::is_empty) => warnings.push(format!(
+                "`{}` of container `{}` is left out, a pod cannot clear the entrypoint of its image", argument, container.name
+            )),
+            Argument::Entrypoint { args } => {
+                spec.insert("command".into(), args.iter().map(String::as_str).collect::<Vec<_>>().into());
+            },
+            Argument::Command { args } => {
+                spec.insert("args".into(), args.iter().map(String::as_str).collect::<Vec<_>>().into());
+            },
+            Argument::Workdir { path } => {
+                spec.insert("workingDir".into(), path.as_str().into());
+            },
+            Argument::User { user } => match security_context(user) {
+                Some(context) => {
+                    spec.insert("securityContext".into(), context);
+                },
+                None => warnings.push(format!(
+                    "`{}` of container `{}` is left out, a pod only runs as numeric user and group ids", argument, container.name
+                )),
+            },
+            Argument::Network { .. } => warnings.push(format!(
+                "`{}` of container `{}` is left out, every pod shares the cluster network", argument, container.name
+            )),
+            Argument::Alias { .. } => warnings.push(format!(
+                "`{}` of container `{}` is left out, other pods reach it by the names of its Services", argument, container.name
+            )),
+            _ => {},
+        }
+    }
+    if !env.is_empty() {
+        spec.insert("env".into(), Value::Sequence(env));
+    }
+
+    let mounts = volumes.iter().enumerate()
+        .map(|(index, (_, mount, read_only))| match read_only {
+            true => mapping([("name", Value::from(volume_name(index))), ("mountPath", mount.as_str().into()), ("readOnly", true.into())]),
+            false => mapping([("name", Value::from(volume_name(index))), ("mountPath", mount.as_str().into())]),
+        })
+        .collect::<Vec<_>>();
+    let pod_volumes = volumes.iter().enumerate()
+        .map(|(index, (source, ..))| match source.starts_with('/') {
+            true => mapping([
+                ("name", Value::from(volume_name(index))),
+                ("hostPath", mapping([("path", source.as_str()), ("type", "DirectoryOrCreate")])),
+            ]),
+            false => mapping([
+                ("name", Value::from(volume_name(index))),
+                ("persistentVolumeClaim", mapping([("claimName", resource_name(source))])),
+            ]),
+        })
+        .collect::<Vec<_>>();
+    if !mounts.is_empty() {
+        spec.insert("volumeMounts".into(), Value::Sequence(mounts));
+    }
+
+    let mut pod = Mapping::new();
+    pod.insert("containers".into(), Value::Sequence(vec![Value::Mapping(spec)]));
+    if !pod_volumes.is_empty() {
+        pod.insert("volumes".into(), Value::Sequence(pod_volumes));
+    }
+
+    mapping([
+        ("apiVersion", Value::from("apps/v1")),
+        ("kind", "Deployment".into()),
+        ("metadata", metadata(&name, &container.name)),
+        ("spec", mapping([
+            ("replicas", Value::from(1)),
+            ("selector", mapping([("matchLabels", mapping([(NAME_LABEL, name.as_str())]))])),
+            ("template", mapping([
+                ("metadata", mapping([("labels", labels(&container.name))])),
+                ("spec", Value::Mapping(pod)),
+            ])),
+        ])),
+    ])
+}
+
+/// The `(port, target port, protocol, published)` of every port of the container, published ones first.
+fn ports(container: &Container) -> Vec<(u16, u16, Protocol, bool)> {
+    let published = container.arguments.iter().flat_map(|argument| match argument {
+        Argument::PublishPort { outer, inner, protocol, .. } if *outer == PortRange::single(0) => {
+            inner.ports().map(|inner| (inner, inner, *protocol, true)).collect()
+        },
+        Argument::PublishPort { outer, inner, protocol, .. } => {
+            outer.ports().zip(inner.ports()).map(|(outer, inner)| (outer, inner, *protocol, true)).collect()
+        },
+        _ => Vec::new(),
+    });
+    let exposed = container.arguments.iter().flat_map(|argument| match argument {
+        Argument::ExposePort { port, protocol } => port.ports().map(|port| (port, port, *protocol, false)).collect(),
+        _ => Vec::new(),
+    });
+    published.chain(exposed).collect()
+}
+
+fn protocol_name(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Tcp => "TCP",
+        Protocol::Udp => "UDP",
+        Protocol::Sctp => "SCTP",
+    }
+}
+
+/// Published ports are reachable from outside the cluster through a node port, exposed ones only inside it.
+fn services(container: &Container) -> Vec<Value> {
+    let name = resource_name(&container.name);
+    ports(container).into_iter()
+        .map(|(port, target, protocol, published)| {
+            // The same port can be published for TCP and UDP.
+            let (service, port) = match protocol {
+                Protocol::Tcp => (format!("{}-{}", name, port), mapping([("port", port), ("targetPort", target)])),
+                protocol => (format!("{}-{}-{}", name, port, protocol), mapping([
+                    ("port", Value::from(port)),
+                    ("targetPort", target.into()),
+                    ("protocol", protocol_name(protocol).into()),
+                ])),
+            };
+            mapping([
+                ("apiVersion", Value::from("v1")),
+                ("kind", "Service".into()),
+                ("metadata", metadata(&service, &container.name)),
+                ("spec", mapping([
+                    ("type", Value::from(if published { "NodePort" } else { "ClusterIP" })),
+                    ("selector", mapping([(NAME_LABEL, name.as_str())])),
+                    ("ports", Value::Sequence(vec![port])),
+                ])),
+            ])
+        })
+        .collect()
+}
+
+fn claim_resource(claim: &str) -> Value {
+    let name = resource_name(claim);
+    mapping([
+        ("apiVersion", Value::from("v1")),
+        ("kind", "PersistentVolumeClaim".into()),
+        ("metadata", mapping([
+            ("name", Value::from(name.as_str())),
+            ("labels", mapping([(MANAGED_BY_LABEL, "crab")])),
+        ])),
+        ("spec", mapping([
+            ("accessModes", Value::Sequence(vec!["ReadWriteOnce".into()])),
+            ("resources", mapping([("requests", mapping([("storage", "1Gi")]))])),
+        ])),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use crate::parser::Parser;
+    use crate::parser::ast::Crabfile;
+    use super::export;
+
+    const CRABFILE: &str = indoc::indoc! {"
+        @network backend:
+
+        @web_app:
+            from: Dockerfile.web
+            port: 80:8080
+            port: 127.0.0.1:53:5353/udp
+            expose: 9090-9091
+            volume-from: data
+            volume-from: cache
+            env: RUST_LOG=debug
+            env-file: .env
+            entrypoint: /bin/web --verbose
+            command: serve --port 8080
+            workdir: /srv/web
+            user: 1000:1000
+
+        @data:
+            from: busybox:latest
+            user: postgres
+            volume: /srv/data:/data
+            volume: pgdata:/var/lib/postgresql:ro
+            volume: ./seed:/docker-entrypoint-initdb.d
+
+        @cache:
+            from: redis:7
+            network: backend
+            alias: redis
+    "};
+
+    fn documents(manifests: &str) -> Vec<serde_yaml::Value> {
+        serde_yaml::Deserializer::from_str(manifests).map(|document| serde_yaml::Value::deserialize(document).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_resources() {
+        let crabfile = Crabfile::from(&Parser::parse(CRABFILE.as_bytes()).unwrap());
+        let documents = documents(&export(&crabfile).manifests);
+
+        let resources = documents.iter()
+            .map(|document| format!("{} {}", document["kind"].as_str().unwrap(), document["metadata"]["name"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(resources, vec![
+            "PersistentVolumeClaim pgdata",
+            "Deployment web-app",
+            "Service web-app-80",
+            "Service web-app-53-udp",
+            "Service web-app-9090",
+            "Service web-app-9091",
+            "Deployment data",
+            "Deployment cache",
+        ]);
+    }
+
+    #[test]
+    fn test_deployment() {
+        let crabfile = Crabfile::from(&Parser::parse(CRABFILE.as_bytes()).unwrap());
+        let documents = documents(&export(&crabfile).manifests);
+
+        let web = &documents[1];
+        assert_eq!(web["spec"]["selector"]["matchLabels"]["app.kubernetes.io/name"], "web-app");
+        let pod = &web["spec"]["template"]["spec"];
+        let container = &pod["containers"][0];
+        assert_eq!(container["image"], "crab-web_app");
+        assert_eq!(container["imagePullPolicy"], "Never");
+        assert_eq!(container["ports"][0]["containerPort"], 8080);
+        assert_eq!(container["ports"][1]["protocol"], "UDP");
+        assert_eq!(container["env"][0]["name"], "RUST_LOG");
+        assert_eq!(container["command"], serde_yaml::to_value(["/bin/web", "--verbose"]).unwrap());
+        assert_eq!(container["args"], serde_yaml::to_value(["serve", "--port", "8080"]).unwrap());
+        assert_eq!(container["workingDir"], "/srv/web");
+        assert_eq!(container["securityContext"]["runAsGroup"], 1000);
+        assert_eq!(container["volumeMounts"][1]["mountPath"], "/var/lib/postgresql");
+        assert_eq!(container["volumeMounts"][1]["readOnly"], true);
+        assert_eq!(container["volumeMounts"].as_sequence().unwrap().len(), 2);
+        assert_eq!(pod["volumes"][0]["hostPath"]["path"], "/srv/data");
+        assert_eq!(pod["volumes"][1]["persistentVolumeClaim"]["claimName"], "pgdata");
+
+        let service = &documents[2];
+        assert_eq!(service["spec"]["type"], "NodePort");
+        assert_eq!(service["spec"]["ports"][0]["port"], 80);
+        assert_eq!(service["spec"]["ports"][0]["targetPort"], 8080);
+        assert_eq!(documents[3]["spec"]["ports"][0]["protocol"], "UDP");
+        assert_eq!(documents[4]["spec"]["type"], "ClusterIP");
+    }
+
+    #[test]
+    fn test_warnings() {
+        let crabfile = Crabfile::from(&Parser::parse(CRABFILE.as_bytes()).unwrap());
+
+        assert_eq!(export(&crabfile).warnings, vec![
+            "container `web_app` takes volumes from `cache`, which declares none in the Crabfile, so nothing is shared",
+            "`volume: ./seed:/docker-entrypoint-initdb.d`, which container `web_app` takes from `data` through `volume-from`, \
+            is left out, paths relative to the Crabfile or the home directory do not exist on the nodes",
+            "container `web_app` is built from Dockerfile.web, build it as `crab-web_app` and load it into the cluster",
+            "the host address of `port: 127.0.0.1:53:5353/udp` of container `web_app` is left out, the Service listens on every node address",
+            "`env-file: .env` of container `web_app` is left out, create a ConfigMap or Secret from it",
+            "`volume: ./seed:/docker-entrypoint-initdb.d` of container `data` is left out, paths relative to the Crabfile or the home directory do not exist on the nodes",
+            "`user: postgres` of container `data` is left out, a pod only runs as numeric user and group ids",
+            "`network: backend` of container `cache` is left out, every pod shares the cluster network",
+            "`alias: redis` of container `cache` is left out, other pods reach it by the names of its Services",
+        ]);
+    }
+
+    #[test]
+    fn test_relative_volume_from() {
+        let input = "@seed:\n    from: alpine\n    volume: ./seed:/seed\n\n@db:\n    from: postgres:16\n    volume-from: seed\n";
+        let crabfile = Crabfile::from(&Parser::parse(input.as_bytes()).unwrap());
+
+        let export = export(&crabfile);
+        assert!(!export.manifests.contains("/seed"));
+        assert_eq!(export.warnings, vec![
+            "`volume: ./seed:/seed` of container `seed` is left out, paths relative to the Crabfile or the home directory do not exist on the nodes",
+            "`volume: ./seed:/seed`, which container `db` takes from `seed` through `volume-from`, is left out, paths relative to \
+            the Crabfile or the home directory do not exist on the nodes",
+        ]);
+    }
+}
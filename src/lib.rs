@@ -0,0 +1,8 @@
+#[macro_use] extern crate nom;
+
+pub mod parser;
+pub mod runtime;
+#[cfg(feature = "serde")]
+pub mod compose;
+#[cfg(feature = "serde")]
+pub mod kubernetes;
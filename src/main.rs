@@ -1,7 +1,5 @@
-#[macro_use] extern crate nom;
-
-mod parser;
+mod cli;
 
 fn main() {
-    unimplemented!()
+    std::process::exit(cli::run(std::env::args().skip(1)))
 }
@@ -0,0 +1,520 @@
+//! An owned copy of a parsed Crabfile, independent of the source it was parsed from.
+//!
+//! With the `serde` feature the model serializes to a stable shape, arguments are tagged by their Crabfile key:
+//! `{ "type": "port", "outer": 80, "inner": 8080 }`. A port range is written as a string, `"8000-8010"`, and the
+//! host address and protocol of a port only when they are set. A subnet is written as a string too, `"172.20.0.0/16"`.
+
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+pub use super::{PortRange, Protocol, Subnet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Crabfile {
+    pub shell: String,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub networks: Vec<Network>,
+    pub containers: Vec<Container>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Network {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub driver: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub subnet: Option<Subnet>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_false"))]
+    pub internal: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Container {
+    pub name: String,
+    /// Kept as `{ image: ... }` in YAML too, instead of a `!image` tag.
+    #[cfg_attr(feature = "serde", serde(with = "serde_yaml::with::singleton_map"))]
+    pub manifest: Manifest,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub arguments: Vec<Argument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Manifest {
+    File(String),
+    Image(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "type", rename_all = "kebab-case"))]
+pub enum Argument {
+    Volume {
+        source: String,
+        mount: String,
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+        options: Vec<String>
+    },
+    #[cfg_attr(feature = "serde", serde(rename = "port"))]
+    PublishPort {
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+        host: Option<IpAddr>,
+        outer: PortRange,
+        inner: PortRange,
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_tcp"))]
+        protocol: Protocol
+    },
+    #[cfg_attr(feature = "serde", serde(rename = "expose"))]
+    ExposePort {
+        port: PortRange,
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_tcp"))]
+        protocol: Protocol
+    },
+    VolumeFrom {
+        name: String
+    },
+    Env {
+        key: String,
+        value: String
+    },
+    EnvFile {
+        path: String
+    },
+    Profiles {
+        names: Vec<String>
+    },
+    Command {
+        args: Vec<String>
+    },
+    Entrypoint {
+        args: Vec<String>
+    },
+    Workdir {
+        path: String
+    },
+    User {
+        user: String
+    },
+    Network {
+        name: String
+    },
+    Alias {
+        name: String
+    },
+}
+
+/// Indentation of the lines of a block.
+pub(in crate::parser) const INDENT: &str = "    ";
+
+fn split(args: &[u8]) -> Vec<String> {
+    super::split_argv(args).expect("the parser only accepts arguments that split")
+}
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+impl From<&super::Parser<'_>> for Crabfile {
+    fn from(parser: &super::Parser<'_>) -> Self {
+        Crabfile {
+            shell: string(parser.shell().path()),
+            networks: parser.networks().map(Network::from).collect(),
+            containers: parser.containers().map(Container::from).collect()
+        }
+    }
+}
+
+impl From<&super::Network<'_>> for Network {
+    fn from(network: &super::Network<'_>) -> Self {
+        Network {
+            name: string(network.name()),
+            driver: network.driver().map(string),
+            subnet: network.subnet(),
+            internal: network.internal(),
+        }
+    }
+}
+
+impl From<&super::Container<'_>> for Container {
+    fn from(container: &super::Container<'_>) -> Self {
+        Container {
+            name: string(container.name()),
+            manifest: container.manifest().into(),
+            arguments: container.arguments().iter().enumerate()
+                .map(|(index, argument)| Argument::from(argument).in_directory(container.directory(index)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&super::Manifest<'_>> for Manifest {
+    fn from(manifest: &super::Manifest<'_>) -> Self {
+        match manifest {
+            super::Manifest::File(file) => Manifest::File(string(file)),
+            super::Manifest::Image(image) => Manifest::Image(string(image)),
+        }
+    }
+}
+
+impl From<&super::Argument<'_>> for Argument {
+    fn from(argument: &super::Argument<'_>) -> Self {
+        match argument {
+            super::Argument::Volume { source, mount, options } => Argument::Volume {
+                source: string(source),
+                mount: string(mount),
+                options: super::Argument::split_volume_options(options).map(string).collect()
+            },
+            super::Argument::PublishPort { host, outer, inner, protocol } => Argument::PublishPort {
+                host: *host,
+                outer: *outer,
+                inner: *inner,
+                protocol: *protocol
+            },
+            super::Argument::ExposePort { port, protocol } => Argument::ExposePort { port: *port, protocol: *protocol },
+            super::Argument::VolumeFrom { name } => Argument::VolumeFrom { name: string(name) },
+            super::Argument::Env { key, value } => Argument::Env { key: string(key), value: string(value) },
+            super::Argument::EnvFile { path } => Argument::EnvFile { path: string(path) },
+            super::Argument::Profiles { names } => Argument::Profiles {
+                names: super::Argument::split_profiles(names).map(string).collect()
+            },
+            super::Argument::Command { args } => Argument::Command { args: split(args) },
+            super::Argument::Entrypoint { args } => Argument::Entrypoint { args: split(args) },
+            super::Argument::Workdir { path } => Argument::Workdir { path: string(path) },
+            super::Argument::User { user } => Argument::User { user: string(user) },
+            super::Argument::Network { name } => Argument::Network { name: string(name) },
+            super::Argument::Alias { name } => Argument::Alias { name: string(name) },
+        }
+    }
+}
+
+impl Argument {
+    /// Makes the relative host paths of an argument written in the file of `directory` relative to the root
+    /// Crabfile, since the owned copy no longer knows which file each argument comes from.
+    fn in_directory(self, directory: &Path) -> Self {
+        match self {
+            Argument::Volume { source, mount, options } if source.starts_with('.') => Argument::Volume {
+                source: from_root(directory, &source),
+                mount,
+                options
+            },
+            Argument::EnvFile { path } if !path.starts_with('/') && !path.starts_with('~') => Argument::EnvFile {
+                path: from_root(directory, &path)
+            },
+            argument => argument,
+        }
+    }
+}
+
+/// `path`, relative to `directory`, made relative to the root Crabfile. A path starting with `.` still does, so
+/// that `./data` in `shared/Crabfile` becomes `./shared/data` and stays a bind mount, `db.env` becomes `shared/db.env`.
+fn from_root(directory: &Path, path: &str) -> String {
+    if directory.as_os_str().is_empty() {
+        return path.to_string();
+    }
+
+    let mut resolved = PathBuf::new();
+    for component in directory.join(path).components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if matches!(resolved.components().next_back(), Some(Component::Normal(_))) => {
+                resolved.pop();
+            },
+            component => resolved.push(component),
+        }
+    }
+    let resolved = resolved.to_string_lossy();
+    match resolved.as_ref() {
+        "" => ".".to_string(),
+        _ if path.starts_with('.') && !resolved.starts_with('.') && !resolved.starts_with('/') => format!("./{}", resolved),
+        _ => resolved.into_owned(),
+    }
+}
+
+/// Writes the Crabfile in its canonical form: the `@shell` block only when it is not the default,
+/// the `@network` blocks before the containers, four spaces of indentation and a blank line between blocks.
+impl std::fmt::Display for Crabfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut separate = false;
+        if self.shell.as_bytes() != super::Shell::default().path() {
+            writeln!(f, "@shell:")?;
+            writeln!(f, "{}path: {}", INDENT, self.shell)?;
+            separate = true;
+        }
+
+        for network in &self.networks {
+            if separate {
+                writeln!(f)?;
+            }
+            write!(f, "{}", network)?;
+            separate = true;
+        }
+        for container in &self.containers {
+            if separate {
+                writeln!(f)?;
+            }
+            write!(f, "{}", container)?;
+            separate = true;
+        }
+        Ok(())
+    }
+}
+
+/// `internal: false` is the default and is left out.
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "@network {}:", self.name)?;
+        if let Some(driver) = &self.driver {
+            writeln!(f, "{}driver: {}", INDENT, driver)?;
+        }
+        if let Some(subnet) = &self.subnet {
+            writeln!(f, "{}subnet: {}", INDENT, subnet)?;
+        }
+        if self.internal {
+            writeln!(f, "{}internal: true", INDENT)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "@{}:", self.name)?;
+        writeln!(f, "{}{}", INDENT, self.manifest)?;
+        for argument in &self.arguments {
+            writeln!(f, "{}{}", INDENT, argument)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Manifest::File(file) | Manifest::Image(file) => write!(f, "from: {}", file),
+        }
+    }
+}
+
+impl std::fmt::Display for Argument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Argument::Volume { source, mount, options } if options.is_empty() => write!(f, "volume: {}:{}", source, mount),
+            Argument::Volume { source, mount, options } => write!(f, "volume: {}:{}:{}", source, mount, options.join(",")),
+            Argument::PublishPort { host, outer, inner, protocol } => {
+                write!(f, "port: {}", super::format_publish(*host, *outer, *inner, *protocol))
+            },
+            Argument::ExposePort { port, protocol } => write!(f, "expose: {}", super::format_expose(*port, *protocol)),
+            Argument::VolumeFrom { name } => write!(f, "volume-from: {}", name),
+            Argument::Env { key, value } => write!(f, "env: {}={}", key, quote(value)),
+            Argument::EnvFile { path } => write!(f, "env-file: {}", path),
+            Argument::Profiles { names } => write!(f, "profiles: {}", names.join(", ")),
+            Argument::Command { args } => write!(f, "command: {}", super::format_argv(args)),
+            Argument::Entrypoint { args } => write!(f, "entrypoint: {}", super::format_argv(args)),
+            Argument::Workdir { path } => write!(f, "workdir: {}", path),
+            Argument::User { user } => write!(f, "user: {}", user),
+            Argument::Network { name } => write!(f, "network: {}", name),
+            Argument::Alias { name } => write!(f, "alias: {}", name),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn is_tcp(protocol: &Protocol) -> bool {
+    *protocol == Protocol::Tcp
+}
+
+#[cfg(feature = "serde")]
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+/// A single port as a number, a range as a string.
+#[cfg(feature = "serde")]
+impl Serialize for PortRange {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.count() {
+            1 => serializer.serialize_u16(self.start()),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Written {
+            Port(u16),
+            Range(String),
+        }
+
+        match Written::deserialize(deserializer)? {
+            Written::Port(port) => Ok(PortRange::single(port)),
+            Written::Range(range) => range.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Subnet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Subnet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Protocol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Protocol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Quotes an `env:` value the parser would otherwise trim or cut at a comment.
+pub(in crate::parser) fn quote(value: &str) -> String {
+    let plain = !value.starts_with(['"', '\'', ' ', '\t']) && !value.ends_with([' ', '\t']) && !value.contains(" #");
+    match plain {
+        true => value.to_string(),
+        false if value.contains('"') => format!("'{}'", value),
+        false => format!("\"{}\"", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use super::{Crabfile, Network, Container, Manifest, Argument, PortRange, Protocol};
+
+    const CRABFILE: &str = indoc::indoc! {"
+        @shell:
+            path: /bin/zsh
+
+        @network backend:
+            subnet: 172.20.0.0/16
+            internal: true
+
+        @ubuntu:
+            from: ubuntu:latest
+            port: 80:8080
+            port: 127.0.0.1:5353:53/udp
+            volume-from: cache
+
+        @cache:
+            from: Dockerfile.cache
+            env: RUST_LOG=debug
+            expose: 7000-7002
+            network: backend
+            alias: redis
+    "};
+
+    fn crabfile() -> Crabfile {
+        Crabfile::from(&Parser::parse(CRABFILE.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_from_parser() {
+        assert_eq!(crabfile(), Crabfile {
+            shell: "/bin/zsh".to_string(),
+            networks: vec![
+                Network {
+                    name: "backend".to_string(),
+                    driver: None,
+                    subnet: Some("172.20.0.0/16".parse().unwrap()),
+                    internal: true,
+                },
+            ],
+            containers: vec![
+                Container {
+                    name: "ubuntu".to_string(),
+                    manifest: Manifest::Image("ubuntu:latest".to_string()),
+                    arguments: vec![
+                        Argument::PublishPort { host: None, outer: 80.into(), inner: 8080.into(), protocol: Protocol::Tcp },
+                        Argument::PublishPort {
+                            host: Some([127, 0, 0, 1].into()),
+                            outer: 5353.into(),
+                            inner: 53.into(),
+                            protocol: Protocol::Udp
+                        },
+                        Argument::VolumeFrom { name: "cache".to_string() },
+                    ],
+                },
+                Container {
+                    name: "cache".to_string(),
+                    manifest: Manifest::File("Dockerfile.cache".to_string()),
+                    arguments: vec![
+                        Argument::Env { key: "RUST_LOG".to_string(), value: "debug".to_string() },
+                        Argument::ExposePort { port: PortRange::new(7000, 7002).unwrap(), protocol: Protocol::Tcp },
+                        Argument::Network { name: "backend".to_string() },
+                        Argument::Alias { name: "redis".to_string() },
+                    ],
+                },
+            ]
+        });
+    }
+
+    #[test]
+    fn test_display_parses_back() {
+        let mut crabfile = crabfile();
+        crabfile.containers[1].arguments.push(Argument::Env { key: "GREETING".to_string(), value: " hello # world".to_string() });
+        let source = crabfile.to_string();
+
+        assert!(source.contains("    env: GREETING=\" hello # world\"\n"), "{}", source);
+        assert_eq!(Crabfile::from(&Parser::parse(source.as_bytes()).unwrap()), crabfile);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let json = serde_json::to_value(crabfile()).unwrap();
+        let yaml = serde_yaml::to_string(&crabfile()).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "shell": "/bin/zsh",
+            "networks": [
+                { "name": "backend", "subnet": "172.20.0.0/16", "internal": true }
+            ],
+            "containers": [
+                {
+                    "name": "ubuntu",
+                    "manifest": { "image": "ubuntu:latest" },
+                    "arguments": [
+                        { "type": "port", "outer": 80, "inner": 8080 },
+                        { "type": "port", "host": "127.0.0.1", "outer": 5353, "inner": 53, "protocol": "udp" },
+                        { "type": "volume-from", "name": "cache" }
+                    ]
+                },
+                {
+                    "name": "cache",
+                    "manifest": { "file": "Dockerfile.cache" },
+                    "arguments": [
+                        { "type": "env", "key": "RUST_LOG", "value": "debug" },
+                        { "type": "expose", "port": "7000-7002" },
+                        { "type": "network", "name": "backend" },
+                        { "type": "alias", "name": "redis" }
+                    ]
+                }
+            ]
+        }));
+        assert_eq!(serde_json::from_value::<Crabfile>(json).unwrap(), crabfile());
+        assert!(yaml.contains("  manifest:\n    image: ubuntu:latest\n"), "{}", yaml);
+        assert_eq!(serde_yaml::from_str::<Crabfile>(&yaml).unwrap(), crabfile());
+    }
+}
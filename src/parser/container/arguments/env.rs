@@ -0,0 +1,67 @@
+use super::Argument;
+use crate::parser::{SyntaxError, value_end};
+
+named!(pub(in super) env<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        key: call!(Argument::parse_env_key) >>
+        tag!("=") >>
+        value: call!(Argument::parse_env_value) >>
+        value_end >> (
+            Argument::Env {
+                key,
+                value
+            }
+        )
+    )
+);
+
+named!(pub(in super) env_file<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        path: call!(Argument::parse_env_file_path) >>
+        value_end >> (
+            Argument::EnvFile {
+                path
+            }
+        )
+    )
+);
+
+impl Argument<'_> {
+    /// A shell variable name: a letter or `_`, followed by letters, digits or `_`.
+    pub(crate) fn parse_env_key<'i, E: nom::error::ParseError<&'i [u8]>>(input: &'i [u8]) -> nom::IResult<&'i [u8], &'i [u8], E> {
+        let length = input.iter()
+            .take_while(|chr| chr.is_ascii_alphanumeric() || **chr == b'_')
+            .count();
+        match input.first() {
+            Some(chr) if length > 0 && !chr.is_ascii_digit() => Ok((&input[length..], &input[..length])),
+            _ => Err(nom::Err::Error(E::from_error_kind(input, nom::error::ErrorKind::AlphaNumeric))),
+        }
+    }
+
+    /// The rest of the line up to a trailing comment, or the text between a pair of quotes.
+    pub(crate) fn parse_env_value<'i, E: nom::error::ParseError<&'i [u8]>>(input: &'i [u8]) -> nom::IResult<&'i [u8], &'i [u8], E> {
+        let line = &input[..input.iter().position(|chr| b"\r\n\0".contains(chr)).unwrap_or(input.len())];
+        match line.first() {
+            Some(quote @ (b'"' | b'\'')) => match line[1..].iter().position(|chr| chr == quote) {
+                Some(end) => Ok((&input[end + 2..], &line[1..end + 1])),
+                None => Err(nom::Err::Error(E::from_error_kind(&input[line.len()..], nom::error::ErrorKind::Char))),
+            },
+            _ => {
+                let value = crate::parser::strip_comment(line);
+                let end = value.iter().rposition(|chr| *chr != b' ' && *chr != b'\t').map_or(0, |end| end + 1);
+                Ok((&input[end..], &value[..end]))
+            },
+        }
+    }
+
+    /// A relative or absolute path, also `~` and dots are allowed.
+    pub(crate) fn parse_env_file_path<'i, E: nom::error::ParseError<&'i [u8]>>(input: &'i [u8]) -> nom::IResult<&'i [u8], &'i [u8], E> {
+        let length = input.iter()
+            .take_while(|chr| chr.is_ascii_alphanumeric() || b"/_-.~".contains(chr))
+            .count();
+        match length {
+            0 => Err(nom::Err::Error(E::from_error_kind(input, nom::error::ErrorKind::AlphaNumeric))),
+            _ => Ok((&input[length..], &input[..length])),
+        }
+    }
+}
@@ -1,42 +1,137 @@
 mod volume;
 mod port;
-mod expose;
 mod volume_from;
+mod env;
+mod profiles;
+mod process;
+mod network;
 #[cfg(test)]
 mod tests;
 
-use super::{space, digit, line_feed};
+use std::net::IpAddr;
+use nom::combinator::consumed;
+use crate::parser::{ErrorKind, SyntaxError, IResult, line_feed, value, key};
 
-named!(pub argument<Argument>,
-    do_parse!(
-        arg: switch!(take_until!(":"),
-            b"volume" => call!(volume::volume) |
-            b"port" => call!(port::port) |
-            b"expose" => call!(expose::expose) |
-            b"volume-from" => call!(volume_from::volume_from)
-        ) >> (arg)
-    )
-);
+pub use port::{PortRange, Protocol, PortError, format_publish, format_expose};
+pub use volume::{VolumeKind, VolumeError};
+pub use process::{ArgvError, split_argv, format_argv};
 
-#[derive(Eq, PartialEq, Hash)]
+named!(pub argument<&[u8], Argument<'_>, SyntaxError<'_>>, terminated!(argument_value, line_feed));
+
+// An argument with its line, without the indentation, trailing comment and line break.
+named!(pub(in crate::parser) argument_line<&[u8], (&[u8], Argument<'_>), SyntaxError<'_>>, terminated!(call!(consumed(argument_value)), line_feed));
+
+/// The parser of the value after a key.
+type Value = for<'a> fn(&'a [u8]) -> IResult<'a, Argument<'a>>;
+
+/// An argument up to the end of its value, where a trailing comment may start. A value the parser of its key
+/// rejects without knowing better is wrong the way the kind next to it says.
+pub(in crate::parser) fn argument_value(input: &[u8]) -> IResult<'_, Argument<'_>> {
+    let key = key(input);
+    let (kind, parser): (ErrorKind, Value) = match key {
+        b"volume" => (ErrorKind::InvalidPath, volume::volume),
+        b"port" => (ErrorKind::InvalidPort, port::port),
+        b"expose" => (ErrorKind::InvalidPort, port::expose),
+        b"volume-from" => (ErrorKind::InvalidContainerName, volume_from::volume_from),
+        b"env" => (ErrorKind::InvalidEnvironment, env::env),
+        b"env-file" => (ErrorKind::InvalidEnvironment, env::env_file),
+        b"profiles" => (ErrorKind::InvalidProfiles, profiles::profiles),
+        b"command" => (ErrorKind::EmptyCommand, process::command),
+        b"entrypoint" => (ErrorKind::EmptyCommand, process::entrypoint),
+        b"workdir" => (ErrorKind::InvalidWorkdir, process::workdir),
+        b"user" => (ErrorKind::InvalidUser, process::user),
+        b"network" => (ErrorKind::InvalidNetworkName, network::network),
+        b"alias" => (ErrorKind::InvalidAlias, network::alias),
+        _ => {
+            let name = String::from_utf8_lossy(key).into_owned();
+            return Err(nom::Err::Failure(SyntaxError::new(ErrorKind::UnknownArgument { name }, key)))
+        },
+    };
+    value(kind, parser)(&input[key.len()..])
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Argument<'a> {
     Volume {
         source: &'a [u8],
-        mount: &'a [u8]
+        mount: &'a [u8],
+        options: &'a [u8]
     },
     PublishPort {
-        outer: u16,
-        inner: u16
+        host: Option<IpAddr>,
+        outer: PortRange,
+        inner: PortRange,
+        protocol: Protocol
     },
     ExposePort {
-        port: u16
+        port: PortRange,
+        protocol: Protocol
     },
     VolumeFrom {
         name: &'a [u8]
+    },
+    Env {
+        key: &'a [u8],
+        value: &'a [u8]
+    },
+    EnvFile {
+        path: &'a [u8]
+    },
+    Profiles {
+        names: &'a [u8]
+    },
+    /// Replaces the command of the image, see [`split_argv`] for its arguments.
+    Command {
+        args: &'a [u8]
+    },
+    Entrypoint {
+        args: &'a [u8]
+    },
+    Workdir {
+        path: &'a [u8]
+    },
+    /// `user[:group]`, by name or numeric id.
+    User {
+        user: &'a [u8]
+    },
+    /// A network of the `@network` blocks the container joins.
+    Network {
+        name: &'a [u8]
+    },
+    /// Another name the container is reached by on each of its networks.
+    Alias {
+        name: &'a [u8]
     }
 }
 
 impl<'a> Argument<'a> {
+    /// Whether a container declaring this argument replaces `inherited`, the same argument of the container it extends:
+    /// a volume mounted at the same place, the same container ports published, the same variable, the profiles,
+    /// the same argument a container only has once or an argument repeated.
+    pub(in crate::parser) fn replaces(&self, inherited: &Argument) -> bool {
+        match (self, inherited) {
+            (argument, inherited) if argument.is_single() => std::mem::discriminant(argument) == std::mem::discriminant(inherited),
+            (Argument::Volume { mount, .. }, Argument::Volume { mount: other, .. }) => mount == other,
+            (Argument::PublishPort { inner, protocol, .. }, Argument::PublishPort { inner: other, protocol: other_protocol, .. }) => {
+                inner == other && protocol == other_protocol
+            },
+            (Argument::Env { key, .. }, Argument::Env { key: other, .. }) => key == other,
+            (Argument::Profiles { .. }, Argument::Profiles { .. }) => true,
+            (argument, inherited) => argument == inherited,
+        }
+    }
+
+    /// Whether this argument repeats `earlier`, declared before it in the same container: the same argument again,
+    /// or a second one of those a container only has once.
+    pub(in crate::parser) fn repeats(&self, earlier: &Argument) -> bool {
+        self == earlier || (self.is_single() && std::mem::discriminant(self) == std::mem::discriminant(earlier))
+    }
+
+    /// `command`, `entrypoint`, `workdir` and `user`, which a container only has once.
+    fn is_single(&self) -> bool {
+        matches!(self, Argument::Command { .. } | Argument::Entrypoint { .. } | Argument::Workdir { .. } | Argument::User { .. })
+    }
+
     fn parse_to_u16(bytes: &[u8]) -> Result<u16, std::num::ParseIntError> {
         String::from_utf8_lossy(bytes).parse::<u16>()
     }
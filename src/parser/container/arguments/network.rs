@@ -0,0 +1,24 @@
+use super::Argument;
+use crate::parser::{SyntaxError, value_end};
+
+named!(pub(in super) network<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        name: call!(Argument::parse_volume_name) >>
+        value_end >> (
+            Argument::Network {
+                name
+            }
+        )
+    )
+);
+
+named!(pub(in super) alias<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        name: call!(Argument::parse_volume_name) >>
+        value_end >> (
+            Argument::Alias {
+                name
+            }
+        )
+    )
+);
@@ -1,16 +1,289 @@
-use super::{Argument, space, digit, line_feed};
-
-named!(pub(in super) port<Argument>,
-    do_parse!(
-        tag!(":") >>
-        space >>
-        outer: map_res!(take_until!(":"), Argument::parse_to_u16) >>
-        tag!(":") >>
-        inner: map_res!(terminated!(digit, line_feed), Argument::parse_to_u16) >> (
-            Argument::PublishPort {
-                outer,
-                inner
-            }
-        )
-    )
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+use super::Argument;
+use crate::parser::{SyntaxError, IResult, value_end};
+
+named!(pub(in super) port<&[u8], Argument<'_>, SyntaxError<'_>>,
+    terminated!(call!(Argument::parse_port_value, Argument::parse_publish_at), value_end)
+);
+
+named!(pub(in super) expose<&[u8], Argument<'_>, SyntaxError<'_>>,
+    terminated!(call!(Argument::parse_port_value, Argument::parse_expose_at), value_end)
 );
+
+/// A port, or an inclusive range of ports written `8000-8010`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PortRange {
+    start: u16,
+    end: u16,
+}
+
+impl PortRange {
+    /// The range from `start` to `end`, `None` when `end` comes before `start`.
+    pub fn new(start: u16, end: u16) -> Option<Self> {
+        match start <= end {
+            true => Some(PortRange { start, end }),
+            false => None,
+        }
+    }
+
+    pub fn single(port: u16) -> Self {
+        PortRange { start: port, end: port }
+    }
+
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// How many ports the range holds.
+    pub fn count(&self) -> usize {
+        usize::from(self.end - self.start) + 1
+    }
+
+    pub fn ports(&self) -> std::ops::RangeInclusive<u16> {
+        self.start..=self.end
+    }
+
+    pub fn overlaps(&self, other: &PortRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl From<u16> for PortRange {
+    fn from(port: u16) -> Self {
+        PortRange::single(port)
+    }
+}
+
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.start == self.end {
+            true => write!(f, "{}", self.start),
+            false => write!(f, "{}-{}", self.start, self.end),
+        }
+    }
+}
+
+impl std::str::FromStr for PortRange {
+    type Err = PortError;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        PortRange::parse(range.as_bytes()).map_err(|(error, _)| error)
+    }
+}
+
+impl PortRange {
+    fn parse(range: &[u8]) -> Result<Self, Invalid> {
+        let number = |start: usize, digits: &[u8]| match digits.iter().all(u8::is_ascii_digit) {
+            true => Argument::parse_to_u16(digits).map_err(|_| (PortError::Port, start..start + digits.len())),
+            false => Err((PortError::Port, start..start + digits.len())),
+        };
+        match range.iter().position(|chr| *chr == b'-') {
+            Some(dash) => {
+                let start = number(0, &range[..dash])?;
+                let end = number(dash + 1, &range[dash + 1..])?;
+                PortRange::new(start, end).ok_or((PortError::Range, 0..range.len()))
+            },
+            None => number(0, range).map(PortRange::single),
+        }
+    }
+}
+
+/// The transport protocol of a port, TCP unless written otherwise.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+            Protocol::Sctp => "sctp",
+        }
+    }
+
+    fn parse(protocol: &[u8]) -> Option<Self> {
+        match protocol {
+            b"tcp" => Some(Protocol::Tcp),
+            b"udp" => Some(Protocol::Udp),
+            b"sctp" => Some(Protocol::Sctp),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = PortError;
+
+    fn from_str(protocol: &str) -> Result<Self, Self::Err> {
+        Protocol::parse(protocol.as_bytes()).ok_or(PortError::Protocol)
+    }
+}
+
+/// What is wrong with the value of a `port:` or `expose:` argument.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PortError {
+    /// Not a number between 0 and 65535, or a part of the value is missing.
+    Port,
+    /// A range ending before it starts.
+    Range,
+    /// Host and container ranges of different lengths.
+    Lengths,
+    Host,
+    Protocol,
+}
+
+impl std::fmt::Display for PortError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortError::Port => write!(f, "invalid port, expected a number between 0 and 65535"),
+            PortError::Range => write!(f, "invalid port range, the first port is greater than the last"),
+            PortError::Lengths => write!(f, "the host and container port ranges have different lengths"),
+            PortError::Host => write!(f, "invalid host address, expected an IPv4 address or an IPv6 address in brackets"),
+            PortError::Protocol => write!(f, "invalid protocol, expected tcp, udp or sctp"),
+        }
+    }
+}
+
+impl std::error::Error for PortError {}
+
+/// What is wrong with a value, and the bytes of it that are.
+type Invalid = (PortError, Range<usize>);
+
+/// A published port written as `[host:]outer:inner[/protocol]`, the form of `docker run -p` and of a
+/// Crabfile, IPv6 hosts in brackets and the protocol only when it is not TCP.
+pub fn format_publish(host: Option<IpAddr>, outer: PortRange, inner: PortRange, protocol: Protocol) -> String {
+    let host = match host {
+        Some(IpAddr::V6(host)) => format!("[{}]:", host),
+        Some(IpAddr::V4(host)) => format!("{}:", host),
+        None => String::new(),
+    };
+    format!("{}{}:{}", host, outer, format_expose(inner, protocol))
+}
+
+/// An exposed port written as `port[/protocol]`, the protocol only when it is not TCP.
+pub fn format_expose(port: PortRange, protocol: Protocol) -> String {
+    match protocol {
+        Protocol::Tcp => port.to_string(),
+        protocol => format!("{}/{}", port, protocol),
+    }
+}
+
+impl Argument<'_> {
+    /// The value of a `port:` or `expose:` line, up to the trailing comment, failing on the part of it `parse` rejects.
+    fn parse_port_value(input: &[u8], parse: fn(&[u8]) -> Result<Argument<'static>, Invalid>) -> IResult<'_, Argument<'static>> {
+        let length = input.iter()
+            .take_while(|chr| !chr.is_ascii_whitespace() && **chr != b'\0')
+            .count();
+        let (value, rest) = input.split_at(length);
+        match parse(value) {
+            Ok(port) => Ok((rest, port)),
+            Err((error, range)) => Err(nom::Err::Failure(SyntaxError::new(error.into(), &value[range]))),
+        }
+    }
+
+    /// Parses `[host:]outer:inner[/protocol]`, an error pointing at the part of `value` that is wrong.
+    pub(crate) fn parse_publish_at(value: &[u8]) -> Result<Argument<'static>, Invalid> {
+        let (ports, protocol) = Self::split_protocol(value)?;
+        let (host, ports_start) = match ports.first() {
+            Some(b'[') => {
+                let close = ports.iter().position(|chr| *chr == b']').ok_or((PortError::Host, 0..ports.len()))?;
+                let host = std::str::from_utf8(&ports[1..close]).ok()
+                    .and_then(|host| host.parse::<Ipv6Addr>().ok())
+                    .ok_or((PortError::Host, 0..close + 1))?;
+                match ports.get(close + 1) {
+                    Some(b':') => (Some(IpAddr::V6(host)), close + 2),
+                    _ => return Err((PortError::Port, close + 1..ports.len())),
+                }
+            },
+            _ if ports.iter().filter(|chr| **chr == b':').count() >= 2 => {
+                let colon = ports.iter().position(|chr| *chr == b':').unwrap_or_default();
+                let host = std::str::from_utf8(&ports[..colon]).ok()
+                    .and_then(|host| host.parse::<Ipv4Addr>().ok())
+                    .ok_or((PortError::Host, 0..colon))?;
+                (Some(IpAddr::V4(host)), colon + 1)
+            },
+            _ => (None, 0),
+        };
+
+        let rest = &ports[ports_start..];
+        let colon = rest.iter().position(|chr| *chr == b':').ok_or((PortError::Port, ports_start..ports.len()))?;
+        let inner_start = ports_start + colon + 1;
+        let outer = PortRange::parse(&rest[..colon]).map_err(|(error, span)| (error, span.start + ports_start..span.end + ports_start))?;
+        let inner = PortRange::parse(&ports[inner_start..]).map_err(|(error, span)| (error, span.start + inner_start..span.end + inner_start))?;
+        if outer.count() != inner.count() {
+            return Err((PortError::Lengths, ports_start..ports.len()))
+        }
+        Ok(Argument::PublishPort { host, outer, inner, protocol })
+    }
+
+    /// Parses `port[/protocol]`, an error pointing at the part of `value` that is wrong.
+    pub(crate) fn parse_expose_at(value: &[u8]) -> Result<Argument<'static>, Invalid> {
+        let (port, protocol) = Self::split_protocol(value)?;
+        let port = PortRange::parse(port)?;
+        Ok(Argument::ExposePort { port, protocol })
+    }
+
+    fn split_protocol(value: &[u8]) -> Result<(&[u8], Protocol), Invalid> {
+        match value.iter().rposition(|chr| *chr == b'/') {
+            Some(slash) => Protocol::parse(&value[slash + 1..])
+                .map(|protocol| (&value[..slash], protocol))
+                .ok_or((PortError::Protocol, slash + 1..value.len())),
+            None => Ok((value, Protocol::Tcp)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use crate::parser::Argument;
+    use super::{PortRange, Protocol, PortError, format_publish};
+
+    fn error(value: &str) -> (PortError, &str) {
+        let (error, span) = Argument::parse_publish_at(value.as_bytes()).unwrap_err();
+        (error, &value[span])
+    }
+
+    #[test]
+    fn test_publish() {
+        let cases = [
+            ("127.0.0.1:5432:5432", Some("127.0.0.1"), PortRange::single(5432), PortRange::single(5432), Protocol::Tcp),
+            ("53:53/udp", None, PortRange::single(53), PortRange::single(53), Protocol::Udp),
+            ("8000-8010:9000-9010", None, PortRange::new(8000, 8010).unwrap(), PortRange::new(9000, 9010).unwrap(), Protocol::Tcp),
+            ("[::1]:80:8080/sctp", Some("::1"), PortRange::single(80), PortRange::single(8080), Protocol::Sctp),
+        ];
+        for (value, host, outer, inner, protocol) in cases {
+            let host = host.map(|host| host.parse::<IpAddr>().unwrap());
+            assert_eq!(Argument::parse_publish_at(value.as_bytes()), Ok(Argument::PublishPort { host, outer, inner, protocol }));
+            assert_eq!(format_publish(host, outer, inner, protocol), value);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error("80:http"), (PortError::Port, "http"));
+        assert_eq!(error("70000:80"), (PortError::Port, "70000"));
+        assert_eq!(error("8080"), (PortError::Port, "8080"));
+        assert_eq!(error("8010-8000:80"), (PortError::Range, "8010-8000"));
+        assert_eq!(error("8000-8010:80"), (PortError::Lengths, "8000-8010:80"));
+        assert_eq!(error("localhost:80:80"), (PortError::Host, "localhost"));
+        assert_eq!(error("[::g]:80:80"), (PortError::Host, "[::g]"));
+        assert_eq!(error("53:53/icmp"), (PortError::Protocol, "icmp"));
+    }
+}
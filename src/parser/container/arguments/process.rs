@@ -0,0 +1,221 @@
+use std::ops::Range;
+use super::Argument;
+use crate::parser::{PathLike, SyntaxError, IResult, value_end};
+
+named!(pub(in super) command<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        args: call!(Argument::parse_argv_value) >>
+        value_end >> (
+            Argument::Command {
+                args
+            }
+        )
+    )
+);
+
+named!(pub(in super) entrypoint<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        args: call!(Argument::parse_argv_value) >>
+        value_end >> (
+            Argument::Entrypoint {
+                args
+            }
+        )
+    )
+);
+
+named!(pub(in super) workdir<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        path: verify!(call!(Argument::parse_path), |path: &[u8]| path.starts_with(b"/")) >>
+        value_end >> (
+            Argument::Workdir {
+                path
+            }
+        )
+    )
+);
+
+named!(pub(in super) user<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        user: recognize!(pair!(Argument::parse_user_name, opt!(complete!(preceded!(char!(':'), Argument::parse_user_name))))) >>
+        value_end >> (
+            Argument::User {
+                user
+            }
+        )
+    )
+);
+
+/// What is wrong with the value of a `command:` or `entrypoint:` argument.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ArgvError {
+    /// A `'` or `"` without the one closing it.
+    Quote,
+    /// A `\` at the end of the line, escaping nothing.
+    Escape,
+    Empty,
+}
+
+impl std::fmt::Display for ArgvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgvError::Quote => write!(f, "unterminated quote"),
+            ArgvError::Escape => write!(f, "`\\` at the end of the line escapes nothing"),
+            ArgvError::Empty => write!(f, "expected a command, write `\"\"` for an empty one"),
+        }
+    }
+}
+
+impl std::error::Error for ArgvError {}
+
+/// What is wrong with a value, and the bytes of it that are.
+type Invalid = (ArgvError, Range<usize>);
+
+/// Splits a `command:` or `entrypoint:` value into its arguments the way a POSIX shell would, without
+/// expanding anything: whitespace separates arguments, `'` quotes everything up to the next `'`, `"` up to the
+/// next unescaped `"`, and `\` escapes the character after it. A `#` starting an argument begins a comment.
+pub fn split_argv(value: &[u8]) -> Result<Vec<String>, ArgvError> {
+    split_argv_at(value).map(|(args, _)| args).map_err(|(error, _)| error)
+}
+
+/// The arguments of `value` and where the last one ends, an error pointing at the part of `value` that is wrong.
+fn split_argv_at(value: &[u8]) -> Result<(Vec<String>, usize), Invalid> {
+    let mut args = Vec::new();
+    let mut end = 0;
+    let mut index = 0;
+    loop {
+        while matches!(value.get(index), Some(b' ' | b'\t')) {
+            index += 1;
+        }
+        if matches!(value.get(index), None | Some(b'#')) {
+            break
+        }
+
+        let mut arg = Vec::new();
+        while let Some(&chr) = value.get(index) {
+            match chr {
+                b' ' | b'\t' => break,
+                b'\'' => {
+                    let close = value[index + 1..].iter().position(|chr| *chr == b'\'')
+                        .ok_or((ArgvError::Quote, index..value.len()))?;
+                    arg.extend_from_slice(&value[index + 1..index + 1 + close]);
+                    index += close + 2;
+                },
+                b'"' => {
+                    let start = index;
+                    index += 1;
+                    loop {
+                        match (value.get(index), value.get(index + 1)) {
+                            (None, _) => return Err((ArgvError::Quote, start..value.len())),
+                            (Some(b'"'), _) => break,
+                            (Some(b'\\'), Some(escaped @ (b'"' | b'\\' | b'$' | b'`'))) => {
+                                arg.push(*escaped);
+                                index += 2;
+                            },
+                            (Some(chr), _) => {
+                                arg.push(*chr);
+                                index += 1;
+                            },
+                        }
+                    }
+                    index += 1;
+                },
+                b'\\' => match value.get(index + 1) {
+                    Some(escaped) => {
+                        arg.push(*escaped);
+                        index += 2;
+                    },
+                    None => return Err((ArgvError::Escape, index..index + 1)),
+                },
+                chr => {
+                    arg.push(chr);
+                    index += 1;
+                },
+            }
+        }
+        args.push(String::from_utf8_lossy(&arg).into_owned());
+        end = index;
+    }
+
+    match args.is_empty() {
+        true => Err((ArgvError::Empty, 0..value.len())),
+        false => Ok((args, end)),
+    }
+}
+
+/// Joins `args` into a value [`split_argv`] splits back into the same arguments, quoting only those that need it.
+pub fn format_argv<S: AsRef<str>>(args: &[S]) -> String {
+    args.iter()
+        .map(|arg| {
+            let arg = arg.as_ref();
+            let plain = !arg.is_empty() && !arg.starts_with('#')
+                && !arg.contains(|chr: char| chr.is_ascii_whitespace() || "'\"\\".contains(chr));
+            match plain {
+                true => arg.to_string(),
+                false if !arg.contains('\'') => format!("'{}'", arg),
+                false => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$").replace('`', "\\`")),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl<'a> Argument<'a> {
+    /// The arguments of a `command:` or `entrypoint:` line, up to the trailing comment [`split_argv`] finds,
+    /// failing on the part of the line it cannot split.
+    fn parse_argv_value(input: &'a [u8]) -> IResult<'a, &'a [u8]> {
+        let line = &input[..input.iter().position(|chr| b"\r\n\0".contains(chr)).unwrap_or(input.len())];
+        match split_argv_at(line) {
+            Ok((_, end)) => Ok((&input[end..], &input[..end])),
+            Err((error, range)) => Err(nom::Err::Failure(SyntaxError::new(error.into(), &line[range]))),
+        }
+    }
+
+    /// A user or group, by name or numeric id.
+    fn parse_user_name(input: &[u8]) -> IResult<'_, &[u8]> {
+        let length = input.iter()
+            .take_while(|chr| chr.is_ascii_alphanumeric() || b"_-.".contains(chr))
+            .count();
+        match input.first() {
+            Some(chr) if length > 0 && (chr.is_ascii_alphanumeric() || *chr == b'_') => Ok((&input[length..], &input[..length])),
+            _ => Err(nom::Err::Error(nom::error::make_error(input, nom::error::ErrorKind::AlphaNumeric))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_argv, split_argv_at, format_argv, ArgvError};
+
+    #[test]
+    fn test_split_argv() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("sleep infinity", &["sleep", "infinity"]),
+            ("sh -c 'echo $HOME # not a comment'", &["sh", "-c", "echo $HOME # not a comment"]),
+            (r#"echo "say \"hi\"" a\ b"#, &["echo", r#"say "hi""#, "a b"]),
+            (r#"printf "%s\n" it"'"s"#, &["printf", r"%s\n", "it's"]),
+            ("cargo watch  # rebuilds on change", &["cargo", "watch"]),
+            ("''", &[""]),
+        ];
+        for (value, args) in cases {
+            assert_eq!(split_argv(value.as_bytes()).unwrap(), args);
+            assert_eq!(split_argv(format_argv(args).as_bytes()).unwrap(), args);
+        }
+        assert_eq!(split_argv_at(b"cargo watch  # rebuilds").unwrap().1, "cargo watch".len());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(split_argv_at(b"sh -c 'echo"), Err((ArgvError::Quote, 6..11)));
+        assert_eq!(split_argv_at(br#"echo "a\""#), Err((ArgvError::Quote, 5..9)));
+        assert_eq!(split_argv_at(br"echo \"), Err((ArgvError::Escape, 5..6)));
+        assert_eq!(split_argv_at(b"  # nothing"), Err((ArgvError::Empty, 0..11)));
+    }
+
+    #[test]
+    fn test_format_argv() {
+        assert_eq!(format_argv(&["sleep", "infinity"]), "sleep infinity");
+        assert_eq!(format_argv(&["sh", "-c", "echo hi", ""]), "sh -c 'echo hi' ''");
+        assert_eq!(format_argv(&["it's $HOME"]), r#""it's \$HOME""#);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::parser::{SyntaxError, space0, value_end};
+use super::Argument;
+
+named!(pub(in super) profiles<&[u8], Argument<'_>, SyntaxError<'_>>,
+    do_parse!(
+        names: recognize!(pair!(
+            Argument::parse_volume_name,
+            many0!(complete!(tuple!(space0, char!(','), space0, Argument::parse_volume_name)))
+        )) >>
+        value_end >> (
+            Argument::Profiles {
+                names
+            }
+        )
+    )
+);
+
+impl<'a> Argument<'a> {
+    /// The names of a `profiles:` list.
+    pub(in crate::parser) fn split_profiles(names: &'a [u8]) -> impl Iterator<Item=&'a [u8]> {
+        names.split(|chr| *chr == b',').map(<[u8]>::trim_ascii)
+    }
+}
@@ -1,23 +1,53 @@
 use crate::parser::common::error_fmt;
-use super::{argument, Argument};
+use super::{argument, argument_line, Argument, PortRange, Protocol};
 
 impl<'a> std::fmt::Debug for Argument<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Argument::Volume { source, mount} => {
+            Argument::Volume { source, mount, options } => {
                 let source = String::from_utf8_lossy(source);
                 let mount = String::from_utf8_lossy(mount);
-                writeln!(f, "Argument::Volume {{ source: {}, mount: {} }}", source, mount)
+                let options = String::from_utf8_lossy(options);
+                writeln!(f, "Argument::Volume {{ source: {}, mount: {}, options: {} }}", source, mount, options)
             }
-            Argument::PublishPort { inner, outer } => {
-                writeln!(f, "Argument::PublishPort {{ inner: {}, outer: {} }}", inner, outer)
+            Argument::PublishPort { host, inner, outer, protocol } => {
+                writeln!(f, "Argument::PublishPort {{ host: {:?}, inner: {}, outer: {}, protocol: {} }}", host, inner, outer, protocol)
             }
-            Argument::ExposePort { port } => {
-                writeln!(f, "Argument::Port {{ port: {} }}", port)
+            Argument::ExposePort { port, protocol } => {
+                writeln!(f, "Argument::Port {{ port: {}, protocol: {} }}", port, protocol)
             }
             Argument::VolumeFrom { name } => {
                 writeln!(f, "Argument::VolumeFrom {{ name: {} }}", String::from_utf8_lossy(name))
             }
+            Argument::Env { key, value } => {
+                let key = String::from_utf8_lossy(key);
+                let value = String::from_utf8_lossy(value);
+                writeln!(f, "Argument::Env {{ key: {}, value: {} }}", key, value)
+            }
+            Argument::EnvFile { path } => {
+                writeln!(f, "Argument::EnvFile {{ path: {} }}", String::from_utf8_lossy(path))
+            }
+            Argument::Profiles { names } => {
+                writeln!(f, "Argument::Profiles {{ names: {} }}", String::from_utf8_lossy(names))
+            }
+            Argument::Command { args } => {
+                writeln!(f, "Argument::Command {{ args: {} }}", String::from_utf8_lossy(args))
+            }
+            Argument::Entrypoint { args } => {
+                writeln!(f, "Argument::Entrypoint {{ args: {} }}", String::from_utf8_lossy(args))
+            }
+            Argument::Workdir { path } => {
+                writeln!(f, "Argument::Workdir {{ path: {} }}", String::from_utf8_lossy(path))
+            }
+            Argument::User { user } => {
+                writeln!(f, "Argument::User {{ user: {} }}", String::from_utf8_lossy(user))
+            }
+            Argument::Network { name } => {
+                writeln!(f, "Argument::Network {{ name: {} }}", String::from_utf8_lossy(name))
+            }
+            Argument::Alias { name } => {
+                writeln!(f, "Argument::Alias {{ name: {} }}", String::from_utf8_lossy(name))
+            }
         }
     }
 }
@@ -40,11 +70,27 @@ mod test_volume {
 
         let result = argument(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, argument) = result.unwrap();
         assert_eq!(argument, Argument::Volume {
             source: b"/path/to/directory",
-            mount: b"/path/to/mount/point"
+            mount: b"/path/to/mount/point",
+            options: b""
+        });
+    }
+
+    #[test]
+    fn test_parse_relative_with_options() {
+        let input = b"volume: ../config/nginx.conf:/etc/nginx/nginx.conf:ro,Z\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Volume {
+            source: b"../config/nginx.conf",
+            mount: b"/etc/nginx/nginx.conf",
+            options: b"ro,Z"
         });
     }
 
@@ -85,11 +131,29 @@ mod test_publish_port {
 
         let result = argument(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, argument) = result.unwrap();
         assert_eq!(argument, Argument::PublishPort {
-            outer: 80,
-            inner: 8080
+            host: None,
+            outer: 80.into(),
+            inner: 8080.into(),
+            protocol: Protocol::Tcp
+        });
+    }
+
+    #[test]
+    fn test_parse_full() {
+        let input = b"port: [::1]:8000-8001:9000-9001/udp\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::PublishPort {
+            host: Some("::1".parse().unwrap()),
+            outer: PortRange::new(8000, 8001).unwrap(),
+            inner: PortRange::new(9000, 9001).unwrap(),
+            protocol: Protocol::Udp
         });
     }
 
@@ -121,10 +185,22 @@ mod test_exposed_port {
 
         let result = argument(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, argument) = result.unwrap();
         assert_eq!(argument, Argument::ExposePort {
-            port: 8080
+            port: 8080.into(),
+            protocol: Protocol::Tcp
+        });
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let result = argument(b"expose: 7000-7010/sctp\0");
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        assert_eq!(result.unwrap().1, Argument::ExposePort {
+            port: PortRange::new(7000, 7010).unwrap(),
+            protocol: Protocol::Sctp
         });
     }
 
@@ -147,7 +223,7 @@ mod test_volume_from {
 
         let result = argument(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, argument) = result.unwrap();
         assert_eq!(argument, Argument::VolumeFrom {
             name: b"cache_container"
@@ -162,3 +238,180 @@ mod test_volume_from {
         assert!(result.is_err());
     }
 }
+
+mod test_env {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = b"env: DATABASE_URL=postgres://db:5432/crab?ssl=true\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Env {
+            key: b"DATABASE_URL",
+            value: b"postgres://db:5432/crab?ssl=true"
+        });
+    }
+
+    #[test]
+    fn test_parse_with_comment() {
+        let input = b"env: GREETING=hello world  # shown on login\n";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Env {
+            key: b"GREETING",
+            value: b"hello world"
+        });
+    }
+
+    #[test]
+    fn test_parse_quoted() {
+        let input = b"env: GREETING=\" hello # world \"\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Env {
+            key: b"GREETING",
+            value: b" hello # world "
+        });
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        let result = argument(b"env: EMPTY=\0");
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        assert_eq!(result.unwrap().1, Argument::Env { key: b"EMPTY", value: b"" });
+    }
+
+    #[test]
+    fn test_parse_invalid_env() {
+        assert!(argument(b"env: 1KEY=value\0").is_err());
+        assert!(argument(b"env: KEY\0").is_err());
+        assert!(argument(b"env: KEY=\"unterminated\0").is_err());
+    }
+}
+
+mod test_env_file {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = b"env-file: ./config/.env\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::EnvFile {
+            path: b"./config/.env"
+        });
+    }
+
+    #[test]
+    fn test_parse_invalid_env_file() {
+        assert!(argument(b"env-file: config|.env\0").is_err());
+    }
+}
+
+mod test_profiles {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = b"profiles: frontend,backend ,  docs\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Profiles {
+            names: b"frontend,backend ,  docs"
+        });
+        let names = Argument::split_profiles(b"frontend,backend ,  docs").collect::<Vec<_>>();
+        assert_eq!(names, vec![&b"frontend"[..], b"backend", b"docs"]);
+    }
+
+    #[test]
+    fn test_parse_invalid_profiles() {
+        assert!(argument(b"profiles: front end\0").is_err());
+        assert!(argument(b"profiles: frontend,\0").is_err());
+        assert!(argument(b"profiles: \0").is_err());
+    }
+}
+
+mod test_process {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        let input = b"command: sh -c 'echo # done'\0";
+
+        let result = argument(input);
+
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, argument) = result.unwrap();
+        assert_eq!(argument, Argument::Command {
+            args: b"sh -c 'echo # done'"
+        });
+    }
+
+    #[test]
+    fn test_parse_command_with_comment() {
+        let (_, (line, argument)) = argument_line(b"command: sh -c 'echo # done' # run it\n").unwrap();
+        assert_eq!(line, b"command: sh -c 'echo # done'");
+        assert_eq!(argument, Argument::Command { args: b"sh -c 'echo # done'" });
+
+        let (_, (line, _)) = argument_line(b"entrypoint:  /entrypoint.sh \"# a\" # b\n").unwrap();
+        assert_eq!(line, b"entrypoint:  /entrypoint.sh \"# a\"");
+    }
+
+    #[test]
+    fn test_parse_entrypoint() {
+        let (_, argument) = argument(b"entrypoint: \"\"\0").unwrap();
+
+        assert_eq!(argument, Argument::Entrypoint {
+            args: b"\"\""
+        });
+    }
+
+    #[test]
+    fn test_parse_workdir_and_user() {
+        assert_eq!(argument(b"workdir: /workspace/.cache\0").unwrap().1, Argument::Workdir { path: b"/workspace/.cache" });
+        assert_eq!(argument(b"user: 1000:1000\0").unwrap().1, Argument::User { user: b"1000:1000" });
+        assert_eq!(argument(b"user: www-data\0").unwrap().1, Argument::User { user: b"www-data" });
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(argument(b"command: echo 'hi\0").is_err());
+        assert!(argument(b"command: \0").is_err());
+        assert!(argument(b"workdir: workspace\0").is_err());
+        assert!(argument(b"user: 1000:\0").is_err());
+        assert!(argument(b"user: -1\0").is_err());
+    }
+}
+
+mod test_network {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(argument(b"network: backend\0").unwrap().1, Argument::Network { name: b"backend" });
+        assert_eq!(argument(b"alias: api-v2\0").unwrap().1, Argument::Alias { name: b"api-v2" });
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(argument(b"network: back end\0").is_err());
+        assert!(argument(b"alias: api.local\0").is_err());
+    }
+}
@@ -1,25 +1,180 @@
-use super::{Argument, space, line_feed};
-use crate::parser::PathLike;
-
-named!(pub(in super) volume<Argument>,
-    do_parse!(
-        tag!(":") >>
-        space >>
-        source: verify!(take_until!(":"), Argument::verify_path) >>
-        tag!(":") >>
-        mount: terminated!(Argument::parse_path, line_feed) >> (
-            Argument::Volume {
-                source,
-                mount
+use std::ops::Range;
+use super::Argument;
+use crate::parser::{PathLike, SyntaxError, IResult, value_end};
+
+named!(pub(in super) volume<&[u8], Argument<'_>, SyntaxError<'_>>, terminated!(call!(Argument::parse_volume_value), value_end));
+
+/// The options a volume can be mounted with.
+const OPTIONS: &[&[u8]] = &[b"ro", b"rw", b"z", b"Z", b"cached"];
+
+/// Whether a volume mounts a directory of the host or a volume the engine manages.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VolumeKind {
+    /// An absolute path, a path relative to the Crabfile, starting with `.`, or one in the home directory, `~`.
+    Bind,
+    /// A volume name, letters, digits, `_`, `.` and `-`.
+    Named,
+}
+
+impl VolumeKind {
+    pub fn of(source: &[u8]) -> Self {
+        match source.first() {
+            Some(b'/' | b'.' | b'~') => VolumeKind::Bind,
+            _ => VolumeKind::Named,
+        }
+    }
+}
+
+/// What is wrong with the value of a `volume:` argument.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VolumeError {
+    Path,
+    /// A source that is neither a path nor a volume name, like `src/app`.
+    Source,
+    Mount,
+    Option,
+    /// `ro` with `rw`, or `z` with `Z`.
+    Conflict,
+}
+
+impl std::fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolumeError::Path => write!(f, "invalid path, only letters, digits, `/`, `_`, `-` and `.` are allowed"),
+            VolumeError::Source => write!(
+                f, "invalid volume source, expected a path starting with `/`, `.` or `~`, or a volume name"
+            ),
+            VolumeError::Mount => write!(f, "invalid mount point, expected an absolute path"),
+            VolumeError::Option => write!(f, "invalid volume option, expected one of ro, rw, z, Z, cached"),
+            VolumeError::Conflict => write!(f, "conflicting volume options, only one of ro and rw, or z and Z, applies"),
+        }
+    }
+}
+
+impl std::error::Error for VolumeError {}
+
+/// What is wrong with a value, and the bytes of it that are.
+type Invalid = (VolumeError, Range<usize>);
+
+impl<'a> Argument<'a> {
+    /// The value of a `volume:` line, up to the trailing comment, failing on the part of it that is wrong.
+    fn parse_volume_value(input: &'a [u8]) -> IResult<'a, Argument<'a>> {
+        let length = input.iter()
+            .take_while(|chr| !chr.is_ascii_whitespace() && **chr != b'\0')
+            .count();
+        let (value, rest) = input.split_at(length);
+        match Argument::parse_volume_at(value) {
+            Ok(volume) => Ok((rest, volume)),
+            Err((error, range)) => Err(nom::Err::Failure(SyntaxError::new(error.into(), &value[range]))),
+        }
+    }
+
+    /// Parses `source:mount[:options]`, an error pointing at the part of `value` that is wrong.
+    pub(crate) fn parse_volume_at(value: &'a [u8]) -> Result<Argument<'a>, Invalid> {
+        let mut parts = value.splitn(3, |chr| *chr == b':');
+        let source = parts.next().unwrap_or_default();
+        let mount = parts.next().ok_or((VolumeError::Mount, value.len()..value.len()))?;
+        let mount_start = source.len() + 1;
+        let options = parts.next().unwrap_or_default();
+        let options_start = mount_start + mount.len() + 1;
+
+        Self::verify_source(source)?;
+        if !mount.starts_with(b"/") {
+            return Err((VolumeError::Mount, mount_start..mount_start + mount.len()))
+        }
+        if let Some(position) = mount.iter().position(|chr| !Self::is_allowed(*chr as char)) {
+            return Err((VolumeError::Path, mount_start + position..mount_start + mount.len()))
+        }
+
+        let mut offset = options_start;
+        let mut seen: Vec<&[u8]> = Vec::new();
+        for option in Argument::split_volume_options(options) {
+            let span = offset..offset + option.len();
+            offset += option.len() + 1;
+            if !OPTIONS.contains(&option) {
+                return Err((VolumeError::Option, span))
+            }
+            let conflicts: &[u8] = match option {
+                b"ro" => b"rw",
+                b"rw" => b"ro",
+                b"z" => b"Z",
+                b"Z" => b"z",
+                other => other,
+            };
+            if seen.contains(&conflicts) || seen.contains(&option) {
+                return Err((VolumeError::Conflict, span))
             }
-        )
-    )
-);
+            seen.push(option);
+        }
+
+        Ok(Argument::Volume {
+            source,
+            mount,
+            options
+        })
+    }
+
+    /// The options of a `volume:`, `ro,z`, none when it has none.
+    pub(in crate::parser) fn split_volume_options(options: &'a [u8]) -> impl Iterator<Item=&'a [u8]> {
+        options.split(|chr| *chr == b',').filter(move |_| !options.is_empty())
+    }
 
-impl Argument<'_> {
-    fn verify_path(path: &[u8]) -> bool {
-        path.iter().all(|chr| Self::is_allowed(*chr as char))
+    fn verify_source(source: &[u8]) -> Result<(), Invalid> {
+        let path = match VolumeKind::of(source) {
+            // `~` only stands for the home directory on its own or before a `/`.
+            VolumeKind::Bind if source.starts_with(b"~") => match source.get(1) {
+                None | Some(b'/') => &source[1..],
+                Some(_) => return Err((VolumeError::Path, 0..source.len())),
+            },
+            VolumeKind::Bind => source,
+            VolumeKind::Named if source.is_empty() => return Err((VolumeError::Source, 0..0)),
+            VolumeKind::Named if source.contains(&b'/') => return Err((VolumeError::Source, 0..source.len())),
+            VolumeKind::Named => source,
+        };
+        let start = source.len() - path.len();
+        match path.iter().position(|chr| !Self::is_allowed(*chr as char)) {
+            Some(position) => Err((VolumeError::Path, start + position..source.len())),
+            None => Ok(()),
+        }
     }
 }
 
 impl PathLike for Argument<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Argument;
+    use super::{VolumeKind, VolumeError};
+
+    fn error(value: &str) -> (VolumeError, &str) {
+        let (error, span) = Argument::parse_volume_at(value.as_bytes()).unwrap_err();
+        (error, &value[span])
+    }
+
+    #[test]
+    fn test_volume() {
+        let cases = [
+            ("./src:/app", "./src", "/app", "", VolumeKind::Bind),
+            ("/etc/nginx.conf:/etc/nginx/nginx.conf:ro", "/etc/nginx.conf", "/etc/nginx/nginx.conf", "ro", VolumeKind::Bind),
+            ("~/.cargo:/root/.cargo:rw,z", "~/.cargo", "/root/.cargo", "rw,z", VolumeKind::Bind),
+            ("../shared:/shared:cached", "../shared", "/shared", "cached", VolumeKind::Bind),
+            ("pg.data:/var/lib/postgresql/data", "pg.data", "/var/lib/postgresql/data", "", VolumeKind::Named),
+        ];
+        for (value, source, mount, options, kind) in cases {
+            let volume = Argument::parse_volume_at(value.as_bytes()).unwrap();
+            assert_eq!(volume, Argument::Volume { source: source.as_bytes(), mount: mount.as_bytes(), options: options.as_bytes() });
+            assert_eq!(VolumeKind::of(source.as_bytes()), kind);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error("./src"), (VolumeError::Mount, ""));
+        assert_eq!(error("./src:app"), (VolumeError::Mount, "app"));
+        assert_eq!(error("src/app:/app"), (VolumeError::Source, "src/app"));
+        assert_eq!(error("~user/src:/app"), (VolumeError::Path, "~user/src"));
+        assert_eq!(error("/my files:/app"), (VolumeError::Path, " files"));
+        assert_eq!(error("./src:/app:ro,delegated"), (VolumeError::Option, "delegated"));
+        assert_eq!(error("./src:/app:ro,z,rw"), (VolumeError::Conflict, "rw"));
+    }
+}
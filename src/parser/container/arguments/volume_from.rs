@@ -1,10 +1,10 @@
-use super::{Argument, space, line_feed};
+use super::Argument;
+use crate::parser::{SyntaxError, value_end};
 
-named!(pub(in super) volume_from<Argument>,
+named!(pub(in super) volume_from<&[u8], Argument<'_>, SyntaxError<'_>>,
     do_parse!(
-        tag!(":") >>
-        space >>
-        name: terminated!(Argument::parse_volume_name, line_feed) >> (
+        name: call!(Argument::parse_volume_name) >>
+        value_end >> (
             Argument::VolumeFrom {
                 name
             }
@@ -13,7 +13,8 @@ named!(pub(in super) volume_from<Argument>,
 );
 
 impl Argument<'_> {
-    fn parse_volume_name<T, E: nom::error::ParseError<T>>(input: T) -> nom::IResult<T, T, E>
+    /// A container name.
+    pub(in crate::parser) fn parse_volume_name<T, E: nom::error::ParseError<T>>(input: T) -> nom::IResult<T, T, E>
         where T: nom::InputTakeAtPosition,
               <T as nom::InputTakeAtPosition>::Item: nom::AsChar,
     {
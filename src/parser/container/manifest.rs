@@ -1,11 +1,12 @@
-use super::{space, line_feed};
+use crate::parser::{ErrorKind, SyntaxError, line_feed, value_end, expect, value};
 
-named!(pub manifest<Manifest>,
+named!(pub manifest<&[u8], Manifest<'_>, SyntaxError<'_>>, terminated!(manifest_value, line_feed));
+
+// The `from:` line up to the end of its value, where a trailing comment may start.
+named!(pub(in crate::parser) manifest_value<&[u8], Manifest<'_>, SyntaxError<'_>>,
     do_parse!(
         tag!("from") >>
-        tag!(":") >>
-        space >>
-        manifest: terminated!(Manifest::parse_manifest, line_feed) >> (
+        manifest: call!(value(ErrorKind::InvalidImageReference, reference)) >> (
             if manifest.starts_with(b"Dockerfile") {
                 Manifest::File(manifest)
             } else {
@@ -15,6 +16,12 @@ named!(pub manifest<Manifest>,
     )
 );
 
+// An image or a Dockerfile, what follows it on the line is the invalid part.
+named!(reference<&[u8], &[u8], SyntaxError<'_>>,
+    terminated!(call!(Manifest::parse_manifest), call!(expect(ErrorKind::InvalidImageReference, value_end)))
+);
+
+#[derive(Clone, Copy)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum Manifest<'a> {
     File(&'a [u8]),
@@ -59,7 +66,7 @@ mod tests {
 
         let result = manifest(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, manifest) = result.unwrap();
         assert_eq!(manifest, Manifest::Image(b"ubuntu:latest"))
     }
@@ -70,7 +77,7 @@ mod tests {
 
         let result = manifest(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, manifest) = result.unwrap();
         assert_eq!(manifest, Manifest::File(b"Dockerfile.ubuntu"))
     }
@@ -2,50 +2,227 @@ mod manifest;
 mod arguments;
 mod name;
 
-use crate::parser::{space, newline, tab, digit, line_feed};
-use manifest::{manifest, Manifest};
-use name::container_name;
-use arguments::{argument, Argument};
+use std::path::Path;
+use nom::combinator::consumed;
+use crate::parser::{ErrorKind, SyntaxError, IResult, line_end, line_feed, value_end, comment_lines, indentation, expect, value, key, rest_of_line};
+use manifest::manifest_value;
+use arguments::argument_line;
 
-named!(pub container<Container>,
+pub use manifest::Manifest;
+pub use arguments::{Argument, PortRange, Protocol, PortError, VolumeKind, VolumeError, ArgvError, format_publish, format_expose, split_argv, format_argv};
+pub(in crate::parser) use arguments::argument_value;
+pub(in crate::parser) use name::container_name;
+
+named!(pub container<&[u8], Container<'_>, SyntaxError<'_>>,
+    do_parse!(
+        tag!("@") >>
+        name: call!(expect(ErrorKind::InvalidContainerName, container_name)) >>
+        call!(expect(ErrorKind::UnexpectedInput, line_end)) >>
+        container: call!(Container::parse_block, name) >> (container)
+    )
+);
+
+named!(block<&[u8], (Option<(&[u8], &[u8])>, Option<(&[u8], Manifest<'_>)>, Vec<(&[u8], Argument<'_>)>), SyntaxError<'_>>,
     do_parse!(
-            tag!("@") >>
-            name: terminated!(container_name, newline) >>
-            manifest: preceded!(tab, manifest) >>
-            arguments: verify!(many0!(complete!(preceded!(tab, argument))), Container::verify_arguments) >>
-            alt!(newline | eof!()) >> (
-                Container {
-                    name,
-                    manifest,
-                    arguments
-                }
-            )
-        )
+        extends: opt!(complete!(preceded!(
+            comment_lines,
+            preceded!(indentation, preceded!(peek!(tag!("extends:")), extends))
+        ))) >>
+        manifest: call!(Container::parse_manifest, extends.is_some()) >>
+        arguments: many0!(complete!(preceded!(
+            comment_lines,
+            preceded!(indentation, call!(Container::parse_argument, manifest.map(|(line, _)| line)))
+        ))) >>
+        call!(Container::verify_arguments, &arguments) >> (extends, manifest, arguments)
+    )
+);
+
+named!(extends<&[u8], (&[u8], &[u8]), SyntaxError<'_>>, terminated!(call!(consumed(extends_value)), line_feed));
+
+named!(extends_value<&[u8], &[u8], SyntaxError<'_>>,
+    preceded!(tag!("extends"), call!(value(ErrorKind::InvalidContainerName, parent)))
 );
 
+named!(parent<&[u8], &[u8], SyntaxError<'_>>, terminated!(call!(Argument::parse_volume_name), value_end));
+
+named!(manifest_line<&[u8], (&[u8], Manifest<'_>), SyntaxError<'_>>, terminated!(call!(consumed(manifest_value)), line_feed));
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct Container<'a> {
     name: &'a [u8],
-    manifest: Manifest<'a>,
-    arguments: Vec<Argument<'a>>
+    /// The `extends:` line and the name of the container it extends.
+    extends: Option<(&'a [u8], &'a [u8])>,
+    /// Only missing until the parser resolved what the container inherits.
+    manifest: Option<Manifest<'a>>,
+    /// Directory of the file the manifest is written in, see [`Container::manifest_directory`].
+    manifest_directory: &'a Path,
+    arguments: Vec<Argument<'a>>,
+    /// Source line of each argument, without indentation and trailing comment, the `extends:` line for inherited ones.
+    lines: Vec<&'a [u8]>,
+    /// Directory of the file each argument is written in, see [`Container::directory`].
+    directories: Vec<&'a Path>,
+    inherits_manifest: bool,
+    /// How many of the arguments, at the front, are inherited.
+    inherited: usize,
 }
 
 impl<'a> Container<'a> {
-    fn verify_arguments(arguments: &[Argument<'a>]) -> bool {
-        let mut unique = std::collections::HashSet::new();
-        arguments.iter().all(move |arg| unique.insert(arg))
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// The container's own manifest, or the one it inherits.
+    pub fn manifest(&self) -> &Manifest<'a> {
+        self.manifest.as_ref().expect("the parser resolves inherited manifests")
+    }
+
+    /// Directory of the file the manifest is written in, relative to the including Crabfile.
+    /// A Dockerfile is relative to it, and built with it as the context.
+    pub fn manifest_directory(&self) -> &'a Path {
+        self.manifest_directory
+    }
+
+    /// Name of the container this one extends.
+    pub fn extends(&self) -> Option<&'a [u8]> {
+        self.extends.map(|(_, name)| name)
+    }
+
+    pub fn arguments(&self) -> &[Argument<'a>] {
+        &self.arguments
+    }
+
+    /// Names of the profiles the container belongs to, from every `profiles:` line.
+    pub fn profiles(&self) -> impl Iterator<Item=&'a [u8]> + '_ {
+        self.arguments.iter().flat_map(|argument| match argument {
+            Argument::Profiles { names } => Some(Argument::split_profiles(names)),
+            _ => None,
+        }).flatten()
+    }
+
+    /// Names of the networks the container joins, in the order of its `network:` lines.
+    pub fn networks(&self) -> impl Iterator<Item=&'a [u8]> + '_ {
+        self.arguments.iter().filter_map(|argument| match argument {
+            Argument::Network { name } => Some(*name),
+            _ => None,
+        })
+    }
+
+    /// The other names the container has on each of its networks.
+    pub fn aliases(&self) -> impl Iterator<Item=&'a [u8]> + '_ {
+        self.arguments.iter().filter_map(|argument| match argument {
+            Argument::Alias { name } => Some(*name),
+            _ => None,
+        })
+    }
+
+    /// Directory of the file the argument at `index` is written in, relative to the including Crabfile.
+    /// Paths of the argument, like the file of an `env-file:`, are relative to it.
+    pub fn directory(&self, index: usize) -> &'a Path {
+        self.directories.get(index).copied().unwrap_or_else(|| Path::new(""))
+    }
+
+    /// Source line of the argument at `index`.
+    pub(in crate::parser) fn line(&self, index: usize) -> &'a [u8] {
+        self.lines[index]
+    }
+
+    /// The `extends:` line, without indentation and trailing comment.
+    pub(in crate::parser) fn extends_line(&self) -> Option<&'a [u8]> {
+        self.extends.map(|(line, _)| line)
+    }
+
+    /// Whether the manifest is inherited rather than declared by the block.
+    pub(in crate::parser) fn inherits_manifest(&self) -> bool {
+        self.inherits_manifest
+    }
+
+    /// Whether the argument at `index` is inherited rather than declared by the block.
+    pub(in crate::parser) fn is_inherited(&self, index: usize) -> bool {
+        index < self.inherited
+    }
+
+    /// Places the manifest and the arguments declared by the block in the file of `directory`.
+    pub(in crate::parser) fn in_directory(&mut self, directory: &'a Path) {
+        self.manifest_directory = directory;
+        self.directories = vec![directory; self.arguments.len()];
+    }
+
+    /// Takes over the manifest of `parent`, unless the container declares its own, and the arguments
+    /// of `parent` it does not replace, see [`Argument::replaces`], in front of its own.
+    pub(in crate::parser) fn inherit(&mut self, (manifest, directory): (Manifest<'a>, &'a Path), parent: &[(Argument<'a>, &'a Path)]) {
+        let line = self.extends_line().unwrap_or(self.name);
+        if self.manifest.is_none() {
+            self.manifest = Some(manifest);
+            self.manifest_directory = directory;
+            self.inherits_manifest = true;
+        }
+
+        let inherited = parent.iter()
+            .filter(|(inherited, _)| !self.arguments[self.inherited..].iter().any(|argument| argument.replaces(inherited)))
+            .copied()
+            .collect::<Vec<_>>();
+        self.lines.splice(..self.inherited, std::iter::repeat_n(line, inherited.len()));
+        self.directories.splice(..self.inherited, inherited.iter().map(|(_, directory)| *directory));
+        self.arguments.splice(..self.inherited, inherited.iter().map(|(argument, _)| *argument));
+        self.inherited = inherited.len();
+    }
+
+    /// The lines of the block after its header, errors naming the container.
+    fn parse_block(input: &'a [u8], name: &'a [u8]) -> IResult<'a, Container<'a>> {
+        let (input, (extends, manifest, arguments)) = block(input).map_err(|err| err.map(|error| error.in_container(name)))?;
+        let (lines, arguments) = arguments.into_iter().unzip();
+        Ok((input, Container {
+            name,
+            extends,
+            manifest: manifest.map(|(_, manifest)| manifest),
+            manifest_directory: Path::new(""),
+            arguments,
+            lines,
+            directories: Vec::new(),
+            inherits_manifest: false,
+            inherited: 0
+        }))
     }
-}
 
-impl<'a> std::iter::FromIterator<Container<'a>> for std::collections::HashMap<&'a [u8], Container<'a>> {
-    fn from_iter<T: IntoIterator<Item=Container<'a>>>(iter: T) -> Self {
-        iter.into_iter().map(|c| (c.name, c)).collect()
+    /// The `from:` line with the manifest, optional for a container extending another one.
+    fn parse_manifest(input: &'a [u8], optional: bool) -> IResult<'a, Option<(&'a [u8], Manifest<'a>)>> {
+        match optional {
+            true => opt!(input, complete!(preceded!(comment_lines, preceded!(indentation, preceded!(peek!(tag!("from:")), manifest_line))))),
+            false => map!(input, preceded!(
+                comment_lines,
+                preceded!(call!(expect(ErrorKind::MissingManifest, indentation)), call!(expect(ErrorKind::MissingManifest, manifest_line)))
+            ), Some),
+        }
+    }
+
+    /// An argument line, `manifest` being the `from:` line another one would repeat.
+    fn parse_argument(input: &'a [u8], manifest: Option<&'a [u8]>) -> IResult<'a, (&'a [u8], Argument<'a>)> {
+        let line = rest_of_line(input);
+        match (key(input), manifest) {
+            (b"extends", _) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::MisplacedExtends, line))),
+            (b"from", Some(first)) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::DuplicateArgument, line).with_first(first))),
+            (b"from", None) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::MissingManifest, line))),
+            _ => argument_line(input),
+        }
+    }
+
+    /// Fails on the first argument repeating an earlier one, see [`Argument::repeats`].
+    fn verify_arguments(input: &'a [u8], arguments: &[(&'a [u8], Argument<'a>)]) -> IResult<'a, ()> {
+        let duplicate = arguments.iter().enumerate().find_map(|(index, (line, argument))| {
+            arguments[..index].iter()
+                .find(|(_, earlier)| argument.repeats(earlier))
+                .map(|(first, _)| (*line, *first))
+        });
+        match duplicate {
+            Some((line, first)) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::DuplicateArgument, line).with_first(first))),
+            None => Ok((input, ())),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{container, Manifest, Argument};
+    use super::{container, Manifest, Argument, Protocol};
     use crate::parser::common::error_fmt;
 
     #[test]
@@ -56,11 +233,11 @@ mod tests {
         "};
 
         let result = container(input.as_bytes());
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (remaining, container) = result.unwrap();
-        assert!(remaining.is_empty(), format!("Remaining input should be empty: {}", String::from_utf8_lossy(remaining)));
+        assert!(remaining.is_empty(), "Remaining input should be empty: {}", String::from_utf8_lossy(remaining));
         assert_eq!(container.name, b"ubuntu");
-        assert_eq!(container.manifest, Manifest::Image(b"ubuntu:latest"))
+        assert_eq!(container.manifest, Some(Manifest::Image(b"ubuntu:latest")))
     }
 
     #[test]
@@ -76,29 +253,34 @@ mod tests {
         "};
 
         let result = container(input.as_bytes());
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (remaining, container) = result.unwrap();
-        assert!(remaining.is_empty(), format!("Remaining input should be empty: {}", String::from_utf8_lossy(remaining)));
+        assert!(remaining.is_empty(), "Remaining input should be empty: {}", String::from_utf8_lossy(remaining));
         assert_eq!(container.name, b"ubuntu");
-        assert_eq!(container.manifest, Manifest::Image(b"ubuntu:latest"));
+        assert_eq!(container.manifest, Some(Manifest::Image(b"ubuntu:latest")));
         assert_eq!(container.arguments, vec![
             Argument::PublishPort {
-                outer: 80,
-                inner: 8080
+                host: None,
+                outer: 80.into(),
+                inner: 8080.into(),
+                protocol: Protocol::Tcp
             },
             Argument::Volume {
                 source: b"/usr/lib/",
-                mount: b"/usr/share/lib"
+                mount: b"/usr/share/lib",
+                options: b""
             },
             Argument::VolumeFrom {
                 name: b"cache_container"
             },
             Argument::ExposePort {
-                port: 443
+                port: 443.into(),
+                protocol: Protocol::Tcp
             },
             Argument::Volume {
                 source: b"/home/apple",
-                mount: b"/home/peach"
+                mount: b"/home/peach",
+                options: b""
             }
         ])
     }
@@ -113,23 +295,48 @@ mod tests {
         "};
 
         let result = container(input.as_bytes());
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (remaining, container) = result.unwrap();
-        assert!(remaining.is_empty(), format!("Remaining input should be empty: {}", String::from_utf8_lossy(remaining)));
+        assert!(remaining.is_empty(), "Remaining input should be empty: {}", String::from_utf8_lossy(remaining));
         assert_eq!(container.name, b"ubuntu");
-        assert_eq!(container.manifest, Manifest::Image(b"ubuntu:latest"));
+        assert_eq!(container.manifest, Some(Manifest::Image(b"ubuntu:latest")));
         assert_eq!(container.arguments, vec![
             Argument::PublishPort {
-                outer: 80,
-                inner: 8080
+                host: None,
+                outer: 80.into(),
+                inner: 8080.into(),
+                protocol: Protocol::Tcp
             },
             Argument::PublishPort {
-                outer: 443,
-                inner: 8443
+                host: None,
+                outer: 443.into(),
+                inner: 8443.into(),
+                protocol: Protocol::Tcp
             },
         ])
     }
 
+    #[test]
+    fn test_parse_extends() {
+        let input = indoc::indoc! {"
+            @ubuntu-focal:
+                extends: ubuntu # same ports
+                port: 80:8080
+        "};
+
+        let result = container(input.as_bytes());
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (_, child) = result.unwrap();
+        assert_eq!(child.extends(), Some(&b"ubuntu"[..]));
+        assert_eq!(child.extends_line(), Some(&b"extends: ubuntu"[..]));
+        assert_eq!(child.manifest, None);
+        assert_eq!(child.arguments, vec![Argument::PublishPort { host: None, outer: 80.into(), inner: 8080.into(), protocol: Protocol::Tcp }]);
+
+        let input = "@ubuntu-focal:\n    extends: ubuntu\n    from: ubuntu:focal\n";
+        let (_, focal) = container(input.as_bytes()).unwrap();
+        assert_eq!(focal.manifest, Some(Manifest::Image(b"ubuntu:focal")));
+    }
+
     #[test]
     fn test_parse_input_with_duplicated_manifest() {
         let input = indoc::indoc! {"
@@ -1,4 +1,6 @@
-named!(pub container_name<&[u8]>,
+use crate::parser::SyntaxError;
+
+named!(pub container_name<&[u8], &[u8], SyntaxError<'_>>,
     do_parse!(
         name: verify!(
             take_until!(":"),
@@ -21,7 +23,7 @@ mod tests {
 
         let result = container_name(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, container_name) = result.unwrap();
         assert_eq!(container_name, b"ubuntu");
     }
@@ -32,7 +34,7 @@ mod tests {
 
         let result = container_name(input);
 
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, container_name) = result.unwrap();
         assert_eq!(container_name, b"ubuntu-bionic");
     }
@@ -0,0 +1,186 @@
+use super::{Parser, Container, Argument};
+
+/// Containers that take each other's volumes, listed from the first back to itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub path: Vec<String>,
+}
+
+impl std::fmt::Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dependency cycle {}", self.path.join(" -> "))
+    }
+}
+
+impl std::error::Error for Cycle {}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+/// Names of the containers `container` takes volumes from.
+pub fn dependencies<'c, 'a>(container: &'c Container<'a>) -> impl Iterator<Item=&'a [u8]> + 'c {
+    container.arguments().iter().filter_map(|argument| match argument {
+        Argument::VolumeFrom { name } => Some(*name),
+        _ => None,
+    })
+}
+
+/// Orders the containers so that every one comes after those it takes volumes from.
+///
+/// Containers without dependencies between them keep the order they are declared in.
+/// References to containers outside the Crabfile are skipped, [`Parser::validate`] reports them.
+pub fn start_order<'p, 'a>(parser: &'p Parser<'a>) -> Result<Vec<&'p Container<'a>>, Cycle> {
+    start_order_for(parser, &[])
+}
+
+/// Like [`start_order`], only for the containers belonging to any of `profiles`, and every container
+/// they take volumes from whatever its profiles. No profiles at all selects every container.
+pub fn start_order_for<'p, 'a>(parser: &'p Parser<'a>, profiles: &[&str]) -> Result<Vec<&'p Container<'a>>, Cycle> {
+    let mut marks = std::collections::HashMap::new();
+    let mut order = Vec::with_capacity(parser.containers.len());
+    let mut stack = Vec::new();
+    let selected = parser.containers.iter().filter(|(_, container)| {
+        profiles.is_empty() || container.profiles().any(|name| profiles.iter().any(|profile| profile.as_bytes() == name))
+    });
+    for name in selected.map(|(name, _)| *name) {
+        visit(parser, name, &mut marks, &mut stack, &mut order)?;
+    }
+    Ok(order)
+}
+
+fn visit<'p, 'a>(
+    parser: &'p Parser<'a>,
+    name: &'a [u8],
+    marks: &mut std::collections::HashMap<&'a [u8], Mark>,
+    stack: &mut Vec<&'a [u8]>,
+    order: &mut Vec<&'p Container<'a>>,
+) -> Result<(), Cycle> {
+    let container = match parser.containers.get(name) {
+        Some(container) => container,
+        None => return Ok(()),
+    };
+
+    match marks.get(name) {
+        Some(Mark::Done) => return Ok(()),
+        Some(Mark::Visiting) => {
+            let start = stack.iter().position(|visiting| *visiting == name).unwrap_or_default();
+            return Err(Cycle {
+                path: stack[start..].iter().chain(Some(&name))
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .collect()
+            })
+        },
+        None => {},
+    }
+
+    marks.insert(name, Mark::Visiting);
+    stack.push(name);
+    for dependency in dependencies(container) {
+        visit(parser, dependency, marks, stack, order)?;
+    }
+    stack.pop();
+    marks.insert(name, Mark::Done);
+    order.push(container);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use super::Cycle;
+
+    fn names(input: &str) -> Result<Vec<String>, Cycle> {
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+        parser.start_order().map(|order| order.iter()
+            .map(|container| String::from_utf8_lossy(container.name()).into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn test_dependencies_first() {
+        let input = indoc::indoc! {"
+            @app:
+                from: ubuntu:latest
+                volume-from: data
+                volume-from: cache
+
+            @cache:
+                from: alpine:latest
+                volume-from: data
+
+            @data:
+                from: busybox:latest
+
+            @tools:
+                from: ubuntu:latest
+                volume-from: external
+        "};
+
+        assert_eq!(names(input).unwrap(), vec!["data", "cache", "app", "tools"]);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let input = indoc::indoc! {"
+            @web:
+                from: node:20
+                profiles: frontend
+                volume-from: assets
+
+            @api:
+                from: rust:latest
+                profiles: backend
+                volume-from: db
+
+            @assets:
+                from: busybox:latest
+
+            @db:
+                from: postgres:15
+
+            @docs:
+                from: nginx:latest
+                profiles: frontend, docs
+        "};
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+        let names = |profiles: &[&str]| parser.start_order_for(profiles).unwrap().iter()
+            .map(|container| String::from_utf8_lossy(container.name()).into_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names(&["frontend"]), vec!["assets", "web", "docs"]);
+        assert_eq!(names(&["backend", "docs"]), vec!["db", "api", "docs"]);
+        assert_eq!(names(&[]), vec!["assets", "web", "db", "api", "docs"]);
+        assert!(parser.has_profile("docs"));
+        assert!(!parser.has_profile("assets"));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let input = indoc::indoc! {"
+            @a:
+                from: ubuntu:latest
+                volume-from: b
+
+            @b:
+                from: ubuntu:latest
+                volume-from: c
+
+            @c:
+                from: ubuntu:latest
+                volume-from: a
+        "};
+
+        let cycle = names(input).unwrap_err();
+        assert_eq!(cycle.path, vec!["a", "b", "c", "a"]);
+        assert_eq!(cycle.to_string(), "dependency cycle a -> b -> c -> a");
+    }
+
+    #[test]
+    fn test_self_reference() {
+        let cycle = names("@a:\n    from: ubuntu:latest\n    volume-from: a\n").unwrap_err();
+        assert_eq!(cycle.path, vec!["a", "a"]);
+    }
+}
@@ -0,0 +1,234 @@
+use super::{ParseError, ErrorKind, Span, Sources};
+
+/// A parse failure resolved to a position in the source, ready to be shown to a human.
+pub struct Diagnostic {
+    file: Option<String>,
+    label: Label,
+    notes: Vec<Label>,
+    message: String,
+}
+
+/// A line of the source with a span of it underlined.
+struct Label {
+    line: usize,
+    column: usize,
+    width: usize,
+    snippet: String,
+    message: Option<String>,
+    /// The file of a note pointing into another file than the error.
+    file: Option<String>,
+}
+
+impl Label {
+    fn new(source: &[u8], span: Span, message: Option<String>) -> Self {
+        let offset = span.start.min(source.len());
+        let start = source[..offset].iter().rposition(|chr| *chr == b'\n').map_or(0, |pos| pos + 1);
+        let end = source[offset..].iter().position(|chr| *chr == b'\n').map_or(source.len(), |pos| offset + pos);
+
+        Label {
+            line: source[..start].iter().filter(|chr| **chr == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&source[start..offset]).chars().count() + 1,
+            width: String::from_utf8_lossy(&source[offset..span.end.clamp(offset, end)]).chars().count().max(1),
+            snippet: String::from_utf8_lossy(&source[start..end]).into_owned(),
+            message,
+            file: None,
+        }
+    }
+
+    fn location(&self, f: &mut std::fmt::Formatter<'_>, gutter: usize, file: &str) -> std::fmt::Result {
+        writeln!(f, "{}--> {}:{}:{}", " ".repeat(gutter), file, self.line, self.column)?;
+        writeln!(f, "{:gutter$} |", "", gutter = gutter)
+    }
+
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, gutter: usize, underline: char) -> std::fmt::Result {
+        let indent: String = self.snippet.chars()
+            .take(self.column - 1)
+            .map(|chr| if chr == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{:>gutter$} | {}", self.line, self.snippet, gutter = gutter)?;
+        write!(f, "{:gutter$} | {}{}", "", indent, underline.to_string().repeat(self.width), gutter = gutter)?;
+        match &self.message {
+            Some(message) => write!(f, " {}", message),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new<M: Into<String>>(source: &[u8], span: Span, message: M) -> Self {
+        Diagnostic {
+            file: None,
+            label: Label::new(source, span, None),
+            notes: Vec::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Points at the earlier definition for errors about something defined or published twice.
+    pub fn from_error(source: &[u8], error: &ParseError) -> Self {
+        let diagnostic = Self::new(source, error.span(), error.to_string());
+        match note(error) {
+            Some((first, message)) => diagnostic.with_note(source, first, message),
+            None => diagnostic,
+        }
+    }
+
+    /// Like [`Diagnostic::from_error`], naming the file of [`Sources`] the error and its note are in.
+    pub fn from_sources(sources: &Sources, error: &ParseError) -> Self {
+        let (path, source, span) = sources.locate(error.span());
+        let file = path.display().to_string();
+        let diagnostic = Self::new(source, span, error.to_string()).with_file(file.as_str());
+        match note(error) {
+            Some((first, message)) => {
+                let (note_path, note_source, first) = sources.locate(first);
+                let mut diagnostic = diagnostic.with_note(note_source, first, message);
+                if note_path != path {
+                    diagnostic.notes.last_mut().expect("the note was just added").file = Some(note_path.display().to_string());
+                }
+                diagnostic
+            },
+            None => diagnostic,
+        }
+    }
+
+    pub fn with_file<F: Into<String>>(mut self, file: F) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Adds another location of the source that explains the error.
+    pub fn with_note<M: Into<String>>(mut self, source: &[u8], span: Span, message: M) -> Self {
+        self.notes.push(Label::new(source, span, Some(message.into())));
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.label.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.label.column
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The earlier location errors about something defined or published twice point at.
+fn note(error: &ParseError) -> Option<(Span, &'static str)> {
+    let message = match error.kind() {
+        ErrorKind::PortCollision { .. } => "first published here",
+        _ => "first defined here",
+    };
+    error.first().map(|first| (first, message))
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gutter = std::iter::once(&self.label).chain(&self.notes)
+            .map(|label| label.line.to_string().len())
+            .max()
+            .unwrap_or_default();
+
+        writeln!(f, "{}", self.message)?;
+        self.label.location(f, gutter, self.file.as_deref().unwrap_or("<input>"))?;
+        self.label.render(f, gutter, '^')?;
+        for note in &self.notes {
+            writeln!(f)?;
+            writeln!(f, "{:gutter$} |", "", gutter = gutter)?;
+            if let Some(file) = &note.file {
+                note.location(f, gutter, file)?;
+            }
+            note.render(f, gutter, '-')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::parser::{Parser, Span};
+
+    fn diagnose(input: &str) -> Diagnostic {
+        let err = Parser::parse(input.as_bytes()).expect_err("input should not parse");
+        Diagnostic::from_error(input.as_bytes(), &err)
+    }
+
+    #[test]
+    fn test_position() {
+        let diagnostic = Diagnostic::new(b"@ubuntu:\n    from: ubuntu\n", Span::new(15, 21), "message");
+        assert_eq!((diagnostic.line(), diagnostic.column()), (2, 7));
+    }
+
+    #[test]
+    fn test_unknown_argument() {
+        let diagnostic = diagnose(indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+
+        @ubuntu-focal:
+            from: ubuntu:focal
+            port: 80:8080
+            invalid: invalid
+        "});
+
+        assert_eq!((diagnostic.line(), diagnostic.column()), (7, 5));
+        assert_eq!(
+            diagnostic.message(),
+            "unknown argument `invalid`, expected one of volume, port, expose, volume-from, env, env-file, profiles, command, entrypoint, workdir, user, network, alias in container `ubuntu-focal`"
+        );
+    }
+
+    #[test]
+    fn test_invalid_port() {
+        let diagnostic = diagnose(indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+            port: 80:abc
+        "});
+
+        assert_eq!((diagnostic.line(), diagnostic.column()), (3, 14));
+        assert_eq!(diagnostic.message(), "invalid port, expected a number between 0 and 65535 in container `ubuntu`");
+    }
+
+    #[test]
+    fn test_unexpected_indentation() {
+        let diagnostic = diagnose("@ubuntu:\n    from: ubuntu\n  port: 80:8080\n");
+
+        assert_eq!((diagnostic.line(), diagnostic.column()), (3, 1));
+    }
+
+    #[test]
+    fn test_render() {
+        let source = b"@ubuntu:\n    from: ubuntu\n    invalid: invalid\n";
+        let diagnostic = Diagnostic::new(source, Span::new(30, 37), "unknown argument").with_file("Crabfile");
+
+        assert_eq!(diagnostic.to_string(), indoc::indoc! {"
+            unknown argument
+             --> Crabfile:3:5
+              |
+            3 |     invalid: invalid
+              |     ^^^^^^^"
+        });
+    }
+
+    #[test]
+    fn test_render_duplicate_container() {
+        let source = "@ubuntu:\n    from: ubuntu:latest\n\n@cache:\n    from: alpine\n\n@ubuntu:\n    from: ubuntu:focal\n";
+        let diagnostic = diagnose(source).with_file("Crabfile");
+
+        assert_eq!(diagnostic.to_string(), indoc::indoc! {"
+            container `ubuntu` is defined more than once
+             --> Crabfile:7:2
+              |
+            7 | @ubuntu:
+              |  ^^^^^^
+              |
+            1 | @ubuntu:
+              |  ------ first defined here"
+        });
+    }
+}
@@ -0,0 +1,131 @@
+use super::Argument;
+
+/// A `KEY=VALUE` line of an env file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub key: String,
+    pub value: String,
+    /// 1-based line number the variable is defined on.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses an env file the way dotenv does.
+///
+/// Blank lines and `#` comments are skipped and an `export ` prefix is allowed. Unquoted values are
+/// trimmed and end at a ` #` comment, single-quoted values are taken literally and double-quoted ones
+/// understand `\n`, `\t`, `\"` and `\\`. Variables are not expanded.
+pub fn parse(source: &str) -> Result<Vec<Variable>, Error> {
+    let mut variables = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: &str| Error { line: index + 1, message: message.to_string() };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let (key, value) = line.split_once('=').ok_or_else(|| error("expected `KEY=VALUE`"))?;
+        let key = key.trim_end();
+        match Argument::parse_env_key::<nom::error::Error<&[u8]>>(key.as_bytes()) {
+            Ok((b"", _)) => {},
+            _ => return Err(error(&format!("invalid variable name `{}`", key))),
+        }
+
+        variables.push(Variable {
+            key: key.to_string(),
+            value: parse_value(value.trim_start()).map_err(error)?,
+            line: index + 1,
+        });
+    }
+    Ok(variables)
+}
+
+fn parse_value(value: &str) -> Result<String, &'static str> {
+    let quote = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        _ => return Ok(match value.find(" #").or_else(|| value.find("\t#")) {
+            Some(end) => value[..end].trim_end().to_string(),
+            None => value.trim_end().to_string(),
+        }),
+    };
+
+    let mut parsed = String::new();
+    let mut chars = value[1..].chars();
+    while let Some(chr) = chars.next() {
+        match chr {
+            chr if chr == quote => {
+                let rest = chars.as_str().trim_start();
+                return match rest.is_empty() || rest.starts_with('#') {
+                    true => Ok(parsed),
+                    false => Err("unexpected text after the closing quote"),
+                }
+            },
+            '\\' if quote == '"' => parsed.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(chr @ ('"' | '\\')) => chr,
+                Some(_) => return Err("unsupported escape sequence"),
+                None => return Err("unterminated quoted value"),
+            }),
+            chr => parsed.push(chr),
+        }
+    }
+    Err("unterminated quoted value")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Variable, Error};
+
+    fn pairs(source: &str) -> Vec<(String, String)> {
+        parse(source).unwrap().into_iter().map(|variable| (variable.key, variable.value)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let source = indoc::indoc! {r#"
+            # database
+            DATABASE_URL=postgres://localhost/crab
+            export RUST_LOG = debug # verbose
+
+            GREETING='hello # world'
+            MULTILINE="first\nsecond \"quoted\""
+            EMPTY=
+        "#};
+
+        assert_eq!(pairs(source), vec![
+            ("DATABASE_URL".to_string(), "postgres://localhost/crab".to_string()),
+            ("RUST_LOG".to_string(), "debug".to_string()),
+            ("GREETING".to_string(), "hello # world".to_string()),
+            ("MULTILINE".to_string(), "first\nsecond \"quoted\"".to_string()),
+            ("EMPTY".to_string(), String::new()),
+        ]);
+        assert_eq!(parse(source).unwrap()[1], Variable {
+            key: "RUST_LOG".to_string(),
+            value: "debug".to_string(),
+            line: 3
+        });
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("A=1\nB\n"), Err(Error { line: 2, message: "expected `KEY=VALUE`".to_string() }));
+        assert_eq!(parse("1A=1").unwrap_err().message, "invalid variable name `1A`");
+        assert_eq!(parse("A=\"open").unwrap_err().message, "unterminated quoted value");
+        assert_eq!(parse("A='a' b").unwrap_err().message, "unexpected text after the closing quote");
+    }
+}
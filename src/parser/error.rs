@@ -0,0 +1,736 @@
+use super::{PortError, VolumeError, ArgvError, SubnetError, Cycle, rest_of_line};
+use super::network::PROPERTIES;
+
+const ARGUMENTS: &[&str] = &[
+    "volume", "port", "expose", "volume-from", "env", "env-file", "profiles", "command", "entrypoint", "workdir", "user",
+    "network", "alias"
+];
+
+/// Byte range of the source a [`ParseError`] points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Span of `slice`, which has to be borrowed from `source`.
+    pub(in crate::parser) fn of(source: &[u8], slice: &[u8]) -> Self {
+        let start = slice.as_ptr() as usize - source.as_ptr() as usize;
+        Span::new(start, start + slice.len())
+    }
+}
+
+/// What is wrong with a Crabfile, where, and in which container block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    kind: ErrorKind,
+    span: Span,
+    /// The earlier definition, for something defined or published twice.
+    first: Option<Span>,
+    container: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    UnknownArgument {
+        name: String,
+    },
+    DuplicateArgument,
+    MissingManifest,
+    InvalidPort,
+    InvalidPortRange,
+    MismatchedPortRanges,
+    InvalidHostAddress,
+    InvalidProtocol,
+    InvalidPath,
+    InvalidVolumeSource,
+    InvalidMountPoint,
+    InvalidVolumeOption,
+    ConflictingVolumeOptions,
+    UnterminatedQuote,
+    TrailingEscape,
+    EmptyCommand,
+    InvalidWorkdir,
+    InvalidUser,
+    InvalidImageReference,
+    InvalidEnvironment,
+    InvalidContainerName,
+    InvalidProfiles,
+    DuplicateContainer,
+    MisplacedShell,
+    UnexpectedIndentation,
+    UnexpectedInput,
+    UnknownContainer {
+        name: String,
+    },
+    SelfReference,
+    PortCollision {
+        port: u16,
+    },
+    DependencyCycle {
+        cycle: Cycle,
+    },
+    UnsetVariable {
+        name: String,
+        message: Option<String>,
+    },
+    UnsafeVariable {
+        name: String,
+    },
+    InvalidInterpolation,
+    InvalidVariable,
+    MisplacedVars,
+    MisplacedInclude,
+    IncludedShell,
+    UnreadableInclude {
+        path: String,
+        reason: String,
+    },
+    IncludeCycle {
+        cycle: Vec<String>,
+    },
+    UnknownParent {
+        name: String,
+    },
+    ExtensionCycle {
+        cycle: Vec<String>,
+    },
+    MisplacedExtends,
+    InvalidNetworkName,
+    UnknownNetworkProperty {
+        name: String,
+    },
+    InvalidNetworkDriver,
+    InvalidSubnet,
+    InvalidInternal,
+    DuplicateNetwork {
+        name: String,
+    },
+    InvalidAlias,
+    UnknownNetwork {
+        name: String,
+    },
+    AliasWithoutNetwork,
+}
+
+impl ParseError {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        ParseError {
+            kind,
+            span,
+            first: None,
+            container: None
+        }
+    }
+
+    pub(in crate::parser) fn in_container(mut self, container: Option<String>) -> Self {
+        self.container = container;
+        self
+    }
+
+    pub(in crate::parser) fn with_first(mut self, first: Span) -> Self {
+        self.first = Some(first);
+        self
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Where something defined or published twice was first, see [`Diagnostic::from_error`](super::Diagnostic::from_error).
+    pub fn first(&self) -> Option<Span> {
+        self.first
+    }
+
+    /// Name of the container block the error happened in, if any.
+    pub fn container(&self) -> Option<&str> {
+        self.container.as_deref()
+    }
+
+    /// The error with its spans moved by `map`, to point into another version of the source.
+    pub(in crate::parser) fn map_spans<F: Fn(Span) -> Span>(mut self, map: F) -> Self {
+        self.span = map(self.span);
+        self.first = self.first.map(map);
+        self
+    }
+
+    /// The error the grammar reported while parsing `input`, a part of `source`.
+    pub(in crate::parser) fn from_syntax(source: &[u8], input: &[u8], error: nom::Err<SyntaxError>) -> Self {
+        let error = match error {
+            nom::Err::Error(error) | nom::Err::Failure(error) => error,
+            nom::Err::Incomplete(_) => SyntaxError::new(ErrorKind::UnexpectedInput, &input[input.len()..]),
+        };
+        let (kind, at) = match error.kind {
+            Some(kind) => (kind, error.at),
+            None => (ErrorKind::UnexpectedInput, rest_of_line(error.at)),
+        };
+        ParseError {
+            kind,
+            span: Span::of(source, at),
+            first: error.first.map(|first| Span::of(source, first)),
+            container: error.container.map(|name| String::from_utf8_lossy(name).into_owned())
+        }
+    }
+}
+
+impl From<PortError> for ErrorKind {
+    fn from(error: PortError) -> Self {
+        match error {
+            PortError::Port => ErrorKind::InvalidPort,
+            PortError::Range => ErrorKind::InvalidPortRange,
+            PortError::Lengths => ErrorKind::MismatchedPortRanges,
+            PortError::Host => ErrorKind::InvalidHostAddress,
+            PortError::Protocol => ErrorKind::InvalidProtocol,
+        }
+    }
+}
+
+impl From<VolumeError> for ErrorKind {
+    fn from(error: VolumeError) -> Self {
+        match error {
+            VolumeError::Path => ErrorKind::InvalidPath,
+            VolumeError::Source => ErrorKind::InvalidVolumeSource,
+            VolumeError::Mount => ErrorKind::InvalidMountPoint,
+            VolumeError::Option => ErrorKind::InvalidVolumeOption,
+            VolumeError::Conflict => ErrorKind::ConflictingVolumeOptions,
+        }
+    }
+}
+
+impl From<ArgvError> for ErrorKind {
+    fn from(error: ArgvError) -> Self {
+        match error {
+            ArgvError::Quote => ErrorKind::UnterminatedQuote,
+            ArgvError::Escape => ErrorKind::TrailingEscape,
+            ArgvError::Empty => ErrorKind::EmptyCommand,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ErrorKind::UnknownArgument { name } => write!(
+                f, "unknown argument `{}`, expected one of {}", name, ARGUMENTS.join(", ")
+            ),
+            ErrorKind::DuplicateArgument => write!(f, "duplicate argument"),
+            ErrorKind::MissingManifest => write!(
+                f, "expected `from: <image>` or `extends: <container>` as the first argument"
+            ),
+            ErrorKind::InvalidPort => write!(f, "{}", PortError::Port),
+            ErrorKind::InvalidPortRange => write!(f, "{}", PortError::Range),
+            ErrorKind::MismatchedPortRanges => write!(f, "{}", PortError::Lengths),
+            ErrorKind::InvalidHostAddress => write!(f, "{}", PortError::Host),
+            ErrorKind::InvalidProtocol => write!(f, "{}", PortError::Protocol),
+            ErrorKind::InvalidPath => write!(f, "{}", VolumeError::Path),
+            ErrorKind::InvalidVolumeSource => write!(f, "{}", VolumeError::Source),
+            ErrorKind::InvalidMountPoint => write!(f, "{}", VolumeError::Mount),
+            ErrorKind::InvalidVolumeOption => write!(f, "{}", VolumeError::Option),
+            ErrorKind::ConflictingVolumeOptions => write!(f, "{}", VolumeError::Conflict),
+            ErrorKind::UnterminatedQuote => write!(f, "{}", ArgvError::Quote),
+            ErrorKind::TrailingEscape => write!(f, "{}", ArgvError::Escape),
+            ErrorKind::EmptyCommand => write!(f, "{}", ArgvError::Empty),
+            ErrorKind::InvalidWorkdir => write!(
+                f, "invalid working directory, expected an absolute path of letters, digits, `/`, `_`, `-` and `.`"
+            ),
+            ErrorKind::InvalidUser => write!(
+                f, "invalid user, expected `user[:group]` of names or numeric ids"
+            ),
+            ErrorKind::InvalidImageReference => write!(
+                f, "invalid image reference, expected an image or a Dockerfile"
+            ),
+            ErrorKind::InvalidEnvironment => write!(
+                f, "invalid environment, expected `env: KEY=VALUE` or `env-file: <path>`"
+            ),
+            ErrorKind::InvalidContainerName => write!(
+                f, "invalid container name, only letters, digits, `_` and `-` are allowed"
+            ),
+            ErrorKind::InvalidProfiles => write!(
+                f, "invalid profiles, expected names of letters, digits, `_` and `-` separated by `,`"
+            ),
+            ErrorKind::DuplicateContainer => write!(
+                f, "container `{}` is defined more than once", self.container().unwrap_or_default()
+            ),
+            ErrorKind::MisplacedShell => write!(
+                f, "misplaced `@shell` block, it has to be the first block of the Crabfile"
+            ),
+            ErrorKind::UnexpectedIndentation => write!(
+                f, "unexpected indentation, expected a single tab or four spaces"
+            ),
+            ErrorKind::UnexpectedInput if self.span.start == self.span.end => write!(f, "unexpected end of file"),
+            ErrorKind::UnexpectedInput => write!(f, "unexpected input, expected a block header `@<name>:`"),
+            ErrorKind::UnknownContainer { name } => write!(f, "`volume-from` names unknown container `{}`", name),
+            ErrorKind::SelfReference => write!(f, "container takes volumes from itself"),
+            ErrorKind::PortCollision { port } => write!(f, "host port {} is published more than once", port),
+            ErrorKind::DependencyCycle { cycle } => write!(f, "{}", cycle),
+            ErrorKind::UnsetVariable { name, message: None } => write!(f, "variable `{}` is not set", name),
+            ErrorKind::UnsetVariable { name, message: Some(message) } => write!(f, "variable `{}` is not set: {}", name, message),
+            ErrorKind::UnsafeVariable { name } => write!(
+                f, "variable `{}` takes a line break, a NUL or a comment into the Crabfile", name
+            ),
+            ErrorKind::InvalidInterpolation => write!(
+                f, "invalid interpolation, expected `${{NAME}}`, `${{NAME:-default}}` or `${{NAME:?message}}`"
+            ),
+            ErrorKind::InvalidVariable => write!(f, "invalid variable, expected `NAME: value`"),
+            ErrorKind::MisplacedVars => write!(
+                f, "misplaced `@vars` block, it has to come before the containers, after the `@shell` block"
+            ),
+            ErrorKind::MisplacedInclude => write!(f, "misplaced `@include`, it has to come before the containers"),
+            ErrorKind::IncludedShell => write!(f, "only the including Crabfile can set the `@shell`"),
+            ErrorKind::UnreadableInclude { path, reason } => write!(f, "cannot include `{}`, {}", path, reason),
+            ErrorKind::IncludeCycle { cycle } => write!(f, "include cycle {}", cycle.join(" -> ")),
+            ErrorKind::UnknownParent { name } => write!(f, "`extends` names unknown container `{}`", name),
+            ErrorKind::ExtensionCycle { cycle } => write!(f, "extension cycle {}", cycle.join(" -> ")),
+            ErrorKind::MisplacedExtends => write!(f, "misplaced `extends`, it has to be the first argument"),
+            ErrorKind::InvalidNetworkName => write!(
+                f, "invalid network name, only letters, digits, `_` and `-` are allowed"
+            ),
+            ErrorKind::UnknownNetworkProperty { name } => write!(
+                f, "unknown network property `{}`, expected one of {}", name, PROPERTIES.join(", ")
+            ),
+            ErrorKind::InvalidNetworkDriver => write!(f, "invalid network driver, expected a name like bridge or overlay"),
+            ErrorKind::InvalidSubnet => write!(f, "{}", SubnetError),
+            ErrorKind::InvalidInternal => write!(f, "invalid `internal`, expected true or false"),
+            ErrorKind::DuplicateNetwork { name } => write!(f, "network `{}` is defined more than once", name),
+            ErrorKind::InvalidAlias => write!(f, "invalid alias, only letters, digits, `_` and `-` are allowed"),
+            ErrorKind::UnknownNetwork { name } => write!(f, "`network` names unknown network `{}`", name),
+            ErrorKind::AliasWithoutNetwork => write!(f, "`alias` needs a `network`"),
+        }?;
+
+        match (&self.kind, self.container()) {
+            (ErrorKind::DuplicateContainer, _) | (ErrorKind::DependencyCycle { .. }, _) => Ok(()),
+            (ErrorKind::ExtensionCycle { .. }, _) => Ok(()),
+            (ErrorKind::UnsetVariable { message: Some(_), .. }, _) | (_, None) => Ok(()),
+            (_, Some(container)) => write!(f, " in container `{}`", container),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The error of the grammar, pointing into the input it parses until [`ParseError::from_syntax`] places it in the source.
+///
+/// Parsers that know what is wrong fail with its [`ErrorKind`], see [`expect`](super::expect). Without one, nom
+/// backtracks and tries the next alternative, and the rest of the line is unexpected when none is left.
+pub(in crate::parser) struct SyntaxError<'a> {
+    kind: Option<ErrorKind>,
+    /// What is wrong, or where the parser gave up when there is no kind.
+    at: &'a [u8],
+    first: Option<&'a [u8]>,
+    container: Option<&'a [u8]>,
+}
+
+pub(in crate::parser) type IResult<'a, O> = nom::IResult<&'a [u8], O, SyntaxError<'a>>;
+
+impl<'a> SyntaxError<'a> {
+    pub(in crate::parser) fn new(kind: ErrorKind, at: &'a [u8]) -> Self {
+        SyntaxError {
+            kind: Some(kind),
+            at,
+            first: None,
+            container: None
+        }
+    }
+
+    pub(in crate::parser) fn with_first(mut self, first: &'a [u8]) -> Self {
+        self.first = Some(first);
+        self
+    }
+
+    pub(in crate::parser) fn in_container(mut self, name: &'a [u8]) -> Self {
+        self.container = Some(name);
+        self
+    }
+
+    /// Whether the parser failing knew what is wrong.
+    pub(in crate::parser) fn is_known(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+impl std::fmt::Debug for SyntaxError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} at {:?}", self.kind, String::from_utf8_lossy(self.at))
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for SyntaxError<'a> {
+    fn from_error_kind(input: &'a [u8], _: nom::error::ErrorKind) -> Self {
+        SyntaxError {
+            kind: None,
+            at: input,
+            first: None,
+            container: None
+        }
+    }
+
+    fn append(_: &'a [u8], _: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseError, ErrorKind, Span, VolumeError};
+    use crate::parser::Parser;
+
+    fn parse_error(input: &str) -> ParseError {
+        Parser::parse(input.as_bytes()).expect_err("input should not parse")
+    }
+
+    fn text(input: &str, span: Span) -> &str {
+        &input[span.start..span.end]
+    }
+
+    #[test]
+    fn test_unknown_argument() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+
+        @ubuntu-focal:
+            from: ubuntu:focal
+            port: 80:8080
+            invalid: invalid
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::UnknownArgument { name } if name == "invalid"));
+        assert_eq!(error.container(), Some("ubuntu-focal"));
+        assert_eq!(text(input, error.span()), "invalid");
+    }
+
+    #[test]
+    fn test_duplicate_argument() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+            port: 80:8080
+            expose: 443
+            port: 80:8080
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateArgument), "{:?}", error);
+        assert_eq!(error.span().start, input.rfind("port").unwrap());
+        assert_eq!(error.first().map(|first| first.start), input.find("port"));
+        assert_eq!(text(input, error.span()), "port: 80:8080");
+        assert_eq!(error.container(), Some("ubuntu"));
+    }
+
+    #[test]
+    fn test_duplicate_manifest() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+            from: ubuntu:bionic
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateArgument), "{:?}", error);
+        assert_eq!(text(input, error.span()), "from: ubuntu:bionic");
+        assert_eq!(text(input, error.first().unwrap()), "from: ubuntu:latest");
+    }
+
+    #[test]
+    fn test_invalid_port() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+            port: 80:abc
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidPort));
+        assert_eq!(text(input, error.span()), "abc");
+    }
+
+    #[test]
+    fn test_invalid_port_specification() {
+        let cases = [
+            ("port: 127.0.0.300:80:80 # db", "127.0.0.300", "invalid host address, expected an IPv4 address or an IPv6 address in brackets"),
+            ("port: 53:53/icmp", "icmp", "invalid protocol, expected tcp, udp or sctp"),
+            ("port: 8000-8010:80", "8000-8010:80", "the host and container port ranges have different lengths"),
+            ("expose: 9010-9000/udp", "9010-9000", "invalid port range, the first port is greater than the last"),
+            ("port: [::1]:80:99999", "99999", "invalid port, expected a number between 0 and 65535"),
+        ];
+        for (line, span, message) in cases {
+            let input = format!("@ubuntu:\n    from: ubuntu\n    {}\n", line);
+            let error = parse_error(&input);
+            assert_eq!(text(&input, error.span()), span);
+            assert_eq!(error.to_string(), format!("{} in container `ubuntu`", message));
+        }
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+            volume: /path+to:/mount
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidPath));
+        assert_eq!(text(input, error.span()), "+to");
+    }
+
+    #[test]
+    fn test_invalid_command() {
+        let input = "@ubuntu:\n    from: ubuntu\n    command: sh -c 'echo # done\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::UnterminatedQuote), "{:?}", error);
+        assert_eq!(text(input, error.span()), "'echo # done");
+
+        let input = "@ubuntu:\n    from: ubuntu\n    entrypoint: # none\n";
+        assert!(matches!(parse_error(input).kind(), ErrorKind::EmptyCommand));
+
+        let input = "@ubuntu:\n    from: ubuntu\n    command: sleep 1\n    command: sleep 2\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateArgument), "{:?}", error);
+        assert_eq!(text(input, error.span()), "command: sleep 2");
+    }
+
+    #[test]
+    fn test_invalid_workdir_and_user() {
+        let input = "@ubuntu:\n    from: ubuntu\n    workdir: workspace\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidWorkdir), "{:?}", error);
+        assert_eq!(text(input, error.span()), "workspace");
+
+        let input = "@ubuntu:\n    from: ubuntu\n    user: 1000: # dev\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidUser), "{:?}", error);
+        assert_eq!(text(input, error.span()), "1000:");
+    }
+
+    #[test]
+    fn test_invalid_volume() {
+        let cases = [
+            ("./src", VolumeError::Mount, ""),
+            ("./src:app", VolumeError::Mount, "app"),
+            ("src/app:/app", VolumeError::Source, "src/app"),
+            ("./src:/app:ro,delegated", VolumeError::Option, "delegated"),
+        ];
+        for (volume, expected, span) in cases {
+            let input = format!("@ubuntu:\n    from: ubuntu\n    volume: {} # mounted\n", volume);
+            let error = parse_error(&input);
+            assert!(error.to_string().starts_with(&expected.to_string()), "{}", error);
+            assert_eq!(text(&input, error.span()), span);
+        }
+
+        let input = "@ubuntu:\n    from: ubuntu\n    volume: ~/.cargo:/root/.cargo:ro,rw\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::ConflictingVolumeOptions));
+        assert_eq!(text(input, error.span()), "rw");
+    }
+
+    #[test]
+    fn test_invalid_environment() {
+        let input = "@ubuntu:\n    from: ubuntu\n    env: 1KEY=value\n";
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidEnvironment));
+        assert_eq!(text(input, error.span()), "1KEY=value");
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        let input = "@ubuntu:\n    from: ubuntu\n    profiles: front end\n";
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidProfiles));
+        assert_eq!(text(input, error.span()), "front end");
+    }
+
+    #[test]
+    fn test_invalid_image_reference() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: Dockerfile@ubuntu
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidImageReference));
+        assert_eq!(error.container(), Some("ubuntu"));
+    }
+
+    #[test]
+    fn test_invalid_container_name() {
+        let error = parse_error("@ubu ntu:\n    from: ubuntu\n");
+        assert_eq!(error, ParseError::new(ErrorKind::InvalidContainerName, Span::new(1, 9)));
+    }
+
+    #[test]
+    fn test_errors_around_comments() {
+        let input = indoc::indoc! {"
+        @ubuntu: # focal
+            from: ubuntu:latest # pinned
+            port: 80:8080 # first
+            # again
+            port: 80:8080 # second
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateArgument), "{:?}", error);
+        assert_eq!(error.container(), Some("ubuntu"));
+        assert_eq!(text(input, error.first().unwrap()), "port: 80:8080");
+        assert_eq!(error.span().start, input.rfind("port").unwrap());
+        assert_eq!(text(input, error.span()), "port: 80:8080");
+
+        let input = "@ubuntu:\n    # the image comes later\n    port: 80:8080\n";
+        assert!(matches!(parse_error(input).kind(), ErrorKind::MissingManifest));
+
+        let input = "@ubuntu:\n    from: ubuntu\n    port: 80:http # web\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidPort));
+        assert_eq!(text(input, error.span()), "http");
+
+        let input = "# header\n@shell: # zsh\n    # comment\n    path: /bin/z sh\n";
+        assert!(matches!(parse_error(input).kind(), ErrorKind::InvalidPath));
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let error = parse_error("@ubuntu:\n    port: 80:8080\n");
+        assert!(matches!(error.kind(), ErrorKind::MissingManifest));
+    }
+
+    #[test]
+    fn test_duplicate_container() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+
+        @ubuntu:
+            from: ubuntu:focal
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateContainer), "{:?}", error);
+        assert_eq!(error.container(), Some("ubuntu"));
+        assert_eq!(error.first(), Some(Span::new(1, 7)));
+        assert_eq!(error.span().start, input.rfind("ubuntu:\n").unwrap());
+    }
+
+    #[test]
+    fn test_misplaced_shell() {
+        let input = indoc::indoc! {"
+        @ubuntu:
+            from: ubuntu:latest
+
+        @shell:
+            path: /bin/zsh
+        "};
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::MisplacedShell));
+        assert_eq!(text(input, error.span()), "@shell:");
+    }
+
+    #[test]
+    fn test_vars_errors() {
+        let input = "@vars:\n    TAG: latest\n    1TAG: latest\n\n@ubuntu:\n    from: ubuntu\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidVariable));
+        assert_eq!(text(input, error.span()), "1TAG: latest");
+
+        let input = "@ubuntu:\n    from: ubuntu\n\n@vars: # too late\n    TAG: latest\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::MisplacedVars));
+        assert_eq!(text(input, error.span()), "@vars:");
+    }
+
+    #[test]
+    fn test_extends_errors() {
+        let input = "@ubuntu-focal:\n    extends: ubuntu focal\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidContainerName));
+        assert_eq!(text(input, error.span()), "ubuntu focal");
+
+        let input = "@ubuntu-focal:\n    extends: ubuntu\n    from: ubuntu@focal\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidImageReference));
+        assert_eq!(text(input, error.span()), "@focal");
+
+        let input = "@ubuntu-focal:\n    from: ubuntu:focal\n    extends: ubuntu\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::MisplacedExtends));
+        assert_eq!(error.container(), Some("ubuntu-focal"));
+
+        let input = "@ubuntu-focal:\n    extends: ubuntu\n    port: 80:8080\n    from: ubuntu:focal\n";
+        assert!(matches!(parse_error(input).kind(), ErrorKind::MissingManifest));
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let input = "@include: shared/db.crab
+@include: shared db.crab
+
+@ubuntu:
+    from: ubuntu
+";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::InvalidPath));
+        assert_eq!(text(input, error.span()), "shared db.crab");
+
+        let input = "@ubuntu:
+    from: ubuntu
+
+@include: shared/db.crab
+";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::MisplacedInclude));
+        assert_eq!(text(input, error.span()), "@include: shared/db.crab");
+    }
+
+    #[test]
+    fn test_network_errors() {
+        let cases = [
+            ("@network back end:\n", "back end:", "invalid network name, only letters, digits, `_` and `-` are allowed"),
+            ("@network backend:\n    driver: bridge!\n", "bridge!", "invalid network driver"),
+            ("@network backend:\n    subnet: 172.20.0.1/16 # not the network address\n", "172.20.0.1/16", "invalid subnet"),
+            ("@network backend:\n    internal: yes\n", "yes", "invalid `internal`, expected true or false"),
+            ("@network backend:\n    from: alpine\n", "from", "unknown network property `from`, expected one of driver, subnet, internal"),
+            ("@app:\n    from: alpine\n    network: back-end!\n", "back-end!", "invalid network name"),
+            ("@app:\n    from: alpine\n    alias: api.local\n", "api.local", "invalid alias"),
+        ];
+        for (input, span, message) in cases {
+            let error = parse_error(input);
+            assert!(error.to_string().starts_with(message), "{}", error);
+            assert_eq!(text(input, error.span()), span);
+        }
+
+        let input = "@network backend:\n    # the default one\n    driver: bridge\n    driver: overlay\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateArgument), "{:?}", error);
+        assert_eq!(error.container(), None);
+        assert_eq!(text(input, error.first().unwrap()), "driver: bridge");
+        assert_eq!(text(input, error.span()), "driver: overlay");
+
+        let input = "@network backend:\n\n@app:\n    from: alpine\n\n@network backend:\n    internal: true\n";
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateNetwork { .. }), "{:?}", error);
+        assert_eq!(error.to_string(), "network `backend` is defined more than once");
+        assert_eq!(error.span().start, input.rfind("backend").unwrap());
+    }
+
+    #[test]
+    fn test_unexpected_indentation() {
+        let input = "@ubuntu:\n    from: ubuntu\n  port: 80:8080\n";
+
+        let error = parse_error(input);
+        assert!(matches!(error.kind(), ErrorKind::UnexpectedIndentation));
+        assert_eq!(error.container(), Some("ubuntu"));
+        assert_eq!(text(input, error.span()), "  ");
+    }
+}
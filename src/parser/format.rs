@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use super::{Parser, Container, Manifest, Argument, Span, ast, is_comment, strip_line_comment};
+use super::ast::{INDENT, quote};
+use super::interpolate::{raw_offset, substituted};
+
+impl std::fmt::Display for Argument<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ast::Argument::from(self).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Manifest<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ast::Manifest::from(self).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Container<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ast::Container::from(self).fmt(f)
+    }
+}
+
+/// Writes the Crabfile in the canonical form of [`ast::Crabfile`], keeping its comments.
+///
+/// Comments on a line of their own stay in front of the line that follows them, trailing comments
+/// stay on their line. A comment before a block header moves below the blank line separating the blocks.
+impl std::fmt::Display for Parser<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (mut comments, trailing) = comments(self.source);
+        let mut first = true;
+
+        for line in self.lines() {
+            if line.header && !first {
+                writeln!(f)?;
+            }
+            first = false;
+
+            let indent = if line.indented { INDENT } else { "" };
+            while let Some(comment) = comments.next_if(|comment| comment.offset < line.offset) {
+                writeln!(f, "{}{}", indent, comment.text)?;
+            }
+            match trailing.get(&line.offset) {
+                Some(comment) => writeln!(f, "{}{} {}", indent, line.text, comment)?,
+                None => writeln!(f, "{}{}", indent, line.text)?,
+            }
+        }
+
+        let mut separate = !first;
+        for comment in comments {
+            if separate && !comment.indented {
+                writeln!(f)?;
+            }
+            separate = comment.indented;
+            writeln!(f, "{}{}", if comment.indented { INDENT } else { "" }, comment.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// A line of the canonical output.
+struct Line {
+    /// Start of the source line it is written for.
+    offset: usize,
+    /// Starts a block, separated from the previous one by a blank line.
+    header: bool,
+    indented: bool,
+    text: String,
+}
+
+/// A comment on a line of its own.
+struct Comment<'a> {
+    offset: usize,
+    indented: bool,
+    text: std::borrow::Cow<'a, str>,
+}
+
+/// The comments on lines of their own, in source order, and the trailing ones by the start of their line.
+fn comments(source: &[u8]) -> (std::iter::Peekable<std::vec::IntoIter<Comment<'_>>>, HashMap<usize, String>) {
+    let mut comments = Vec::new();
+    let mut trailing = HashMap::new();
+    let mut offset = 0;
+    for line in source.split(|chr| *chr == b'\n') {
+        let text = line.strip_suffix(b"\r").unwrap_or(line);
+        let code = strip_line_comment(text);
+        if is_comment(text) {
+            let start = text.iter().position(|chr| *chr == b'#').unwrap_or_default();
+            comments.push(Comment {
+                offset,
+                indented: start > 0,
+                text: String::from_utf8_lossy(text[start..].trim_ascii_end()),
+            });
+        } else if code.len() < text.len() {
+            trailing.insert(offset, String::from_utf8_lossy(text[code.len()..].trim_ascii()).into_owned());
+        }
+        offset += line.len() + 1;
+    }
+    (comments.into_iter().peekable(), trailing)
+}
+
+fn line_start(source: &[u8], offset: usize) -> usize {
+    source[..offset].iter().rposition(|chr| *chr == b'\n').map_or(0, |pos| pos + 1)
+}
+
+/// Start of the block header above the line starting at `offset`.
+fn header_start(source: &[u8], offset: usize) -> usize {
+    source[..offset].split(|chr| *chr == b'\n')
+        .scan(0, |start, line| {
+            let line_offset = *start;
+            *start += line.len() + 1;
+            Some((line_offset, line))
+        })
+        .filter(|(_, line)| line.starts_with(b"@"))
+        .last()
+        .map_or(0, |(start, _)| start)
+}
+
+impl Parser<'_> {
+    /// Whether `slice` is borrowed from the source, rather than being a default.
+    fn in_source(&self, slice: &[u8]) -> bool {
+        self.source.as_ptr_range().contains(&slice.as_ptr())
+    }
+
+    /// The line as written in the Crabfile when variables were expanded in it, `text` otherwise.
+    fn written(&self, offset: usize, text: String) -> String {
+        let end = self.source[offset..].iter().position(|chr| *chr == b'\n').map_or(self.source.len(), |end| offset + end);
+        if !substituted(self.substitutions, offset, end) {
+            return text
+        }
+
+        let raw = &self.raw[raw_offset(self.substitutions, offset, false)..];
+        let line = &raw[..raw.iter().position(|chr| *chr == b'\n').unwrap_or(raw.len())];
+        String::from_utf8_lossy(strip_line_comment(line).trim_ascii()).into_owned()
+    }
+
+    /// The lines of the canonical output, each with the source line it is written for.
+    fn lines(&self) -> Vec<Line> {
+        let source = self.source;
+        let offset = |slice: &[u8]| line_start(source, Span::of(source, slice).start);
+        let mut lines = Vec::new();
+
+        let path = self.shell.path();
+        if self.in_source(path) {
+            let path_line = offset(path);
+            lines.push(Line { offset: header_start(source, path_line), header: true, indented: false, text: "@shell:".to_string() });
+            lines.push(Line {
+                offset: path_line,
+                header: false,
+                indented: true,
+                text: self.written(path_line, format!("path: {}", String::from_utf8_lossy(path))),
+            });
+        }
+
+        if let Some(first) = self.vars.first() {
+            lines.push(Line { offset: header_start(source, offset(first.name())), header: true, indented: false, text: "@vars:".to_string() });
+            for variable in &self.vars {
+                lines.push(Line {
+                    offset: offset(variable.name()),
+                    header: false,
+                    indented: true,
+                    text: format!("{}: {}", String::from_utf8_lossy(variable.name()), quote(&String::from_utf8_lossy(variable.value()))),
+                });
+            }
+        }
+
+        for (index, include) in self.includes.iter().enumerate() {
+            lines.push(Line {
+                offset: offset(include),
+                header: index == 0,
+                indented: false,
+                text: format!("@include: {}", String::from_utf8_lossy(include)),
+            });
+        }
+
+        for network in self.networks() {
+            lines.push(Line {
+                offset: offset(network.name()),
+                header: true,
+                indented: false,
+                text: format!("@network {}:", String::from_utf8_lossy(network.name())),
+            });
+            for property in network.properties() {
+                let property_line = offset(property.value());
+                lines.push(Line {
+                    offset: property_line,
+                    header: false,
+                    indented: true,
+                    text: self.written(property_line, format!("{}: {}", property.key(), String::from_utf8_lossy(property.value()))),
+                });
+            }
+        }
+
+        for container in self.containers() {
+            lines.push(Line {
+                offset: offset(container.name()),
+                header: true,
+                indented: false,
+                text: format!("@{}:", String::from_utf8_lossy(container.name())),
+            });
+            if let (Some(line), Some(parent)) = (container.extends_line(), container.extends()) {
+                lines.push(Line {
+                    offset: offset(line),
+                    header: false,
+                    indented: true,
+                    text: format!("extends: {}", String::from_utf8_lossy(parent)),
+                });
+            }
+            if !container.inherits_manifest() {
+                let manifest = match container.manifest() {
+                    Manifest::File(manifest) | Manifest::Image(manifest) => manifest,
+                };
+                let manifest_line = offset(manifest);
+                lines.push(Line { offset: manifest_line, header: false, indented: true, text: self.written(manifest_line, container.manifest().to_string()) });
+            }
+            for (index, argument) in container.arguments().iter().enumerate().filter(|(index, _)| !container.is_inherited(*index)) {
+                let argument_line = offset(container.line(index));
+                lines.push(Line { offset: argument_line, header: false, indented: true, text: self.written(argument_line, argument.to_string()) });
+            }
+        }
+        // Networks and containers can come in any order, the blocks stay where they are.
+        lines.sort_by_key(|line| line.offset);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Parser, Expanded};
+    use crate::parser::ast::Crabfile;
+
+    fn format(input: &str) -> String {
+        Parser::parse(input.as_bytes()).unwrap().to_string()
+    }
+
+    fn assert_round_trip(input: &str) {
+        let formatted = format(input);
+        let original = Crabfile::from(&Parser::parse(input.as_bytes()).unwrap());
+        let reparsed = Crabfile::from(&Parser::parse(formatted.as_bytes()).unwrap());
+
+        assert_eq!(reparsed, original, "{}", formatted);
+        assert_eq!(format(&formatted), formatted, "formatting should be idempotent");
+    }
+
+    #[test]
+    fn test_format() {
+        let input = "\
+# Toolchain containers
+@shell:
+\tpath: /bin/zsh
+@ubuntu:   # the main one
+    from: ubuntu:latest
+\tport: 80:8080
+        # keep the cache around
+    volume-from: cache_container
+
+
+
+@cache_container:
+\tfrom: Dockerfile.cache
+\tenv: GREETING=\"hello # world\" # quoted
+";
+
+        assert_eq!(format(input), indoc::indoc! {"
+            # Toolchain containers
+            @shell:
+                path: /bin/zsh
+
+            @ubuntu: # the main one
+                from: ubuntu:latest
+                port: 80:8080
+                # keep the cache around
+                volume-from: cache_container
+
+            @cache_container:
+                from: Dockerfile.cache
+                env: GREETING=\"hello # world\" # quoted
+        "});
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_default_shell_and_trailing_comments() {
+        let input = "@app:\n    from: alpine:latest\n    expose: 80   # web\n    # port: 80:80\n# the end\n";
+
+        assert_eq!(format(input), "@app:\n    from: alpine:latest\n    expose: 80 # web\n    # port: 80:80\n\n# the end\n");
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_comments_after_commands() {
+        let input = "@app:\n    from: alpine:latest\n    command:  sh -c 'echo # not a comment' # run it\n    entrypoint: \"\" # none\n";
+
+        assert_eq!(format(input), "@app:\n    from: alpine:latest\n    command: sh -c 'echo # not a comment' # run it\n    entrypoint: '' # none\n");
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_variables_are_kept() {
+        let input = "@vars: # defaults\n\tTAG:   latest\n    GREETING: \"hello # world\" # quoted\n\n\n@app:\n    from: ubuntu:${TAG}   # pinned\n\tport: ${PORT:-80}:80\n    env: GREETING=${GREETING}\n";
+        let expanded = Expanded::placeholders(input.as_bytes()).unwrap();
+
+        assert_eq!(Parser::parse_expanded(&expanded).unwrap().to_string(), indoc::indoc! {"
+            @vars: # defaults
+                TAG: latest
+                GREETING: \"hello # world\" # quoted
+
+            @app:
+                from: ubuntu:${TAG} # pinned
+                port: ${PORT:-80}:80
+                env: GREETING=${GREETING}
+        "});
+    }
+
+    #[test]
+    fn test_path_variables_are_kept() {
+        let input = "@app:\n    from: alpine\n    volume: ${HOME}/x:/x\n    volume: ./${DATA}:/data\n    workdir: ${WD}\n";
+        let expanded = Expanded::placeholders(input.as_bytes()).unwrap();
+
+        assert_eq!(Parser::parse_expanded(&expanded).unwrap().to_string(), indoc::indoc! {"
+            @app:
+                from: alpine
+                volume: ${HOME}/x:/x
+                volume: ./${DATA}:/data
+                workdir: ${WD}
+        "});
+    }
+
+    #[test]
+    fn test_extends() {
+        let input = "@ubuntu:\n    from: ubuntu:latest\n    port: 80:8080\n\n@ubuntu-focal:\n\textends: ubuntu   # the same ports\n    from: ubuntu:focal\n@ubuntu-bionic:\n    extends: ubuntu\n";
+
+        assert_eq!(format(input), indoc::indoc! {"
+            @ubuntu:
+                from: ubuntu:latest
+                port: 80:8080
+
+            @ubuntu-focal:
+                extends: ubuntu # the same ports
+                from: ubuntu:focal
+
+            @ubuntu-bionic:
+                extends: ubuntu
+        "});
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_includes() {
+        let input = "@include:   shared/db.crab # postgres\n# the cache\n@include: shared/cache.crab\n@app:\n    from: alpine\n";
+
+        assert_eq!(format(input), "@include: shared/db.crab # postgres\n# the cache\n@include: shared/cache.crab\n\n@app:\n    from: alpine\n");
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_networks() {
+        let input = "@app:\n    from: alpine\n    network: backend # private\n\n# services only\n@network backend:\n\tinternal: true\n    subnet: 172.20.0.0/16\n@network frontend:\n";
+
+        assert_eq!(format(input), indoc::indoc! {"
+            @app:
+                from: alpine
+                network: backend # private
+
+            # services only
+            @network backend:
+                internal: true
+                subnet: 172.20.0.0/16
+
+            @network frontend:
+        "});
+        assert_round_trip(input);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(indoc::indoc! {"
+            @shell:
+                path: /bin/bash
+
+            @data:
+                from: busybox:latest
+                volume: /srv/data:/data
+                expose: 5432
+                env: EMPTY=
+                env: QUOTED=' leading'
+                env-file: ./config/.env
+        "});
+    }
+}
@@ -0,0 +1,280 @@
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use super::{ParseError, ErrorKind, SyntaxError, Span, space1, line_feed, value_end, expect, strip_line_comment};
+use super::container::Argument;
+
+named!(pub include<&[u8], &[u8], SyntaxError<'_>>,
+    do_parse!(
+        complete!(tag!("@include:")) >>
+        call!(expect(ErrorKind::InvalidPath, space1)) >>
+        path: call!(expect(ErrorKind::InvalidPath, include_path)) >>
+        line_feed >> (path)
+    )
+);
+
+named!(include_path<&[u8], &[u8], SyntaxError<'_>>, terminated!(call!(Argument::parse_env_file_path), value_end));
+
+const INCLUDE: &[u8] = b"@include:";
+
+/// A Crabfile and the Crabfiles it includes, one after the other in a single text.
+///
+/// Spans of a [`ParseError`] are offsets into this text, [`Sources::locate`] finds the file they point into.
+pub struct Sources {
+    text: Vec<u8>,
+    files: Vec<File>,
+    errors: Vec<ParseError>,
+}
+
+struct File {
+    path: PathBuf,
+    range: Range<usize>,
+    /// Whether a newline was added to end its last line.
+    terminated: bool,
+}
+
+impl Sources {
+    /// Reads the Crabfile at `path` and, recursively, every file it includes. Paths of included
+    /// files are relative to the file including them.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        Self::load_with(path, |path| std::fs::read(path))
+    }
+
+    /// Like [`Sources::load`], reading the files with `read`.
+    pub fn load_with<F>(path: &Path, read: F) -> std::io::Result<Self>
+        where F: Fn(&Path) -> std::io::Result<Vec<u8>>
+    {
+        let mut sources = Sources {
+            text: Vec::new(),
+            files: Vec::new(),
+            errors: Vec::new(),
+        };
+        let root = read(path)?;
+        sources.add(path.to_path_buf(), root, &read, &mut Vec::new());
+        sources.errors.sort_by_key(|error| error.span().start);
+        Ok(sources)
+    }
+
+    /// Every file, the including one first.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// The text of the Crabfile that includes the others.
+    pub fn root(&self) -> &[u8] {
+        let root = &self.files[0];
+        &self.text[root.range.start..root.range.end - usize::from(root.terminated)]
+    }
+
+    /// Files that could not be included and include cycles, pointing at the `@include` directive.
+    pub fn validate(&self) -> Result<(), Vec<ParseError>> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors.clone()),
+        }
+    }
+
+    /// The file `span` points into, its text and the span within it.
+    pub fn locate(&self, span: Span) -> (&Path, &[u8], Span) {
+        let file = self.files.iter()
+            .rfind(|file| file.range.start <= span.start)
+            .unwrap_or(&self.files[0]);
+        let start = file.range.start;
+        let end = span.end.clamp(span.start, file.range.end);
+        (&file.path, &self.text[file.range.clone()], Span::new(span.start.min(file.range.end) - start, end - start))
+    }
+
+    /// Where every file is in [`Sources::text`], with its directory relative to the one of the including Crabfile.
+    pub(in crate::parser) fn files(&self) -> impl Iterator<Item=(Range<usize>, &Path)> + '_ {
+        let root = parent(&self.files[0].path);
+        self.files.iter().map(move |file| {
+            let directory = parent(&file.path);
+            (file.range.clone(), directory.strip_prefix(root).unwrap_or(directory))
+        })
+    }
+
+    fn push(&mut self, path: PathBuf, text: Vec<u8>) -> usize {
+        let start = self.text.len();
+        let terminated = !text.is_empty() && !text.ends_with(b"\n");
+        self.text.extend_from_slice(&text);
+        // Every file starts on a line of its own.
+        if terminated {
+            self.text.push(b'\n');
+        }
+        self.files.push(File {
+            path,
+            range: start..self.text.len(),
+            terminated,
+        });
+        start
+    }
+
+    fn add<F>(&mut self, path: PathBuf, text: Vec<u8>, read: &F, stack: &mut Vec<PathBuf>)
+        where F: Fn(&Path) -> std::io::Result<Vec<u8>>
+    {
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        stack.push(normalize(&path));
+        let start = self.push(path, text);
+
+        for (offset, value) in includes(&self.text[start..]) {
+            let span = Span::new(start + offset, start + offset + value.len());
+            let included = directory.join(&value);
+            let normalized = normalize(&included);
+
+            if let Some(position) = stack.iter().position(|path| *path == normalized) {
+                let cycle = stack[position..].iter().chain(Some(&normalized)).map(|path| path.display().to_string()).collect();
+                self.errors.push(ParseError::new(ErrorKind::IncludeCycle { cycle }, span));
+                continue
+            }
+            // Included more than once, the containers are already there.
+            if self.files.iter().any(|file| normalize(&file.path) == normalized) {
+                continue
+            }
+            match read(&included) {
+                Ok(text) => self.add(included, text, read, stack),
+                Err(err) => self.errors.push(ParseError::new(
+                    ErrorKind::UnreadableInclude { path: included.display().to_string(), reason: err.to_string() },
+                    span
+                )),
+            }
+        }
+        stack.pop();
+    }
+}
+
+/// The paths of the `@include` directives of a file, with their offset.
+fn includes(text: &[u8]) -> Vec<(usize, String)> {
+    let mut includes = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive(|chr| *chr == b'\n') {
+        if let Some(value) = line.strip_prefix(INCLUDE) {
+            let value = strip_line_comment(value);
+            let start = value.iter().take_while(|chr| chr.is_ascii_whitespace()).count();
+            let value = value[start..].trim_ascii_end();
+            if !value.is_empty() {
+                includes.push((offset + INCLUDE.len() + start, String::from_utf8_lossy(value).into_owned()));
+            }
+        }
+        offset += line.len();
+    }
+    includes
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// The path without `.` and with `..` applied, so the same file is recognized however it is reached.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use super::Sources;
+    use crate::parser::{Parser, ErrorKind, Expanded, Diagnostic};
+
+    fn load(files: &[(&str, &str)]) -> Sources {
+        let files = files.iter().map(|(path, text)| (Path::new(path).to_path_buf(), text.as_bytes().to_vec())).collect::<HashMap<_, _>>();
+        Sources::load_with(Path::new("Crabfile"), |path| files.get(path)
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "not found")))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_include() {
+        let sources = load(&[
+            ("Crabfile", "@include: shared/db.crab\n@include: ./shared/cache.crab # the cache\n\n@app:\n    from: alpine\n    volume-from: postgres\n"),
+            ("shared/db.crab", "@include: cache.crab\n\n@postgres:\n    from: postgres:15\n    volume-from: cache_container"),
+            ("shared/cache.crab", "@cache_container:\n    from: redis:7\n"),
+        ]);
+        assert_eq!(sources.validate(), Ok(()));
+
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let parser = Parser::parse_sources(&sources, &expanded).unwrap();
+        let names = parser.containers().map(|container| String::from_utf8_lossy(container.name()).into_owned()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["app", "postgres", "cache_container"]);
+        assert_eq!(parser.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let sources = load(&[
+            ("Crabfile", "@include: a.crab\n\n@app:\n    from: alpine\n"),
+            ("a.crab", "@include: b.crab\n"),
+            ("b.crab", "@include: ./a.crab\n@include: missing.crab\n"),
+        ]);
+
+        let errors = sources.validate().unwrap_err();
+        let messages = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(messages, vec![
+            "include cycle a.crab -> b.crab -> a.crab",
+            "cannot include `missing.crab`, not found",
+        ]);
+        let (path, text, span) = sources.locate(errors[0].span());
+        assert_eq!(path, Path::new("b.crab"));
+        assert_eq!(&text[span.start..span.end], b"./a.crab");
+    }
+
+    #[test]
+    fn test_errors_name_the_file() {
+        let sources = load(&[
+            ("Crabfile", "@include: db.crab\n\n@app:\n    from: alpine\n    port: 5432:5432\n"),
+            ("db.crab", "@postgres:\n    from: postgres:15\n    port: 5432:5432\n    expose: x\n"),
+        ]);
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+
+        let error = Parser::parse_sources(&sources, &expanded).expect_err("the exposed port is invalid");
+        assert!(matches!(error.kind(), ErrorKind::InvalidPort));
+        assert_eq!(Diagnostic::from_sources(&sources, &error).to_string(), indoc::indoc! {"
+            invalid port, expected a number between 0 and 65535 in container `postgres`
+             --> db.crab:4:13
+              |
+            4 |     expose: x
+              |             ^"
+        });
+
+        let sources = load(&[
+            ("Crabfile", "@include: db.crab\n\n@app:\n    from: alpine\n    port: 5432:5432\n"),
+            ("db.crab", "@postgres:\n    from: postgres:15\n    port: 5432:5432\n"),
+        ]);
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let errors = Parser::parse_sources(&sources, &expanded).unwrap().validate().unwrap_err();
+        assert_eq!(Diagnostic::from_sources(&sources, &errors[0]).to_string(), indoc::indoc! {"
+            host port 5432 is published more than once in container `postgres`
+             --> db.crab:3:5
+              |
+            3 |     port: 5432:5432
+              |     ^^^^^^^^^^^^^^^
+              |
+             --> Crabfile:5:5
+              |
+            5 |     port: 5432:5432
+              |     --------------- first published here"
+        });
+    }
+
+    #[test]
+    fn test_included_shell() {
+        let sources = load(&[
+            ("Crabfile", "@include: shell.crab\n"),
+            ("shell.crab", "@shell:\n    path: /bin/zsh\n"),
+        ]);
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+
+        let error = Parser::parse_sources(&sources, &expanded).expect_err("only the Crabfile sets the shell");
+        assert!(matches!(error.kind(), ErrorKind::IncludedShell));
+    }
+}
@@ -0,0 +1,131 @@
+use indexmap::IndexMap;
+use super::{Container, ParseError, ErrorKind, Span};
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Merges every container with the one it `extends:`, the parent resolved first, so a container
+/// inherits what its parent inherited. A parent missing from the Crabfile or a chain of containers
+/// extending each other is an error.
+pub(in crate::parser) fn resolve(source: &[u8], containers: &mut IndexMap<&[u8], Container>) -> Result<(), ParseError> {
+    let mut resolved = vec![false; containers.len()];
+    for index in 0..containers.len() {
+        resolve_at(source, containers, index, &mut Vec::new(), &mut resolved)?;
+    }
+    Ok(())
+}
+
+fn resolve_at(
+    source: &[u8],
+    containers: &mut IndexMap<&[u8], Container>,
+    index: usize,
+    chain: &mut Vec<usize>,
+    resolved: &mut [bool],
+) -> Result<(), ParseError> {
+    if resolved[index] {
+        return Ok(())
+    }
+    let container = &containers[index];
+    let parent = match container.extends() {
+        Some(parent) => parent,
+        None => {
+            resolved[index] = true;
+            return Ok(())
+        },
+    };
+
+    let parent_index = containers.get_index_of(parent).ok_or_else(|| {
+        ParseError::new(ErrorKind::UnknownParent { name: string(parent) }, Span::of(source, parent))
+            .in_container(Some(string(container.name())))
+    })?;
+    chain.push(index);
+    if let Some(position) = chain.iter().position(|member| *member == parent_index) {
+        let first = &containers[chain[position]];
+        let cycle = chain[position..].iter().chain(Some(&parent_index)).map(|member| string(containers[*member].name())).collect();
+        return Err(
+            ParseError::new(ErrorKind::ExtensionCycle { cycle }, Span::of(source, first.extends().unwrap_or(first.name())))
+                .in_container(Some(string(first.name())))
+        )
+    }
+    resolve_at(source, containers, parent_index, chain, resolved)?;
+    chain.pop();
+
+    let parent = &containers[parent_index];
+    let arguments = parent.arguments().iter().enumerate().map(|(index, argument)| (*argument, parent.directory(index))).collect::<Vec<_>>();
+    let manifest = (*parent.manifest(), parent.manifest_directory());
+    containers[index].inherit(manifest, &arguments);
+    resolved[index] = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Parser, ErrorKind, Manifest, Argument, Protocol};
+
+    #[test]
+    fn test_inherit() {
+        let input = indoc::indoc! {"
+            @ubuntu:
+                from: ubuntu:latest
+                port: 80:8080
+                volume: /srv/cache:/cache
+                volume: /srv/data:/data
+                env: RUST_LOG=info
+
+            @ubuntu-focal:
+                extends: ubuntu
+                from: ubuntu:focal
+                port: 81:8080
+                volume: /srv/focal:/data
+                env: TZ=UTC
+
+            @ubuntu-focal-debug:
+                extends: ubuntu-focal
+                env: RUST_LOG=debug
+        "};
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+
+        let focal = parser.container("ubuntu-focal").unwrap();
+        assert_eq!(focal.manifest(), &Manifest::Image(b"ubuntu:focal"));
+        assert_eq!(focal.arguments(), &[
+            Argument::Volume { source: b"/srv/cache", mount: b"/cache", options: b"" },
+            Argument::Env { key: b"RUST_LOG", value: b"info" },
+            Argument::PublishPort { host: None, outer: 81.into(), inner: 8080.into(), protocol: Protocol::Tcp },
+            Argument::Volume { source: b"/srv/focal", mount: b"/data", options: b"" },
+            Argument::Env { key: b"TZ", value: b"UTC" },
+        ]);
+
+        let debug = parser.container("ubuntu-focal-debug").unwrap();
+        assert_eq!(debug.manifest(), &Manifest::Image(b"ubuntu:focal"));
+        assert_eq!(debug.arguments().last(), Some(&Argument::Env { key: b"RUST_LOG", value: b"debug" }));
+        assert_eq!(debug.arguments().len(), 5);
+    }
+
+    #[test]
+    fn test_parent_declared_later() {
+        let input = "@child:\n    extends: parent\n\n@parent:\n    from: alpine\n    expose: 80\n";
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+
+        let child = parser.container("child").unwrap();
+        assert_eq!(child.manifest(), &Manifest::Image(b"alpine"));
+        assert_eq!(child.arguments(), &[Argument::ExposePort { port: 80.into(), protocol: Protocol::Tcp }]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "@child:\n    extends: parent\n";
+        let error = Parser::parse(input.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "`extends` names unknown container `parent` in container `child`");
+        assert_eq!(&input[error.span().start..error.span().end], "parent");
+
+        let input = "@a:\n    extends: b\n\n@b:\n    extends: c\n    from: alpine\n\n@c:\n    extends: a\n";
+        let error = Parser::parse(input.as_bytes()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::ExtensionCycle { .. }));
+        assert_eq!(error.to_string(), "extension cycle a -> b -> c -> a");
+        assert_eq!(error.container(), Some("a"));
+
+        let error = Parser::parse(b"@a:\n    extends: a\n    from: alpine\n").unwrap_err();
+        assert_eq!(error.to_string(), "extension cycle a -> a");
+    }
+}
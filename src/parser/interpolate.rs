@@ -0,0 +1,405 @@
+use super::{ParseError, ErrorKind, Span, is_comment, strip_comment, strip_line_comment};
+use super::vars::vars;
+
+/// Keys whose values may refer to variables.
+const INTERPOLATED: &[&[u8]] = &[b"from:", b"port:", b"volume:", b"workdir:", b"user:", b"path:"];
+/// Keys whose values are paths, or hold them.
+const PATHS: &[&[u8]] = &[b"volume:", b"workdir:", b"path:"];
+
+/// A Crabfile with the `${NAME}` references of its values replaced.
+///
+/// `${NAME}` fails when the variable is unset, `${NAME:-default}` falls back to the default and
+/// `${NAME:?message}` fails with the message when it is unset or empty. Only the values of `from:`,
+/// `port:`, `volume:`, `workdir:`, `user:` and the `path:` of `@shell` are expanded, comments and the other
+/// arguments are kept as they are.
+pub struct Expanded<'a> {
+    raw: &'a [u8],
+    source: Vec<u8>,
+    substitutions: Vec<Substitution>,
+}
+
+/// Where a reference was in the Crabfile and where its value is in the expanded source.
+#[derive(Debug, Clone, Copy)]
+pub(in crate::parser) struct Substitution {
+    raw: Span,
+    expanded: Span,
+}
+
+/// A `${...}` reference found in a value.
+enum Reference<'a> {
+    Plain(&'a str),
+    Default(&'a str, &'a str),
+    Required(&'a str, &'a str),
+}
+
+impl<'a> Expanded<'a> {
+    /// Resolves the references from `lookup`, the variables of the `@vars` block filling in for what it does not set.
+    ///
+    /// Every unset variable, value that would change the lines around it and malformed reference is reported,
+    /// in source order.
+    pub fn new<F: Fn(&str) -> Option<String>>(raw: &'a [u8], lookup: F) -> Result<Self, Vec<ParseError>> {
+        let defaults = variables(raw).map_err(|err| vec![err])?;
+        let lookup = |name: &str| lookup(name).or_else(|| defaults.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone()));
+
+        let mut errors = Vec::new();
+        let expanded = Self::expand(raw, |reference, span| {
+            let (name, value) = match reference {
+                Reference::Plain(name) => (name, lookup(name)),
+                Reference::Default(name, default) => (name, lookup(name).filter(|value| !value.is_empty()).or_else(|| Some(default.to_string()))),
+                Reference::Required(name, _) => (name, lookup(name).filter(|value| !value.is_empty())),
+            };
+            let error = match value {
+                Some(value) if !is_safe(&value) => ErrorKind::UnsafeVariable { name: name.to_string() },
+                Some(value) => return Some(value),
+                None => {
+                    let message = match reference {
+                        Reference::Required(_, message) if !message.is_empty() => Some(message.to_string()),
+                        _ => None,
+                    };
+                    ErrorKind::UnsetVariable { name: name.to_string(), message }
+                },
+            };
+            errors.push(ParseError::new(error, span).in_container(enclosing_container(raw, span.start)));
+            None
+        });
+
+        match expanded {
+            Ok(expanded) if errors.is_empty() => Ok(expanded),
+            Ok(_) => Err(errors),
+            Err(invalid) => {
+                errors.extend(invalid);
+                errors.sort_by_key(|error| error.span().start);
+                Err(errors)
+            },
+        }
+    }
+
+    /// Resolves the references from the environment of the process and the `@vars` block.
+    pub fn from_env(raw: &'a [u8]) -> Result<Self, Vec<ParseError>> {
+        Self::new(raw, |name| std::env::var(name).ok())
+    }
+
+    /// Replaces every reference with its default, or a placeholder, so the Crabfile can be parsed
+    /// without depending on the environment. Only malformed references fail.
+    pub fn placeholders(raw: &'a [u8]) -> Result<Self, Vec<ParseError>> {
+        Self::expand(raw, |reference, span| match reference {
+            Reference::Default(_, default) => Some(default.to_string()),
+            Reference::Plain(_) | Reference::Required(..) => Some(placeholder(raw, span.start).to_string()),
+        })
+    }
+
+    /// The Crabfile as it was read.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    pub(in crate::parser) fn substitutions(&self) -> &[Substitution] {
+        &self.substitutions
+    }
+
+    fn expand<F>(raw: &'a [u8], mut resolve: F) -> Result<Self, Vec<ParseError>>
+        where F: FnMut(Reference, Span) -> Option<String>
+    {
+        let mut source = Vec::with_capacity(raw.len());
+        let mut substitutions = Vec::new();
+        let mut errors = Vec::new();
+        let mut in_vars = false;
+        let mut offset = 0;
+
+        for line in raw.split_inclusive(|chr| *chr == b'\n') {
+            let start = offset;
+            offset += line.len();
+            if line.starts_with(b"@") {
+                in_vars = strip_line_comment(line.trim_ascii_end()) == b"@vars:";
+            }
+            let code = line.trim_ascii_start();
+            let key = INTERPOLATED.iter().find(|key| code.starts_with(key));
+            let value = match key {
+                Some(key) if !in_vars && !is_comment(line) => {
+                    let value_start = line.len() - code.len() + key.len();
+                    value_start..value_start + strip_comment(&line[value_start..]).trim_ascii_end().len()
+                },
+                _ => {
+                    source.extend_from_slice(line);
+                    continue
+                },
+            };
+
+            source.extend_from_slice(&line[..value.start]);
+            let mut rest = value.start;
+            while let Some(found) = find(&line[rest..value.end], b"${") {
+                let reference_start = rest + found;
+                source.extend_from_slice(&line[rest..reference_start]);
+                let end = line[reference_start..value.end].iter().position(|chr| *chr == b'}').map(|end| reference_start + end + 1);
+                let span = Span::new(start + reference_start, start + end.unwrap_or(value.end));
+                rest = end.unwrap_or(value.end);
+
+                match end.and_then(|end| reference(&line[reference_start + 2..end - 1])) {
+                    Some(reference) => {
+                        let expanded_start = source.len();
+                        if let Some(value) = resolve(reference, span) {
+                            source.extend_from_slice(value.as_bytes());
+                        }
+                        substitutions.push(Substitution { raw: span, expanded: Span::new(expanded_start, source.len()) });
+                    },
+                    None => errors.push(
+                        ParseError::new(ErrorKind::InvalidInterpolation, span).in_container(enclosing_container(raw, start))
+                    ),
+                }
+            }
+            source.extend_from_slice(&line[rest..]);
+        }
+
+        match errors.is_empty() {
+            true => Ok(Expanded { raw, source, substitutions }),
+            false => Err(errors),
+        }
+    }
+}
+
+/// Maps an offset of the expanded source to the Crabfile, a span ending inside a value ending after its reference.
+pub(in crate::parser) fn raw_offset(substitutions: &[Substitution], offset: usize, end: bool) -> usize {
+    let mut mapped = offset;
+    for substitution in substitutions.iter().take_while(|substitution| substitution.expanded.start < offset) {
+        if offset < substitution.expanded.end {
+            return if end { substitution.raw.end } else { substitution.raw.start }
+        }
+        mapped = offset - substitution.expanded.end + substitution.raw.end;
+    }
+    mapped
+}
+
+/// Maps the start of a line of the Crabfile to the expanded source.
+pub(in crate::parser) fn expanded_offset(substitutions: &[Substitution], offset: usize) -> usize {
+    substitutions.iter()
+        .take_while(|substitution| substitution.raw.end <= offset)
+        .last()
+        .map_or(offset, |substitution| offset - substitution.raw.end + substitution.expanded.end)
+}
+
+/// Maps a span of the expanded source to the Crabfile.
+pub(in crate::parser) fn raw_span(substitutions: &[Substitution], span: Span) -> Span {
+    Span::new(raw_offset(substitutions, span.start, false), raw_offset(substitutions, span.end, true))
+}
+
+/// Whether a substitution was made in the expanded source between `start` and `end`.
+pub(in crate::parser) fn substituted(substitutions: &[Substitution], start: usize, end: usize) -> bool {
+    substitutions.iter().any(|substitution| substitution.expanded.start >= start && substitution.expanded.start <= end)
+}
+
+/// Name of the container block the line at `offset` belongs to.
+fn enclosing_container(source: &[u8], offset: usize) -> Option<String> {
+    let mut end = offset;
+    let lines = std::iter::from_fn(|| {
+        let start = source[..end.checked_sub(1)?].iter().rposition(|chr| *chr == b'\n').map_or(0, |pos| pos + 1);
+        let line = &source[start..end - 1];
+        end = start;
+        Some(line)
+    });
+    lines.take_while(|line| !line.is_empty())
+        .find(|line| line.starts_with(b"@"))
+        .filter(|header| !header.starts_with(b"@network "))
+        .and_then(|header| strip_line_comment(header)[1..].strip_suffix(b":"))
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+}
+
+/// The variables of the `@vars` blocks, of the Crabfile and the files it includes, the first definition first.
+fn variables(raw: &[u8]) -> Result<Vec<(String, String)>, ParseError> {
+    let mut variables = Vec::new();
+    let mut offset = 0;
+    for line in raw.split_inclusive(|chr| *chr == b'\n') {
+        if strip_line_comment(line.trim_ascii_end()) == b"@vars:" {
+            let (_, block) = vars(&raw[offset..]).map_err(|err| ParseError::from_syntax(raw, &raw[offset..], err))?;
+            variables.extend(block.iter()
+                .map(|variable| (String::from_utf8_lossy(variable.name()).into_owned(), String::from_utf8_lossy(variable.value()).into_owned())));
+        }
+        offset += line.len();
+    }
+    Ok(variables)
+}
+
+/// Whether `value` stays within the value it is expanded into: no line break or NUL ending the line,
+/// no `#` after whitespace starting a trailing comment.
+fn is_safe(value: &str) -> bool {
+    !value.contains(['\n', '\r', '\0']) && !value.contains(" #") && !value.contains("\t#")
+}
+
+/// What an unset variable without a default at `offset` becomes in [`Expanded::placeholders`]: a number,
+/// which makes a valid tag, port or user, or an absolute path where a path starts.
+fn placeholder(raw: &[u8], offset: usize) -> &'static str {
+    let line = &raw[raw[..offset].iter().rposition(|chr| *chr == b'\n').map_or(0, |pos| pos + 1)..offset];
+    match PATHS.iter().any(|key| line.trim_ascii_start().starts_with(key)) {
+        true if !line.ends_with(b"/") => "/0",
+        _ => "0",
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// The reference between `${` and `}`.
+fn reference(inner: &[u8]) -> Option<Reference<'_>> {
+    let inner = std::str::from_utf8(inner).ok()?;
+    let name_end = inner.find(|chr: char| !chr.is_ascii_alphanumeric() && chr != '_').unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    if name.is_empty() || name.starts_with(|chr: char| chr.is_ascii_digit()) {
+        return None
+    }
+
+    let rest = &inner[name_end..];
+    match rest.get(..2) {
+        None if rest.is_empty() => Some(Reference::Plain(name)),
+        Some(":-") => Some(Reference::Default(name, &rest[2..])),
+        Some(":?") => Some(Reference::Required(name, &rest[2..])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expanded;
+    use crate::parser::{Parser, ParseError, ErrorKind};
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "TAG" => Some("focal".to_string()),
+            "PORT" => Some("8080".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn expand(input: &str) -> Result<String, Vec<ParseError>> {
+        Expanded::new(input.as_bytes(), lookup).map(|expanded| String::from_utf8(expanded.source().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_expand() {
+        let input = indoc::indoc! {"
+            @shell:
+                path: ${SHELL_PATH:-/bin/zsh}
+
+            @vars:
+                TAG: latest
+                DATA: /srv/data
+
+            @app:
+                from: ubuntu:${TAG} # ${NOT_EXPANDED}
+                port: ${HOST_PORT:-80}:${PORT}
+                volume: ${DATA}:/data
+                env: RAW=${TAG}
+        "};
+
+        assert_eq!(expand(input).unwrap(), indoc::indoc! {"
+            @shell:
+                path: /bin/zsh
+
+            @vars:
+                TAG: latest
+                DATA: /srv/data
+
+            @app:
+                from: ubuntu:focal # ${NOT_EXPANDED}
+                port: 80:8080
+                volume: /srv/data:/data
+                env: RAW=${TAG}
+        "});
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(expand("@a:\n    from: alpine:${EMPTY:-3}\n").unwrap(), "@a:\n    from: alpine:3\n");
+        assert_eq!(expand("@a:\n    from: alpine${EMPTY}\n").unwrap(), "@a:\n    from: alpine\n");
+    }
+
+    #[test]
+    fn test_every_unset_variable_is_reported() {
+        let input = "@app:\n    from: ubuntu:${VERSION}\n    port: ${PORT}:${INNER:?set the inner port}\n    volume: ${EMPTY:?}:/data\n";
+
+        let errors = expand(input).unwrap_err();
+        let messages = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(messages, vec![
+            "variable `VERSION` is not set in container `app`",
+            "variable `INNER` is not set: set the inner port",
+            "variable `EMPTY` is not set in container `app`",
+        ]);
+        assert_eq!(&input[errors[1].span().start..errors[1].span().end], "${INNER:?set the inner port}");
+    }
+
+    #[test]
+    fn test_values_cannot_change_the_crabfile() {
+        let input = "@app:\n    from: ubuntu:${TAG}\n";
+        let values = [
+            "focal\n    port: 22:22\n    volume: /:/host",
+            "focal\r",
+            "focal\0",
+            "focal #latest",
+        ];
+        for value in values {
+            let errors = Expanded::new(input.as_bytes(), |_| Some(value.to_string())).err().unwrap_or_default();
+            match &errors[..] {
+                [error] => {
+                    assert!(matches!(error.kind(), ErrorKind::UnsafeVariable { .. }), "{:?}", value);
+                    assert_eq!(&input[error.span().start..error.span().end], "${TAG}");
+                    assert_eq!(error.to_string(), "variable `TAG` takes a line break, a NUL or a comment into the Crabfile in container `app`");
+                },
+                other => panic!("Unexpected errors for {:?}: {:?}", value, other),
+            }
+        }
+
+        assert_eq!(expand("@vars:\n    NAME: 'focal #1'\n\n@app:\n    from: ubuntu:${NAME}\n").unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_interpolation() {
+        let input = "@app:\n    from: ubuntu:${1TAG}\n    port: ${PORT:80}:80\n    volume: ${DATA:/data\n";
+
+        let errors = expand(input).unwrap_err();
+        let spans = errors.iter().map(|error| &input[error.span().start..error.span().end]).collect::<Vec<_>>();
+        assert!(errors.iter().all(|error| matches!(error.kind(), ErrorKind::InvalidInterpolation)));
+        assert_eq!(spans, vec!["${1TAG}", "${PORT:80}", "${DATA:/data"]);
+    }
+
+    #[test]
+    fn test_errors_point_at_the_crabfile() {
+        let input = "@app:\n    from: ubuntu:${TAG}\n    port: ${TAG}:80\n";
+        let expanded = Expanded::new(input.as_bytes(), lookup).unwrap();
+
+        let error = Parser::parse_expanded(&expanded).expect_err("the port is invalid");
+        assert!(matches!(error.kind(), ErrorKind::InvalidPort));
+        assert_eq!(&input[error.span().start..error.span().end], "${TAG}");
+
+        let input = "@a:\n    from: alpine\n    port: ${PORT}:80\n\n@b:\n    from: alpine\n    port: ${PORT:-8080}:80\n";
+        let expanded = Expanded::new(input.as_bytes(), lookup).unwrap();
+        match &Parser::parse_expanded(&expanded).unwrap().validate().unwrap_err()[..] {
+            [error] if matches!(error.kind(), ErrorKind::PortCollision { .. }) => {
+                let (span, first) = (error.span(), error.first().unwrap());
+                assert_eq!(&input[span.start..span.end], "port: ${PORT:-8080}:80");
+                assert_eq!(&input[first.start..first.end], "port: ${PORT}:80");
+            },
+            other => panic!("Unexpected errors: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_environment_overrides_vars() {
+        let input = "@vars:\n    PORT: 80\n    IMAGE: alpine\n\n@a:\n    from: ${IMAGE}\n    port: ${PORT}:80\n";
+
+        assert_eq!(expand(input).unwrap(), "@vars:\n    PORT: 80\n    IMAGE: alpine\n\n@a:\n    from: alpine\n    port: 8080:80\n");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let input = "@a:\n    from: alpine:${TAG:-3}\n    port: ${PORT}:80\n";
+        let expanded = Expanded::placeholders(input.as_bytes()).unwrap();
+
+        assert_eq!(expanded.source(), b"@a:\n    from: alpine:3\n    port: 0:80\n");
+    }
+}
@@ -1,53 +1,358 @@
 mod container;
 mod shell;
 mod path;
+mod diagnostic;
+mod error;
+mod dependency;
+mod semantic;
+mod format;
+mod vars;
+mod interpolate;
+mod include;
+mod inheritance;
+mod network;
+pub mod dotenv;
+pub mod ast;
 #[cfg(test)]
 mod tests;
 
 use path::PathLike;
 pub(in crate::parser) use nom::character::complete::{
     line_ending as newline,
-    digit1 as digit,
+    space0,
+    space1,
+    not_line_ending,
 };
-use std::collections::HashMap;
-use shell::{shell, Shell};
-use container::{container, Container};
+use std::ops::Range;
+use std::path::Path;
+use indexmap::IndexMap;
+use nom::combinator::recognize;
+use shell::shell;
+use vars::{vars, variable_value};
+use include::include;
+use container::{container, argument_value};
+use network::network;
+use interpolate::Substitution;
 
-named!(pub(in crate::parser) space<char>, char!(' '));
-named!(pub(in crate::parser) tab, alt!(tag!("\t") | tag!("    ")));
-named!(pub(in crate::parser) line_feed, alt!(newline | tag!("\0")));
+pub use shell::Shell;
+pub use diagnostic::Diagnostic;
+pub use error::{ParseError, ErrorKind, Span};
+pub(in crate::parser) use error::{SyntaxError, IResult};
+pub use container::{
+    Container, Manifest, Argument, PortRange, Protocol, PortError, VolumeKind, VolumeError, ArgvError,
+    format_publish, format_expose, split_argv, format_argv
+};
+pub use dependency::Cycle;
+pub use vars::Variable;
+pub use interpolate::Expanded;
+pub use include::Sources;
+pub use network::{Network, Subnet, SubnetError};
+
+named!(pub(in crate::parser) comment<&[u8], &[u8], SyntaxError<'_>>, complete!(recognize!(pair!(char!('#'), not_line_ending))));
+named!(trailing_comment<&[u8], &[u8], SyntaxError<'_>>, preceded!(space1, comment));
+named!(pub(in crate::parser) line_end<&[u8], &[u8], SyntaxError<'_>>, preceded!(opt!(trailing_comment), newline));
+named!(line_break<&[u8], &[u8], SyntaxError<'_>>, preceded!(opt!(trailing_comment), alt!(newline | tag!("\0"))));
+// The line break after a value, which has already checked that its line ends there, see `value_end`.
+named!(pub(in crate::parser) line_feed<&[u8], &[u8], SyntaxError<'_>>, call!(expect(ErrorKind::UnexpectedInput, line_break)));
+named!(comment_line<&[u8], &[u8], SyntaxError<'_>>, terminated!(preceded!(space0, comment), alt!(newline | eof!())));
+named!(pub(in crate::parser) comment_lines<&[u8], Vec<&[u8]>, SyntaxError<'_>>, many0!(comment_line));
+named!(separator<&[u8], &[u8], SyntaxError<'_>>, alt!(newline | comment_line));
+named!(line_rest<&[u8], &[u8], SyntaxError<'_>>, preceded!(opt!(trailing_comment), alt!(eof!() | newline | tag!("\0"))));
+// The end of a value, where a trailing comment or the end of the line follows.
+named!(pub(in crate::parser) value_end<&[u8], &[u8], SyntaxError<'_>>, peek!(line_rest));
 
-named!(parse<Parser>,
+named!(parse<&[u8], File<'_>, SyntaxError<'_>>,
     do_parse!(
-        shell: opt!(terminated!(shell, many0!(newline))) >>
-        containers: many0!(complete!(terminated!(container, many0!(newline)))) >>
-        alt!(newline | eof!()) >> (
-            Parser {
-                shell: shell.unwrap_or_default(),
-                containers: containers.into_iter().collect()
+        many0!(separator) >>
+        shell: opt!(complete!(terminated!(shell, many0!(separator)))) >>
+        vars: opt!(complete!(terminated!(vars, many0!(separator)))) >>
+        includes: many0!(complete!(terminated!(include, many0!(separator)))) >>
+        blocks: many0!(complete!(terminated!(block, many0!(separator)))) >>
+        end >> (
+            File {
+                shell,
+                vars: vars.unwrap_or_default(),
+                includes,
+                blocks
             }
         )
     )
 );
 
+named!(block<&[u8], Block<'_>, SyntaxError<'_>>, alt!(misplaced | map!(network, Block::Network) | map!(container, Block::Container)));
+
+/// Fails on a `@shell`, `@vars` or `@include` after the blocks it has to come before.
+fn misplaced(input: &[u8]) -> IResult<'_, Block<'_>> {
+    let line = rest_of_line(input).trim_ascii_end();
+    let kind = match line {
+        b"@shell:" => ErrorKind::MisplacedShell,
+        b"@vars:" => ErrorKind::MisplacedVars,
+        _ if line.starts_with(b"@include:") => ErrorKind::MisplacedInclude,
+        _ => return Err(nom::Err::Error(nom::error::make_error(input, nom::error::ErrorKind::Tag))),
+    };
+    Err(nom::Err::Failure(SyntaxError::new(kind, line)))
+}
+
+/// The end of the file, a line after the last block is not part of any.
+fn end(input: &[u8]) -> IResult<'_, ()> {
+    match indentation(input) {
+        _ if input.is_empty() => Ok((input, ())),
+        Ok((body, _)) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::UnexpectedInput, rest_of_line(body)))),
+        Err(nom::Err::Error(_)) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::UnexpectedInput, rest_of_line(input)))),
+        Err(err) => Err(err),
+    }
+}
+
+/// The indentation of a line of a block, a tab or four spaces. A line without any ends the block.
+pub(in crate::parser) fn indentation(input: &[u8]) -> IResult<'_, &[u8]> {
+    let length = input.iter().take_while(|chr| **chr == b' ' || **chr == b'\t').count();
+    let (indentation, body) = input.split_at(length);
+    match (indentation, rest_of_line(body)) {
+        (b"", _) => Err(nom::Err::Error(nom::error::make_error(input, nom::error::ErrorKind::Space))),
+        (_, b"") => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::UnexpectedIndentation, rest_of_line(input)))),
+        (b"\t" | b"    ", _) => Ok((body, indentation)),
+        _ => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::UnexpectedIndentation, indentation))),
+    }
+}
+
+/// Commits to `parser`: when it fails without knowing what is wrong, the rest of the line is wrong the way `kind` says.
+pub(in crate::parser) fn expect<'a, O, F>(kind: ErrorKind, mut parser: F) -> impl FnMut(&'a [u8]) -> IResult<'a, O>
+    where F: FnMut(&'a [u8]) -> IResult<'a, O>
+{
+    move |input| match parser(input) {
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) if error.is_known() => Err(nom::Err::Failure(error)),
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(kind.clone(), rest_of_line(input)))),
+        result => result,
+    }
+}
+
+/// The value of a `key: value` line, what `parser` takes after the `:` and a space, see [`expect`].
+pub(in crate::parser) fn value<'a, O, F>(kind: ErrorKind, parser: F) -> impl FnMut(&'a [u8]) -> IResult<'a, O>
+    where F: FnMut(&'a [u8]) -> IResult<'a, O>
+{
+    let mut parser = expect(kind.clone(), parser);
+    move |input| match input.strip_prefix(b": ") {
+        Some(value) => parser(value),
+        None => Err(nom::Err::Failure(SyntaxError::new(kind.clone(), rest_of_line(input.strip_prefix(b":").unwrap_or(input))))),
+    }
+}
+
+/// The key of a `key: value` line, up to the `:`.
+pub(in crate::parser) fn key(input: &[u8]) -> &[u8] {
+    let line = rest_of_line(input);
+    &line[..line.iter().position(|chr| *chr == b':').unwrap_or(line.len())]
+}
+
+/// The rest of the line, without the line break and the trailing comment.
+pub(in crate::parser) fn rest_of_line(input: &[u8]) -> &[u8] {
+    strip_comment(&input[..input.iter().position(|chr| b"\n\0".contains(chr)).unwrap_or(input.len())])
+}
+
+/// The blocks of a single Crabfile.
+struct File<'a> {
+    shell: Option<Shell<'a>>,
+    vars: Vec<Variable<'a>>,
+    includes: Vec<&'a [u8]>,
+    blocks: Vec<Block<'a>>,
+}
+
+/// A block after the includes, networks and containers can come in any order.
+enum Block<'a> {
+    Network(Network<'a>),
+    Container(Container<'a>),
+}
+
+/// Whether the line holds nothing but a comment.
+pub(in crate::parser) fn is_comment(line: &[u8]) -> bool {
+    line.iter().find(|chr| **chr != b' ' && **chr != b'\t') == Some(&b'#')
+}
+
+/// The text without its trailing comment, a `#` after whitespace.
+pub(in crate::parser) fn strip_comment(text: &[u8]) -> &[u8] {
+    let end = text.windows(2)
+        .position(|pair| (pair[0] == b' ' || pair[0] == b'\t') && pair[1] == b'#')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// The line without its trailing comment. An argument or a variable of the `@vars` block ends where the
+/// grammar finds the end of its value, so a `#` in a quoted value stays; other lines end at a `#` after whitespace.
+pub(in crate::parser) fn strip_line_comment(line: &[u8]) -> &[u8] {
+    let indentation = line.len() - line.trim_ascii_start().len();
+    let code = recognize(argument_value)(&line[indentation..]).or_else(|_| recognize(variable_value)(&line[indentation..]));
+    match code {
+        Ok((_, code)) => &line[..indentation + code.len()],
+        Err(_) => strip_comment(line),
+    }
+}
+
 #[cfg_attr(test, derive(Debug))]
 pub struct Parser<'a> {
+    source: &'a [u8],
+    /// The Crabfile as written, `source` with its variables expanded.
+    raw: &'a [u8],
+    substitutions: &'a [Substitution],
     shell: Shell<'a>,
-    containers: HashMap<&'a [u8], Container<'a>>
+    vars: Vec<Variable<'a>>,
+    includes: Vec<&'a [u8]>,
+    networks: IndexMap<&'a [u8], Network<'a>>,
+    containers: IndexMap<&'a [u8], Container<'a>>
 }
 
 impl<'a> Parser<'a> {
-    pub fn parse(input: &'a [u8]) -> Result<Self, nom::Err<nom::error::Error<&'a [u8]>>> {
-        parse(input).map(|res| res.1)
+    /// Parses the Crabfile as it is, `${NAME}` references are not expanded, see [`Parser::parse_expanded`],
+    /// and `@include` directives are not followed, see [`Parser::parse_sources`].
+    pub fn parse(input: &'a [u8]) -> Result<Self, ParseError> {
+        Parser::parse_files(input, &[(0..input.len(), Path::new(""))])
+    }
+
+    /// Parses a Crabfile with its variables expanded, the spans of errors pointing into the Crabfile as written.
+    pub fn parse_expanded(expanded: &'a Expanded<'a>) -> Result<Self, ParseError> {
+        Parser::parse_expanded_files(expanded, std::iter::once((0..expanded.raw().len(), Path::new(""))))
+    }
+
+    /// Parses a Crabfile and the files it includes, expanded from [`Sources::text`], into a single set of containers.
+    ///
+    /// Arguments remember the directory of the file they are written in, see [`Container::directory`].
+    pub fn parse_sources(sources: &'a Sources, expanded: &'a Expanded<'a>) -> Result<Self, ParseError> {
+        Parser::parse_expanded_files(expanded, sources.files())
+    }
+
+    fn parse_expanded_files<I>(expanded: &'a Expanded<'a>, files: I) -> Result<Self, ParseError>
+        where I: Iterator<Item=(Range<usize>, &'a Path)>
+    {
+        let substitutions = expanded.substitutions();
+        let files = files
+            .map(|(file, directory)| (
+                interpolate::expanded_offset(substitutions, file.start)..interpolate::expanded_offset(substitutions, file.end),
+                directory
+            ))
+            .collect::<Vec<_>>();
+        let parser = Parser::parse_files(expanded.source(), &files)
+            .map_err(|err| err.map_spans(|span| interpolate::raw_span(substitutions, span)))?;
+
+        Ok(Parser {
+            raw: expanded.raw(),
+            substitutions,
+            ..parser
+        })
+    }
+
+    /// Parses every file of `source` on its own, the first one including the others.
+    fn parse_files(source: &'a [u8], files: &[(Range<usize>, &'a Path)]) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            source,
+            raw: source,
+            substitutions: &[],
+            shell: Shell::default(),
+            vars: Vec::new(),
+            includes: Vec::new(),
+            networks: IndexMap::new(),
+            containers: IndexMap::new()
+        };
+
+        for (index, (range, directory)) in files.iter().enumerate() {
+            let input = &source[range.clone()];
+            let (_, file) = parse(input).map_err(|err| ParseError::from_syntax(source, input, err))?;
+
+            match file.shell {
+                Some(shell) if index > 0 => return Err(ParseError::new(ErrorKind::IncludedShell, Span::of(source, shell.path()))),
+                Some(shell) => parser.shell = shell,
+                None => {},
+            }
+            parser.vars.extend(file.vars);
+            parser.includes.extend(file.includes);
+
+            for block in file.blocks {
+                match block {
+                    Block::Network(network) => {
+                        if let Some(first) = parser.networks.get(network.name()).map(Network::name) {
+                            let name = String::from_utf8_lossy(network.name()).into_owned();
+                            return Err(ParseError::new(ErrorKind::DuplicateNetwork { name }, Span::of(source, network.name()))
+                                .with_first(Span::of(source, first)))
+                        }
+                        parser.networks.insert(network.name(), network);
+                    },
+                    Block::Container(mut container) => {
+                        if let Some(first) = parser.containers.get(container.name()).map(Container::name) {
+                            return Err(ParseError::new(ErrorKind::DuplicateContainer, Span::of(source, container.name()))
+                                .with_first(Span::of(source, first))
+                                .in_container(Some(String::from_utf8_lossy(container.name()).into_owned())))
+                        }
+                        container.in_directory(directory);
+                        parser.containers.insert(container.name(), container);
+                    },
+                }
+            }
+        }
+        inheritance::resolve(source, &mut parser.containers)?;
+        Ok(parser)
+    }
+
+    pub fn shell(&self) -> &Shell<'a> {
+        &self.shell
+    }
+
+    /// The variables of the `@vars` block.
+    pub fn vars(&self) -> &[Variable<'a>] {
+        &self.vars
+    }
+
+    /// The paths of the `@include` directives, as written.
+    pub fn includes(&self) -> &[&'a [u8]] {
+        &self.includes
+    }
+
+    /// The networks of the `@network` blocks, in the order they are declared in.
+    pub fn networks(&self) -> impl Iterator<Item=&Network<'a>> {
+        self.networks.values()
+    }
+
+    pub fn network(&self, name: &str) -> Option<&Network<'a>> {
+        self.networks.get(name.as_bytes())
+    }
+
+    /// The containers in the order they are declared in.
+    pub fn containers(&self) -> impl Iterator<Item=&Container<'a>> {
+        self.containers.values()
+    }
+
+    pub fn container(&self, name: &str) -> Option<&Container<'a>> {
+        self.containers.get(name.as_bytes())
+    }
+
+    /// Checks the containers against each other, returning every problem at once, see [`semantic::analyze`].
+    pub fn validate(&self) -> Result<(), Vec<ParseError>> {
+        match semantic::analyze(self) {
+            errors if errors.is_empty() => Ok(()),
+            errors => Err(errors.into_iter()
+                .map(|err| err.map_spans(|span| interpolate::raw_span(self.substitutions, span)))
+                .collect()),
+        }
+    }
+
+    /// The containers in the order they have to be started, see [`dependency::start_order`].
+    pub fn start_order(&self) -> Result<Vec<&Container<'a>>, Cycle> {
+        dependency::start_order(self)
+    }
+
+    /// The containers of any of `profiles` and those they take volumes from, in the order they have to
+    /// be started, see [`dependency::start_order_for`].
+    pub fn start_order_for(&self, profiles: &[&str]) -> Result<Vec<&Container<'a>>, Cycle> {
+        dependency::start_order_for(self, profiles)
+    }
+
+    /// Whether any container belongs to `profile`.
+    pub fn has_profile(&self, profile: &str) -> bool {
+        self.containers().any(|container| container.profiles().any(|name| name == profile.as_bytes()))
     }
 }
 
 #[cfg(test)]
 pub(in crate::parser) mod common {
-    type NomError<I> = nom::Err<nom::error::Error<I>>;
-    type NomErrorFmt<'a> = nom::Err<(nom::error::ErrorKind, std::borrow::Cow<'a, str>)>;
+    use super::SyntaxError;
 
-    pub fn error_fmt(err: NomError<&[u8]>) -> NomErrorFmt {
-        err.map(|e| (e.code, String::from_utf8_lossy(e.input)))
+    pub fn error_fmt(err: nom::Err<SyntaxError>) -> nom::Err<String> {
+        err.map(|e| format!("{:?}", e))
     }
 }
@@ -0,0 +1,253 @@
+use std::net::IpAddr;
+use nom::combinator::consumed;
+use super::{ErrorKind, SyntaxError, IResult, line_end, line_feed, value_end, comment_lines, indentation, expect, value, key};
+use super::container::container_name;
+
+named!(pub network<&[u8], Network<'_>, SyntaxError<'_>>,
+    do_parse!(
+        complete!(tag!("@network ")) >>
+        name: call!(expect(ErrorKind::InvalidNetworkName, container_name)) >>
+        call!(expect(ErrorKind::UnexpectedInput, line_end)) >>
+        lines: many0!(complete!(preceded!(comment_lines, preceded!(indentation, terminated!(call!(consumed(property)), line_feed))))) >>
+        call!(Network::verify_properties, &lines) >> (
+            Network {
+                name,
+                properties: lines.into_iter().map(|(_, property)| property).collect()
+            }
+        )
+    )
+);
+
+/// A line of the block, failing on an unknown key where the key is.
+fn property(input: &[u8]) -> IResult<'_, Property<'_>> {
+    let key = key(input);
+    let rest = &input[key.len()..];
+    match key {
+        b"driver" => value(ErrorKind::InvalidNetworkDriver, driver)(rest),
+        b"subnet" => value(ErrorKind::InvalidSubnet, subnet)(rest),
+        b"internal" => value(ErrorKind::InvalidInternal, internal)(rest),
+        _ => Err(nom::Err::Failure(SyntaxError::new(
+            ErrorKind::UnknownNetworkProperty { name: String::from_utf8_lossy(key).into_owned() },
+            key
+        ))),
+    }
+}
+
+named!(driver<&[u8], Property<'_>, SyntaxError<'_>>,
+    map!(terminated!(call!(Network::parse_driver), value_end), Property::Driver)
+);
+
+named!(subnet<&[u8], Property<'_>, SyntaxError<'_>>,
+    map!(
+        terminated!(verify!(call!(Network::parse_value), |value: &[u8]| Subnet::parse(value).is_some()), value_end),
+        Property::Subnet
+    )
+);
+
+named!(internal<&[u8], Property<'_>, SyntaxError<'_>>,
+    map!(terminated!(alt!(tag!("true") | tag!("false")), value_end), Property::Internal)
+);
+
+/// The names of the properties of a `@network` block.
+pub(in crate::parser) const PROPERTIES: &[&str] = &["driver", "subnet", "internal"];
+
+/// A `@network <name>:` block, a network `crab up` creates for the containers joining it.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Network<'a> {
+    name: &'a [u8],
+    properties: Vec<Property<'a>>,
+}
+
+/// A line of a `@network` block, with its value as written.
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub(in crate::parser) enum Property<'a> {
+    Driver(&'a [u8]),
+    Subnet(&'a [u8]),
+    Internal(&'a [u8]),
+}
+
+impl<'a> Property<'a> {
+    pub(in crate::parser) fn key(&self) -> &'static str {
+        match self {
+            Property::Driver(_) => "driver",
+            Property::Subnet(_) => "subnet",
+            Property::Internal(_) => "internal",
+        }
+    }
+
+    pub(in crate::parser) fn value(&self) -> &'a [u8] {
+        match self {
+            Property::Driver(value) | Property::Subnet(value) | Property::Internal(value) => value,
+        }
+    }
+}
+
+impl<'a> Network<'a> {
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// The driver the engine creates the network with, its default one when there is none.
+    pub fn driver(&self) -> Option<&'a [u8]> {
+        self.properties.iter().find_map(|property| match property {
+            Property::Driver(driver) => Some(*driver),
+            _ => None,
+        })
+    }
+
+    /// The addresses of the network, the engine picks a free range when there is none.
+    pub fn subnet(&self) -> Option<Subnet> {
+        self.properties.iter().find_map(|property| match property {
+            Property::Subnet(subnet) => Some(Subnet::parse(subnet).expect("the parser only accepts valid subnets")),
+            _ => None,
+        })
+    }
+
+    /// Whether the network is cut off from the outside, its containers only reaching each other.
+    pub fn internal(&self) -> bool {
+        self.properties.iter().any(|property| matches!(property, Property::Internal(b"true")))
+    }
+
+    /// The lines of the block in the order they are written in.
+    pub(in crate::parser) fn properties(&self) -> &[Property<'a>] {
+        &self.properties
+    }
+
+    /// Fails on the second line setting a property, pointing back at the first.
+    fn verify_properties<'i>(input: &'i [u8], lines: &[(&'i [u8], Property<'i>)]) -> IResult<'i, ()> {
+        let duplicate = lines.iter().enumerate().find_map(|(index, (line, property))| {
+            lines[..index].iter().find(|(_, earlier)| earlier.key() == property.key()).map(|(first, _)| (*line, *first))
+        });
+        match duplicate {
+            Some((line, first)) => Err(nom::Err::Failure(SyntaxError::new(ErrorKind::DuplicateArgument, line).with_first(first))),
+            None => Ok((input, ())),
+        }
+    }
+
+    /// A driver name, like `bridge` or a plugin's `vendor/driver:tag`.
+    fn parse_driver(input: &[u8]) -> IResult<'_, &[u8]> {
+        let length = input.iter()
+            .take_while(|chr| chr.is_ascii_alphanumeric() || b"_-./:".contains(chr))
+            .count();
+        match length {
+            0 => Err(nom::Err::Error(nom::error::make_error(input, nom::error::ErrorKind::AlphaNumeric))),
+            length => Ok((&input[length..], &input[..length])),
+        }
+    }
+
+    /// A value up to the trailing comment.
+    fn parse_value(input: &[u8]) -> IResult<'_, &[u8]> {
+        let length = input.iter()
+            .take_while(|chr| !chr.is_ascii_whitespace() && **chr != b'\0')
+            .count();
+        Ok((&input[length..], &input[..length]))
+    }
+}
+
+/// An address range in CIDR notation, `172.20.0.0/16` or `fd00:20::/64`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Subnet {
+    address: IpAddr,
+    prefix: u8,
+}
+
+/// A `subnet:` that is not a network address followed by its prefix length.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SubnetError;
+
+impl std::fmt::Display for SubnetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid subnet, expected a network address and prefix length, like 172.20.0.0/16")
+    }
+}
+
+impl std::error::Error for SubnetError {}
+
+impl Subnet {
+    /// `None` when the prefix is longer than the address, or the address has bits set after it.
+    pub fn new(address: IpAddr, prefix: u8) -> Option<Self> {
+        let host_bits = match address {
+            IpAddr::V4(address) if prefix <= 32 => u128::from(u32::from(address)) & (u128::from(u32::MAX) >> prefix),
+            IpAddr::V6(address) if prefix <= 128 => u128::from(address) & u128::MAX.checked_shr(u32::from(prefix)).unwrap_or(0),
+            _ => return None,
+        };
+        match host_bits {
+            0 => Some(Subnet { address, prefix }),
+            _ => None,
+        }
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    fn parse(value: &[u8]) -> Option<Self> {
+        let (address, prefix) = std::str::from_utf8(value).ok()?.split_once('/')?;
+        match prefix.bytes().all(|chr| chr.is_ascii_digit()) {
+            true => Subnet::new(address.parse().ok()?, prefix.parse().ok()?),
+            false => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Subnet {
+    type Err = SubnetError;
+
+    fn from_str(subnet: &str) -> Result<Self, Self::Err> {
+        Subnet::parse(subnet.as_bytes()).ok_or(SubnetError)
+    }
+}
+
+impl std::fmt::Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{network, Property, Subnet};
+    use crate::parser::common::error_fmt;
+
+    #[test]
+    fn test_network() {
+        let input = b"@network backend: # services only\n    driver: bridge\n    # no way out\n\tinternal: true\n    subnet: 172.20.0.0/16\n";
+
+        let result = network(input);
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (remaining, backend) = result.unwrap();
+        assert!(remaining.is_empty(), "Remaining input should be empty: {}", String::from_utf8_lossy(remaining));
+        assert_eq!(backend.name(), b"backend");
+        assert_eq!(backend.driver(), Some(&b"bridge"[..]));
+        assert_eq!(backend.subnet(), Some(Subnet::new([172, 20, 0, 0].into(), 16).unwrap()));
+        assert!(backend.internal());
+        assert_eq!(backend.properties().iter().map(Property::key).collect::<Vec<_>>(), vec!["driver", "internal", "subnet"]);
+
+        let (_, bare) = network(b"@network frontend:\n").unwrap();
+        assert_eq!((bare.driver(), bare.subnet(), bare.internal()), (None, None, false));
+    }
+
+    #[test]
+    fn test_invalid_network() {
+        assert!(network(b"@network back end:\n").is_err());
+        assert!(network(b"@network backend:\n    driver: bridge\n    driver: overlay\n").is_err());
+        assert!(network(b"@network backend:\n    internal: yes\n").is_err());
+        assert!(network(b"@network backend:\n    from: alpine\n").is_err());
+        assert!(matches!(network(b"@backend:\n"), Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn test_subnet() {
+        assert_eq!("10.0.0.0/8".parse::<Subnet>().unwrap().to_string(), "10.0.0.0/8");
+        assert_eq!("fd00:20::/64".parse::<Subnet>().unwrap().prefix(), 64);
+        assert_eq!("0.0.0.0/0".parse::<Subnet>().unwrap().prefix(), 0);
+        for invalid in ["172.20.0.1/16", "10.0.0.0/33", "10.0.0.0", "10.0.0.0/+8", "fd00::1/64", "subnet/8"] {
+            assert!(invalid.parse::<Subnet>().is_err(), "{}", invalid);
+        }
+    }
+}
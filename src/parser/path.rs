@@ -1,7 +1,7 @@
 pub trait PathLike {
     fn is_allowed(chr: char) -> bool {
         match chr {
-            '/' | '_' | '-' => true,
+            '/' | '_' | '-' | '.' => true,
             chr if chr.is_alphanumeric() => true,
             _ => false
         }
@@ -0,0 +1,341 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use super::{Parser, Container, Argument, PortRange, Protocol, ParseError, ErrorKind, Span, Cycle};
+use super::dependency::dependencies;
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Checks the containers of a parsed Crabfile against each other.
+///
+/// Every problem is returned, ordered by where it is in the source: `volume-from` naming an unknown
+/// container or the container itself, host ports published more than once on the same address and
+/// protocol, `network` naming a network without a `@network` block, `alias` on a container without a
+/// network, and dependency cycles. Ports collide whatever the profiles of their containers, `crab up`
+/// without `--profile` starts them all.
+pub(in crate::parser) fn analyze(parser: &Parser) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    let mut published: Vec<(Option<IpAddr>, Protocol, PortRange, Span)> = Vec::new();
+
+    for container in parser.containers() {
+        let owner = Some(string(container.name()));
+        for (index, argument) in container.arguments().iter().enumerate() {
+            match argument {
+                Argument::VolumeFrom { name } if *name == container.name() => errors.push(
+                    ParseError::new(ErrorKind::SelfReference, Span::of(parser.source, name)).in_container(owner.clone())
+                ),
+                Argument::VolumeFrom { name } if !parser.containers.contains_key(name) => errors.push(
+                    ParseError::new(ErrorKind::UnknownContainer { name: string(name) }, Span::of(parser.source, name))
+                        .in_container(owner.clone())
+                ),
+                Argument::Network { name } if !parser.networks.contains_key(name) => errors.push(
+                    ParseError::new(ErrorKind::UnknownNetwork { name: string(name) }, Span::of(parser.source, name))
+                        .in_container(owner.clone())
+                ),
+                // Port 0 lets the engine pick a free one, so it never collides. An inherited port is published
+                // by the container extending too, which is pointed at through its `extends:`.
+                Argument::PublishPort { host, outer, protocol, .. } if *outer != PortRange::single(0) => {
+                    let line = match container.is_inherited(index) {
+                        true => container.extends_line().unwrap_or(container.line(index)),
+                        false => container.line(index),
+                    };
+                    let span = Span::of(parser.source, line);
+                    let first = published.iter().find(|(other_host, other_protocol, other, _)| {
+                        other_protocol == protocol && other.overlaps(outer) && shares_address(*host, *other_host)
+                    });
+                    match first {
+                        Some((_, _, other, first)) => errors.push(
+                            ParseError::new(ErrorKind::PortCollision { port: outer.start().max(other.start()) }, span)
+                                .with_first(*first)
+                                .in_container(owner.clone())
+                        ),
+                        None => published.push((*host, *protocol, *outer, span)),
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let alias = container.arguments().iter().position(|argument| matches!(argument, Argument::Alias { .. }));
+        if let (Some(index), None) = (alias, container.networks().next()) {
+            errors.push(
+                ParseError::new(ErrorKind::AliasWithoutNetwork, Span::of(parser.source, container.line(index))).in_container(owner.clone())
+            );
+        }
+    }
+
+    errors.extend(cycles(parser));
+    errors.sort_by_key(|error| error.span().start);
+    errors
+}
+
+/// Whether ports bound to `host` and `other` take the same port of some address, no address or an
+/// unspecified one meaning every address of the host.
+fn shares_address(host: Option<IpAddr>, other: Option<IpAddr>) -> bool {
+    match (host, other) {
+        (Some(host), Some(other)) => host == other || host.is_unspecified() || other.is_unspecified(),
+        _ => true,
+    }
+}
+
+/// One cycle for every group of containers taking volumes from each other, pointing at the
+/// `volume-from` of the first declared one.
+fn cycles(parser: &Parser) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    let mut reported = HashSet::new();
+
+    for container in parser.containers() {
+        if reported.contains(container.name()) {
+            continue
+        }
+
+        let mut path = Vec::new();
+        if !path_back(parser, container.name(), container, &mut path, &mut HashSet::new()) {
+            continue
+        }
+
+        reported.extend(path.iter().map(|member| member.name()));
+        let next = path.get(1).map_or(container.name(), |member| member.name());
+        let link = dependencies(container).find(|name| *name == next).unwrap_or(next);
+        let cycle = Cycle {
+            path: path.iter().map(|member| member.name()).chain(Some(container.name())).map(string).collect()
+        };
+        errors.push(
+            ParseError::new(ErrorKind::DependencyCycle { cycle }, Span::of(parser.source, link)).in_container(Some(string(container.name())))
+        );
+    }
+    errors
+}
+
+/// Whether `target` can be reached from `current`, leaving the containers on the way in `path`.
+/// Self references are reported on their own and are not followed.
+fn path_back<'p, 'a>(
+    parser: &'p Parser<'a>,
+    target: &[u8],
+    current: &'p Container<'a>,
+    path: &mut Vec<&'p Container<'a>>,
+    visited: &mut HashSet<&'a [u8]>,
+) -> bool {
+    path.push(current);
+    for dependency in dependencies(current).filter(|name| *name != current.name()) {
+        if dependency == target {
+            return true
+        }
+        if let Some(next) = parser.containers.get(dependency) {
+            if visited.insert(dependency) && path_back(parser, target, next, path, visited) {
+                return true
+            }
+        }
+    }
+    path.pop();
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Parser, ParseError, ErrorKind};
+
+    fn analyze(input: &str) -> Vec<ParseError> {
+        Parser::parse(input.as_bytes()).unwrap().validate().err().unwrap_or_default()
+    }
+
+    fn messages(input: &str) -> Vec<String> {
+        analyze(input).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_valid() {
+        let input = indoc::indoc! {"
+            @app:
+                from: ubuntu:latest
+                port: 80:8080
+                port: 0:9090
+                volume-from: cache
+
+            @cache:
+                from: alpine:latest
+                port: 443:8443
+                port: 0:9090
+        "};
+
+        assert_eq!(analyze(input), vec![]);
+    }
+
+    #[test]
+    fn test_port_collisions() {
+        let input = indoc::indoc! {"
+            @dns:
+                from: alpine:latest
+                port: 53:53
+                port: 53:53/udp
+                port: 127.0.0.1:8000:80
+                port: 127.0.0.2:8000:80
+
+            @web:
+                from: alpine:latest
+                port: 8000-8010:9000-9010
+                port: [::1]:8100:80
+        "};
+
+        assert_eq!(messages(input), vec!["host port 8000 is published more than once in container `web`"]);
+    }
+
+    #[test]
+    fn test_inherited_ports() {
+        let input = indoc::indoc! {"
+            @ubuntu:
+                from: ubuntu:latest
+                port: 80:8080
+
+            @ubuntu-focal:
+                extends: ubuntu
+                from: ubuntu:focal
+                port: 81:9090
+        "};
+        let text = |span: crate::parser::Span| &input[span.start..span.end];
+
+        match &analyze(input)[..] {
+            [collision] => {
+                assert_eq!(collision.to_string(), "host port 80 is published more than once in container `ubuntu-focal`");
+                assert_eq!(text(collision.span()), "extends: ubuntu");
+                assert_eq!(text(collision.first().unwrap()), "port: 80:8080");
+            },
+            other => panic!("Unexpected errors: {:?}", other),
+        }
+
+        let overridden = input.replace("port: 81:9090", "port: 81:8080");
+        assert_eq!(analyze(&overridden), vec![]);
+    }
+
+    #[test]
+    fn test_port_collisions_across_profiles() {
+        let input = indoc::indoc! {"
+            @web:
+                from: nginx:latest
+                port: 80:80
+
+            @docs:
+                from: nginx:latest
+                port: 80:80
+                profiles: docs
+
+            @preview:
+                from: nginx:latest
+                port: 80:80
+                profiles: frontend
+
+            @storybook:
+                from: node:latest
+                port: 80:6006
+                profiles: frontend, storybook
+        "};
+
+        assert_eq!(messages(input), vec![
+            "host port 80 is published more than once in container `docs`",
+            "host port 80 is published more than once in container `preview`",
+            "host port 80 is published more than once in container `storybook`",
+        ]);
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let input = indoc::indoc! {"
+            @ubuntu:
+                from: ubuntu:latest
+                port: 80:8080
+                volume-from: cache_container
+
+            @ubuntu-focal:
+                from: ubuntu:focal
+                port: 80:8080
+                volume-from: ubuntu-focal
+
+            @a:
+                from: alpine:latest
+                volume-from: b
+
+            @b:
+                from: alpine:latest
+                volume-from: a
+        "};
+
+        assert_eq!(messages(input), vec![
+            "`volume-from` names unknown container `cache_container` in container `ubuntu`",
+            "host port 80 is published more than once in container `ubuntu-focal`",
+            "container takes volumes from itself in container `ubuntu-focal`",
+            "dependency cycle a -> b -> a",
+        ]);
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = indoc::indoc! {"
+            @app:
+                from: ubuntu:latest
+                port: 80:8080 # web
+                volume-from: data
+
+            @data:
+                from: busybox:latest
+                port: 80:80
+                volume-from: app
+        "};
+        let text = |span: crate::parser::Span| &input[span.start..span.end];
+
+        match &analyze(input)[..] {
+            [cycle, collision] if matches!(cycle.kind(), ErrorKind::DependencyCycle { .. })
+                && matches!(collision.kind(), ErrorKind::PortCollision { .. }) => {
+                assert_eq!(text(cycle.span()), "data");
+                assert_eq!(text(collision.span()), "port: 80:80");
+                assert_eq!(text(collision.first().unwrap()), "port: 80:8080");
+            },
+            other => panic!("Unexpected errors: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_networks() {
+        let input = indoc::indoc! {"
+            @network backend:
+
+            @api:
+                from: alpine:latest
+                network: backend
+                network: frontend
+                alias: api
+
+            @worker:
+                from: alpine:latest
+                alias: jobs # no network
+        "};
+
+        assert_eq!(messages(input), vec![
+            "`network` names unknown network `frontend` in container `api`",
+            "`alias` needs a `network` in container `worker`",
+        ]);
+    }
+
+    #[test]
+    fn test_cycle_reported_once() {
+        let input = indoc::indoc! {"
+            @a:
+                from: alpine:latest
+                volume-from: b
+
+            @b:
+                from: alpine:latest
+                volume-from: c
+
+            @c:
+                from: alpine:latest
+                volume-from: a
+
+            @d:
+                from: alpine:latest
+                volume-from: a
+        "};
+
+        assert_eq!(messages(input), vec!["dependency cycle a -> b -> c -> a"]);
+    }
+}
@@ -1,19 +1,17 @@
-use super::{newline, tab, line_feed, space};
+use super::{ErrorKind, SyntaxError, line_end, line_feed, value_end, comment_lines, indentation, expect, value};
 use crate::parser::PathLike;
 
 const DEFAULT_SHELL_PATH: &str = "/bin/bash";
 
-named!(pub shell<Shell>,
+named!(pub shell<&[u8], Shell<'_>, SyntaxError<'_>>,
     do_parse!(
-        tag!("@") >>
-        tag!("shell") >>
-        tag!(":") >>
-        newline >>
-        tab >>
-        tag!("path") >>
-        tag!(":") >>
-        space >>
-        path: terminated!(Shell::parse_path, line_feed)>> (
+        complete!(tag!("@shell:")) >>
+        call!(expect(ErrorKind::UnexpectedInput, line_end)) >>
+        comment_lines >>
+        call!(expect(ErrorKind::UnexpectedInput, indentation)) >>
+        call!(expect(ErrorKind::UnexpectedInput, path_key)) >>
+        path: call!(value(ErrorKind::InvalidPath, path)) >>
+        line_feed >> (
             Shell {
                 path
             }
@@ -21,10 +19,20 @@ named!(pub shell<Shell>,
     )
 );
 
+named!(path_key<&[u8], &[u8], SyntaxError<'_>>, complete!(tag!("path")));
+
+named!(path<&[u8], &[u8], SyntaxError<'_>>, terminated!(call!(Shell::parse_path), value_end));
+
 pub struct Shell<'a> {
     path: &'a [u8],
 }
 
+impl<'a> Shell<'a> {
+    pub fn path(&self) -> &'a [u8] {
+        self.path
+    }
+}
+
 impl Default for Shell<'_> {
     fn default() -> Self {
         Shell {
@@ -62,7 +70,7 @@ mod tests {
         "};
 
         let result = shell(input.as_bytes());
-        assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
         let (_, shell) = result.unwrap();
         assert_eq!(shell.path, b"/bin/bash");
     }
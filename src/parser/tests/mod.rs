@@ -1,3 +1,4 @@
+mod test_comments;
 mod test_containers;
 
-use super::{Parser, common::error_fmt};
+use super::Parser;
@@ -0,0 +1,89 @@
+use super::Parser;
+use crate::parser::{Argument, Protocol, strip_line_comment};
+
+#[test]
+fn test_parsing_with_comments() {
+    let input = indoc::indoc! {"
+    # Toolchain containers
+    @shell: # the default is bash
+        # zsh has the nicer prompt
+        path: /bin/zsh # installed in every image
+
+    # Build environment
+    @ubuntu: # LTS only
+        # pinned: latest breaks the linker
+        from: ubuntu:focal # focal
+        port: 80:8080 # why this port: the proxy expects it
+      # indentation of comments does not matter
+        expose: 443
+    # the cache is shared
+    @cache:
+        from: alpine:latest
+        volume-from: ubuntu # keeps the build output
+    # end of file"};
+    let result = Parser::parse(input.as_bytes());
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+    let ast = result.unwrap();
+
+    assert_eq!(ast.shell, "/bin/zsh");
+    assert_eq!(ast.containers.len(), 2);
+    assert_eq!(ast.container("ubuntu").unwrap().arguments(), &[
+        Argument::PublishPort { host: None, outer: 80.into(), inner: 8080.into(), protocol: Protocol::Tcp },
+        Argument::ExposePort { port: 443.into(), protocol: Protocol::Tcp },
+    ]);
+    assert_eq!(ast.container("cache").unwrap().arguments(), &[Argument::VolumeFrom { name: b"ubuntu" }]);
+}
+
+#[test]
+fn test_parsing_only_comments() {
+    let result = Parser::parse(b"# nothing here yet\n\n# really\n");
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+    assert_eq!(result.unwrap().containers.len(), 0);
+}
+
+#[test]
+fn test_comments_keep_duplicate_check() {
+    let input = indoc::indoc! {"
+    @ubuntu:
+        from: ubuntu:latest
+        port: 80:8080 # first
+        # the same port again
+        port: 80:8080 # second
+    "};
+    assert!(Parser::parse(input.as_bytes()).is_err());
+}
+
+#[test]
+fn test_comments_keep_indentation_rule() {
+    let input = indoc::indoc! {"
+    @ubuntu:
+        from: ubuntu:latest
+        # fine
+      port: 80:8080
+    "};
+    assert!(Parser::parse(input.as_bytes()).is_err());
+}
+
+#[test]
+fn test_comments_after_quoted_values() {
+    let input = indoc::indoc! {r#"
+    @vars:
+        GREETING: "hello # world" # quoted
+    @app:
+        from: alpine:latest
+        env: GREETING="hello # world" # quoted
+        env: LEVEL=1 # see: 'levels # 1-3'
+    "#};
+    let ast = Parser::parse(input.as_bytes()).unwrap();
+    let app = ast.container("app").unwrap();
+
+    assert_eq!(ast.vars()[0].value(), b"hello # world");
+    assert_eq!(app.arguments(), &[
+        Argument::Env { key: b"GREETING", value: b"hello # world" },
+        Argument::Env { key: b"LEVEL", value: b"1" },
+    ]);
+    assert_eq!([app.line(0), app.line(1)], [&br#"env: GREETING="hello # world""#[..], b"env: LEVEL=1"]);
+    assert_eq!(strip_line_comment(br#"    GREETING: "hello # world" # quoted"#), br#"    GREETING: "hello # world""#);
+    assert_eq!(strip_line_comment(b"    env: LEVEL=1 # see: 'levels # 1-3'"), b"    env: LEVEL=1");
+    assert_eq!(strip_line_comment(b"    from: alpine:latest # pinned"), b"    from: alpine:latest");
+}
@@ -1,4 +1,4 @@
-use super::{Parser, error_fmt};
+use super::Parser;
 
 #[test]
 fn test_parsing_with_one_container() {
@@ -7,11 +7,11 @@ fn test_parsing_with_one_container() {
         from: ubuntu:latest
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 1);
+    assert_eq!(ast.containers.len(), 1);
 }
 
 #[test]
@@ -21,7 +21,7 @@ fn test_parsing_default_shell() {
         from: ubuntu:latest
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert_eq!(ast.shell, "/bin/bash");
@@ -37,12 +37,12 @@ fn test_parsing_with_one_container_and_shell() {
         from: ubuntu:latest
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert_eq!(ast.shell, "/bin/zsh");
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 1);
+    assert_eq!(ast.containers.len(), 1);
 }
 
 #[test]
@@ -58,13 +58,13 @@ fn test_parsing_with_multiple_container() {
         from: ubuntu:bionic
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-focal".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-bionic".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 3);
+    assert_eq!(ast.containers.len(), 3);
 }
 
 #[test]
@@ -83,14 +83,36 @@ fn test_parsing_with_multiple_container_with_shell() {
         from: ubuntu:bionic
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert_eq!(ast.shell, "/bin/zsh");
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-focal".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-bionic".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 3);
+    assert_eq!(ast.containers.len(), 3);
+}
+
+#[test]
+fn test_parsing_with_vars() {
+    let input = indoc::indoc! {"
+    @shell:
+        path: /bin/zsh
+
+    # defaults for the environment
+    @vars:
+        TAG: latest
+
+    @ubuntu:
+        from: ubuntu:latest
+    "};
+    let result = Parser::parse(input.as_bytes());
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+    let ast = result.unwrap();
+
+    assert_eq!(ast.shell, "/bin/zsh");
+    assert_eq!(ast.vars().iter().map(|variable| (variable.name(), variable.value())).collect::<Vec<_>>(), vec![(&b"TAG"[..], &b"latest"[..])]);
+    assert_eq!(ast.containers.len(), 1);
 }
 
 #[test]
@@ -105,7 +127,7 @@ fn test_parsing_with_container_arguments() {
         volume: /home/apple:/home/peach
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert_eq!(ast.shell, "/bin/bash");
@@ -132,14 +154,14 @@ fn test_parsing_with_multiple_container_with_arguments() {
         volume: /usr/lib/:/usr/share/lib
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert_eq!(ast.shell, "/bin/zsh");
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-focal".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-bionic".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 3);
+    assert_eq!(ast.containers.len(), 3);
 }
 
 #[test]
@@ -161,13 +183,13 @@ fn test_parsing_with_multiple_container_separated_with_multiple_newline() {
 
     "};
     let result = Parser::parse(input.as_bytes());
-    assert!(result.is_ok(), format!("Error: {:?}", result.err().map(error_fmt)));
+    assert!(result.is_ok(), "Error: {:?}", result.err());
     let ast = result.unwrap();
 
     assert!(ast.containers.contains_key("ubuntu".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-focal".as_bytes()));
     assert!(ast.containers.contains_key("ubuntu-bionic".as_bytes()));
-    assert_eq!(ast.containers.iter().count(), 3);
+    assert_eq!(ast.containers.len(), 3);
 }
 
 #[test]
@@ -243,4 +265,20 @@ fn test_parsing_misplaced_shell() {
     "};
     let result = Parser::parse(input.as_bytes());
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parsing_keeps_declaration_order() {
+    let input = indoc::indoc! {"
+    @zeta:
+        from: ubuntu:latest
+    @alpha:
+        from: alpine:latest
+    @mid:
+        from: busybox:latest
+    "};
+    let ast = Parser::parse(input.as_bytes()).unwrap();
+
+    let names: Vec<&[u8]> = ast.containers().map(|container| container.name()).collect();
+    assert_eq!(names, vec![&b"zeta"[..], b"alpha", b"mid"]);
+}
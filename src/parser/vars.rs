@@ -0,0 +1,76 @@
+use super::{ErrorKind, SyntaxError, space0, line_end, line_feed, value_end, comment_lines, indentation, expect};
+use super::container::Argument;
+
+named!(pub vars<&[u8], Vec<Variable<'_>>, SyntaxError<'_>>,
+    do_parse!(
+        complete!(tag!("@vars:")) >>
+        call!(expect(ErrorKind::UnexpectedInput, line_end)) >>
+        variables: many0!(complete!(preceded!(
+            comment_lines,
+            preceded!(indentation, call!(expect(ErrorKind::InvalidVariable, variable)))
+        ))) >>
+        (variables)
+    )
+);
+
+named!(variable<&[u8], Variable<'_>, SyntaxError<'_>>, terminated!(variable_value, line_feed));
+
+// A variable up to the end of its value, where a trailing comment may start.
+named!(pub(in crate::parser) variable_value<&[u8], Variable<'_>, SyntaxError<'_>>,
+    do_parse!(
+        name: call!(Argument::parse_env_key) >>
+        tag!(":") >>
+        space0 >>
+        value: call!(Argument::parse_env_value) >>
+        value_end >> (
+            Variable {
+                name,
+                value
+            }
+        )
+    )
+);
+
+/// A `NAME: value` line of the `@vars` block, a default for `${NAME}` when the environment does not set it.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Variable<'a> {
+    name: &'a [u8],
+    value: &'a [u8],
+}
+
+impl<'a> Variable<'a> {
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vars, Variable};
+    use crate::parser::common::error_fmt;
+
+    #[test]
+    fn test_vars() {
+        let input = b"@vars: # defaults\n    TAG: latest\n    # quoted keeps the spaces\n\tGREETING: ' hello '\n    EMPTY:\n";
+
+        let result = vars(input);
+        assert!(result.is_ok(), "Error: {:?}", result.err().map(error_fmt));
+        let (remaining, variables) = result.unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(variables, vec![
+            Variable { name: b"TAG", value: b"latest" },
+            Variable { name: b"GREETING", value: b" hello " },
+            Variable { name: b"EMPTY", value: b"" },
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_variable() {
+        assert!(matches!(vars(b"@vars:\n    1TAG: latest\n"), Err(nom::Err::Failure(_))));
+        assert!(matches!(vars(b"@vars:\n    TAG latest\n"), Err(nom::Err::Failure(_))));
+    }
+}
@@ -0,0 +1,529 @@
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use crate::parser::{Container, Network, Manifest, Argument, format_publish, format_expose};
+use super::{ContainerRuntime, Error, State, terminal, environment, volume_source, argv};
+
+/// Drives an engine through its command-line client, `docker` or anything compatible with it.
+pub struct Cli {
+    program: String,
+    directory: PathBuf,
+}
+
+impl Cli {
+    pub fn new<P: Into<String>>(program: P) -> Self {
+        Cli {
+            program: program.into(),
+            directory: PathBuf::new()
+        }
+    }
+
+    /// Resolves relative volume sources and env files, and builds images from Dockerfiles, in `directory` instead of the current one.
+    pub fn in_directory<P: AsRef<Path>>(mut self, directory: P) -> Self {
+        self.directory = directory.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn docker() -> Self {
+        Self::new("docker")
+    }
+
+    pub fn podman() -> Self {
+        Self::new("podman")
+    }
+
+    /// Tag of the image built for a container whose manifest is a Dockerfile.
+    pub fn image_tag(container: &Container) -> String {
+        format!("crab-{}", String::from_utf8_lossy(container.name()))
+    }
+
+    /// The `create`/`run` options describing the container, followed by its image and command.
+    ///
+    /// `--entrypoint` only takes the program, the arguments of a longer entrypoint come first in the command.
+    /// The container is created on its first network only, see [`Cli::connections`] for the others.
+    pub fn options(&self, container: &Container) -> Result<Vec<String>, Error> {
+        let mut options = vec!["--name".to_string(), String::from_utf8_lossy(container.name()).into_owned()];
+        let mut command = Vec::new();
+        let mut entrypoint = Vec::new();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        for (index, argument) in container.arguments().iter().enumerate() {
+            match argument {
+                Argument::PublishPort { host, outer, inner, protocol } => options.extend(vec![
+                    "-p".to_string(),
+                    format_publish(*host, *outer, *inner, *protocol)
+                ]),
+                Argument::ExposePort { port, protocol } => options.extend(vec!["--expose".to_string(), format_expose(*port, *protocol)]),
+                Argument::Volume { source, mount, options: volume } => {
+                    let source = volume_source(source, &self.directory.join(container.directory(index)), home.as_deref());
+                    options.extend(vec![
+                        "-v".to_string(),
+                        match volume.is_empty() {
+                            true => format!("{}:{}", source, String::from_utf8_lossy(mount)),
+                            false => format!("{}:{}:{}", source, String::from_utf8_lossy(mount), String::from_utf8_lossy(volume)),
+                        }
+                    ])
+                },
+                Argument::VolumeFrom { name } => options.extend(vec![
+                    "--volumes-from".to_string(),
+                    String::from_utf8_lossy(name).into_owned()
+                ]),
+                Argument::Command { args } => command = argv(args),
+                Argument::Entrypoint { args } => {
+                    entrypoint = argv(args);
+                    options.extend(vec!["--entrypoint".to_string(), entrypoint.remove(0)]);
+                },
+                Argument::Workdir { path } => options.extend(vec!["-w".to_string(), String::from_utf8_lossy(path).into_owned()]),
+                Argument::User { user } => options.extend(vec!["-u".to_string(), String::from_utf8_lossy(user).into_owned()]),
+                Argument::Network { name } if container.networks().next() == Some(*name) => options.extend(vec![
+                    "--network".to_string(),
+                    String::from_utf8_lossy(name).into_owned()
+                ]),
+                Argument::Alias { name } => options.extend(vec!["--network-alias".to_string(), String::from_utf8_lossy(name).into_owned()]),
+                Argument::Network { .. } => {},
+                Argument::Env { .. } | Argument::EnvFile { .. } | Argument::Profiles { .. } => {},
+            }
+        }
+        for (key, value) in environment(container, &self.directory, home.as_deref())? {
+            options.extend(vec!["-e".to_string(), format!("{}={}", key, value)]);
+        }
+
+        options.push(match container.manifest() {
+            Manifest::Image(image) => String::from_utf8_lossy(image).into_owned(),
+            Manifest::File(_) => Self::image_tag(container),
+        });
+        options.extend(entrypoint);
+        options.extend(command);
+        Ok(options)
+    }
+
+    /// The `network connect` arguments joining the container to its networks after the first one, with its aliases.
+    pub fn connections(container: &Container) -> Vec<Vec<String>> {
+        container.networks().skip(1)
+            .map(|network| {
+                let mut connect = vec!["network".to_string(), "connect".to_string()];
+                for alias in container.aliases() {
+                    connect.extend(vec!["--alias".to_string(), String::from_utf8_lossy(alias).into_owned()]);
+                }
+                connect.push(String::from_utf8_lossy(network).into_owned());
+                connect.push(String::from_utf8_lossy(container.name()).into_owned());
+                connect
+            })
+            .collect()
+    }
+
+    /// The `network create` arguments for the network of a `@network` block.
+    pub fn network_options(network: &Network) -> Vec<String> {
+        let mut options = vec!["network".to_string(), "create".to_string()];
+        if let Some(driver) = network.driver() {
+            options.extend(vec!["--driver".to_string(), String::from_utf8_lossy(driver).into_owned()]);
+        }
+        if let Some(subnet) = network.subnet() {
+            options.extend(vec!["--subnet".to_string(), subnet.to_string()]);
+        }
+        if network.internal() {
+            options.push("--internal".to_string());
+        }
+        options.push(String::from_utf8_lossy(network.name()).into_owned());
+        options
+    }
+
+    /// The command creating the network of a `@network` block.
+    pub fn network_plan(&self, network: &Network) -> Vec<String> {
+        std::iter::once(self.program.clone()).chain(Self::network_options(network)).collect()
+    }
+
+    /// The `build` arguments for a container built from `file`, with the directory of the file declaring the
+    /// Dockerfile as the context.
+    fn build_options(&self, container: &Container, file: &[u8]) -> Vec<String> {
+        // Going through the components drops the trailing `/` joining an empty directory leaves.
+        let directory = self.directory.join(container.manifest_directory()).components().collect::<PathBuf>();
+        let context = match directory.as_os_str().is_empty() {
+            true => ".".to_string(),
+            false => directory.to_string_lossy().into_owned(),
+        };
+        vec![
+            "build".to_string(),
+            "-f".to_string(), directory.join(String::from_utf8_lossy(file).as_ref()).to_string_lossy().into_owned(),
+            "-t".to_string(), Self::image_tag(container),
+            context,
+        ]
+    }
+
+    /// The commands `crab up` amounts to for the container: the image build when there is a Dockerfile, then `run`
+    /// and the `network connect` of every further network.
+    pub fn plan(&self, container: &Container) -> Result<Vec<Vec<String>>, Error> {
+        let mut commands = Vec::new();
+        if let Manifest::File(file) = container.manifest() {
+            commands.push(std::iter::once(self.program.clone()).chain(self.build_options(container, file)).collect());
+        }
+
+        let mut run = vec![self.program.clone(), "run".to_string(), "-d".to_string()];
+        run.extend(self.options(container)?);
+        commands.push(run);
+        commands.extend(Self::connections(container).into_iter().map(|connect| std::iter::once(self.program.clone()).chain(connect).collect()));
+        Ok(commands)
+    }
+
+    fn command<I, S>(&self, args: I) -> Command
+        where I: IntoIterator<Item=S>,
+              S: AsRef<std::ffi::OsStr>,
+    {
+        let mut command = Command::new(&self.program);
+        command.args(args);
+        command
+    }
+
+    /// Runs the command to completion, returning its standard output.
+    fn output(&self, mut command: Command) -> Result<String, Error> {
+        let output = command.stdin(Stdio::null()).output()?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            Err(Error::Command {
+                command: format!("{:?}", command).replace('"', ""),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string()
+            })
+        }
+    }
+
+    /// Runs the command on the current terminal, returning its exit code.
+    ///
+    /// The client puts the terminal into raw mode and follows its size itself. It shares crab's
+    /// terminal, so it gets the signals typed there too, only the ones sent to crab are passed on.
+    fn interactive(&self, mut command: Command) -> Result<i32, Error> {
+        let signals = terminal::Signals::catch();
+        let mut child = command.spawn()?;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status.code().or_else(|| status.signal().map(|signal| 128 + signal)).unwrap_or(1))
+            }
+            for signal in signals.take() {
+                if signal == libc::SIGTERM || signal == libc::SIGHUP {
+                    unsafe { libc::kill(child.id() as libc::pid_t, signal) };
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    fn lifecycle(&self, action: &str, name: &str) -> Result<(), Error> {
+        match self.output(self.command([action, name])) {
+            Err(Error::Command { message, .. }) if is_missing(&message) => Err(Error::NoSuchContainer(name.to_string())),
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+impl ContainerRuntime for Cli {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn create(&self, container: &Container) -> Result<String, Error> {
+        if let Manifest::File(file) = container.manifest() {
+            self.output(self.command(self.build_options(container, file)))?;
+        }
+
+        let mut command = self.command(["create"]);
+        command.args(self.options(container)?);
+        let id = self.output(command)?;
+        for connect in Self::connections(container) {
+            self.output(self.command(connect))?;
+        }
+        Ok(id)
+    }
+
+    fn start(&self, name: &str) -> Result<(), Error> {
+        self.lifecycle("start", name)
+    }
+
+    fn stop(&self, name: &str) -> Result<(), Error> {
+        self.lifecycle("stop", name)
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Error> {
+        self.lifecycle("rm", name)
+    }
+
+    fn inspect(&self, name: &str) -> Result<Option<State>, Error> {
+        let format = "{{.Id}} {{.State.Status}} {{.State.Running}}";
+        let output = match self.output(self.command(["container", "inspect", "--format", format, name])) {
+            Err(Error::Command { message, .. }) if is_missing(&message) => return Ok(None),
+            output => output?,
+        };
+
+        match output.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [id, status, running] => Ok(Some(State {
+                id: id.to_string(),
+                status: status.to_string(),
+                running: *running == "true",
+            })),
+            _ => Err(Error::Protocol(format!("unexpected inspect output `{}`", output))),
+        }
+    }
+
+    fn exec(&self, name: &str, command: &[String], tty: bool) -> Result<i32, Error> {
+        let mut exec = self.command(["exec"]);
+        if tty {
+            exec.arg("-it");
+        }
+        exec.arg(name).args(command);
+        self.interactive(exec)
+    }
+
+    fn attach(&self, name: &str) -> Result<i32, Error> {
+        self.interactive(self.command(["attach", name]))
+    }
+
+    fn logs(&self, name: &str) -> Result<String, Error> {
+        let output = self.command(["logs", name]).stdin(Stdio::null()).output()?;
+        if !output.status.success() && is_missing(&String::from_utf8_lossy(&output.stderr)) {
+            return Err(Error::NoSuchContainer(name.to_string()))
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr))
+    }
+
+    fn create_network(&self, network: &Network) -> Result<(), Error> {
+        self.output(self.command(Self::network_options(network))).map(|_| ())
+    }
+
+    fn remove_network(&self, name: &str) -> Result<(), Error> {
+        match self.output(self.command(["network", "rm", name])) {
+            Err(Error::Command { message, .. }) if is_missing_network(&message) => Err(Error::NoSuchNetwork(name.to_string())),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn has_network(&self, name: &str) -> Result<bool, Error> {
+        match self.output(self.command(["network", "inspect", "--format", "{{.Name}}", name])) {
+            Err(Error::Command { message, .. }) if is_missing_network(&message) => Ok(false),
+            result => result.map(|_| true),
+        }
+    }
+}
+
+/// Both docker and podman say "no such container" when the name is unknown.
+fn is_missing(message: &str) -> bool {
+    message.to_lowercase().contains("no such container") || message.to_lowercase().contains("no such object")
+}
+
+/// Docker says "no such network" or "network ... not found", podman "network not found".
+fn is_missing_network(message: &str) -> bool {
+    message.to_lowercase().contains("no such network") || message.to_lowercase().contains("not found")
+}
+
+pub(super) fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|path| path.join(program).is_file()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::parser::{Parser, Sources, Expanded};
+    use crate::runtime::{ContainerRuntime, Error, TempDir};
+    use super::Cli;
+
+    /// Writes an executable stand-in for `docker` that answers with `script`, removed with the directory.
+    ///
+    /// `install` writes the executable in a process of its own: a test thread forking while this one holds
+    /// it open for writing would otherwise make running it fail with ETXTBSY.
+    fn fake_engine(name: &str, script: &str) -> (Cli, TempDir) {
+        let directory = TempDir::new(name);
+        let script_path = directory.path().join("script");
+        let engine = directory.path().join("engine");
+        std::fs::write(&script_path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        let status = std::process::Command::new("install").arg("-m").arg("755").arg(&script_path).arg(&engine).status().unwrap();
+        assert!(status.success());
+        (Cli::new(engine.to_string_lossy()), directory)
+    }
+
+    #[test]
+    fn test_options() {
+        let input = indoc::indoc! {"
+            @ubuntu:
+                from: ubuntu:latest
+                port: 80:8080
+                port: [::1]:5353:53/udp
+                volume: /usr/lib/:/usr/share/lib
+                volume: ./src:/app:ro,z
+                volume-from: cache_container
+                env: RUST_LOG=debug
+                expose: 443
+                expose: 9000-9001/sctp
+        "};
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+
+        assert_eq!(Cli::docker().in_directory("/srv/app").options(parser.container("ubuntu").unwrap()).unwrap(), vec![
+            "--name", "ubuntu",
+            "-p", "80:8080",
+            "-p", "[::1]:5353:53/udp",
+            "-v", "/usr/lib/:/usr/share/lib",
+            "-v", "/srv/app/src:/app:ro,z",
+            "--volumes-from", "cache_container",
+            "--expose", "443",
+            "--expose", "9000-9001/sctp",
+            "-e", "RUST_LOG=debug",
+            "ubuntu:latest",
+        ]);
+    }
+
+    #[test]
+    fn test_included_volumes() {
+        let sources = Sources::load_with(Path::new("Crabfile"), |path| match path.to_str() {
+            Some("Crabfile") => Ok(b"@include: shared/cache.crab\n\n@app:\n    extends: cache\n    volume: ./src:/app\n".to_vec()),
+            Some("shared/cache.crab") => Ok(b"@cache:\n    from: redis:7\n    volume: ./data:/data\n".to_vec()),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "not found")),
+        }).unwrap();
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let parser = Parser::parse_sources(&sources, &expanded).unwrap();
+
+        assert_eq!(Cli::docker().in_directory("/srv/app").options(parser.container("app").unwrap()).unwrap(), vec![
+            "--name", "app",
+            "-v", "/srv/app/shared/data:/data",
+            "-v", "/srv/app/src:/app",
+            "redis:7",
+        ]);
+    }
+
+    #[test]
+    fn test_options_with_dockerfile() {
+        let parser = Parser::parse(b"@ubuntu:\n    from: Dockerfile.ubuntu\n").unwrap();
+
+        assert_eq!(Cli::docker().options(parser.container("ubuntu").unwrap()).unwrap(), vec!["--name", "ubuntu", "crab-ubuntu"]);
+    }
+
+    #[test]
+    fn test_options_with_process() {
+        let input = indoc::indoc! {r#"
+            @toolchain:
+                from: rust:1
+                entrypoint: /usr/bin/env RUST_BACKTRACE=1
+                command: sh -c "cargo build && sleep infinity"
+                workdir: /workspace
+                user: 1000:1000
+        "#};
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+
+        assert_eq!(Cli::docker().options(parser.container("toolchain").unwrap()).unwrap(), vec![
+            "--name", "toolchain",
+            "--entrypoint", "/usr/bin/env",
+            "-w", "/workspace",
+            "-u", "1000:1000",
+            "rust:1",
+            "RUST_BACKTRACE=1", "sh", "-c", "cargo build && sleep infinity",
+        ]);
+    }
+
+    #[test]
+    fn test_plan() {
+        let parser = Parser::parse(b"@ubuntu:\n    from: Dockerfile.ubuntu\n    port: 80:8080\n").unwrap();
+
+        assert_eq!(Cli::podman().plan(parser.container("ubuntu").unwrap()).unwrap(), vec![
+            vec!["podman", "build", "-f", "Dockerfile.ubuntu", "-t", "crab-ubuntu", "."],
+            vec!["podman", "run", "-d", "--name", "ubuntu", "-p", "80:8080", "crab-ubuntu"],
+        ]);
+        assert_eq!(Cli::docker().in_directory("other").plan(parser.container("ubuntu").unwrap()).unwrap()[0], vec![
+            "docker", "build", "-f", "other/Dockerfile.ubuntu", "-t", "crab-ubuntu", "other",
+        ]);
+    }
+
+    #[test]
+    fn test_included_plan() {
+        let sources = Sources::load_with(Path::new("Crabfile"), |path| match path.to_str() {
+            Some("Crabfile") => Ok(b"@include: web/web.crab\n\n@api:\n    extends: web\n\n@worker:\n    extends: web\n    from: Dockerfile.worker\n".to_vec()),
+            Some("web/web.crab") => Ok(b"@web:\n    from: Dockerfile\n".to_vec()),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "not found")),
+        }).unwrap();
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let parser = Parser::parse_sources(&sources, &expanded).unwrap();
+
+        for (name, file, context) in [("web", "web/Dockerfile", "web"), ("api", "web/Dockerfile", "web"), ("worker", "Dockerfile.worker", ".")] {
+            assert_eq!(Cli::docker().plan(parser.container(name).unwrap()).unwrap()[0], vec![
+                "docker".to_string(), "build".to_string(), "-f".to_string(), file.to_string(), "-t".to_string(), format!("crab-{}", name), context.to_string(),
+            ]);
+        }
+        assert_eq!(Cli::docker().in_directory("/srv/app").plan(parser.container("api").unwrap()).unwrap()[0], vec![
+            "docker", "build", "-f", "/srv/app/web/Dockerfile", "-t", "crab-api", "/srv/app/web",
+        ]);
+    }
+
+    #[test]
+    fn test_networks() {
+        let input = indoc::indoc! {"
+            @network backend:
+                driver: bridge
+                subnet: 172.20.0.0/16
+                internal: true
+
+            @network frontend:
+
+            @api:
+                from: alpine:latest
+                network: frontend
+                network: backend
+                alias: api
+                alias: api-v1
+        "};
+        let parser = Parser::parse(input.as_bytes()).unwrap();
+        let api = parser.container("api").unwrap();
+
+        assert_eq!(Cli::docker().options(api).unwrap(), vec![
+            "--name", "api", "--network", "frontend", "--network-alias", "api", "--network-alias", "api-v1", "alpine:latest",
+        ]);
+        assert_eq!(Cli::docker().plan(api).unwrap()[1], vec![
+            "docker", "network", "connect", "--alias", "api", "--alias", "api-v1", "backend", "api",
+        ]);
+        assert_eq!(Cli::podman().network_plan(parser.network("backend").unwrap()), vec![
+            "podman", "network", "create", "--driver", "bridge", "--subnet", "172.20.0.0/16", "--internal", "backend",
+        ]);
+        assert_eq!(Cli::network_options(parser.network("frontend").unwrap()), vec!["network", "create", "frontend"]);
+    }
+
+    #[test]
+    fn test_network_lifecycle() {
+        let (engine, _directory) = fake_engine("network", indoc::indoc! {r#"
+            case "$2 $3 $5" in
+                "inspect --format backend") echo backend ;;
+                "inspect "*) echo "Error response from daemon: network $5 not found" >&2; exit 1 ;;
+                "rm missing "*) echo "Error: No such network: $3" >&2; exit 1 ;;
+            esac
+        "#});
+
+        assert!(engine.has_network("backend").unwrap());
+        assert!(!engine.has_network("frontend").unwrap());
+        engine.remove_network("backend").unwrap();
+        assert!(matches!(engine.remove_network("missing"), Err(Error::NoSuchNetwork(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn test_inspect() {
+        let (engine, _directory) = fake_engine("inspect", indoc::indoc! {r#"
+            case "$5" in
+                ubuntu) echo "4fa6e0f0c678 exited false" ;;
+                *) echo "Error: No such container: $5" >&2; exit 1 ;;
+            esac
+        "#});
+
+        let state = engine.inspect("ubuntu").unwrap().unwrap();
+        assert_eq!((state.id.as_str(), state.status.as_str(), state.running), ("4fa6e0f0c678", "exited", false));
+        assert!(engine.inspect("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_create() {
+        let (engine, _directory) = fake_engine("create", r#"echo "$@" >&2; [ "$1" = create ] && echo 4fa6e0f0c678"#);
+        let parser = Parser::parse(b"@ubuntu:\n    from: ubuntu:latest\n    port: 80:8080\n").unwrap();
+
+        assert_eq!(engine.create(parser.container("ubuntu").unwrap()).unwrap(), "4fa6e0f0c678");
+    }
+
+    #[test]
+    fn test_failure() {
+        let (engine, _directory) = fake_engine("failure", r#"echo "Error: No such container: $2" >&2; exit 1"#);
+
+        assert!(matches!(engine.start("ubuntu"), Err(Error::NoSuchContainer(name)) if name == "ubuntu"));
+        assert_eq!(engine.exec("ubuntu", &["true".to_string()], false).unwrap(), 1);
+    }
+}
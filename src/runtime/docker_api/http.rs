@@ -0,0 +1,220 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use crate::runtime::Error;
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// A connection to the engine, which an upgraded request reads from and writes to at the same time.
+pub trait Stream: Read + Write + Send {
+    fn try_clone(&self) -> std::io::Result<Box<dyn Stream>>;
+
+    fn shutdown(&self) -> std::io::Result<()>;
+}
+
+impl Stream for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> std::io::Result<Box<dyn Stream>> {
+        Ok(Box::new(std::os::unix::net::UnixStream::try_clone(self)?))
+    }
+
+    fn shutdown(&self) -> std::io::Result<()> {
+        std::os::unix::net::UnixStream::shutdown(self, std::net::Shutdown::Both)
+    }
+}
+
+impl Stream for std::net::TcpStream {
+    fn try_clone(&self) -> std::io::Result<Box<dyn Stream>> {
+        Ok(Box::new(std::net::TcpStream::try_clone(self)?))
+    }
+
+    fn shutdown(&self) -> std::io::Result<()> {
+        std::net::TcpStream::shutdown(self, std::net::Shutdown::Both)
+    }
+}
+
+/// Where the Docker Engine API listens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl Endpoint {
+    /// Reads `DOCKER_HOST`, falling back to the default Unix socket.
+    pub fn from_env() -> Result<Self, Error> {
+        match std::env::var("DOCKER_HOST") {
+            Ok(host) if !host.is_empty() => Self::parse(&host),
+            _ => Ok(Endpoint::Unix(PathBuf::from(DEFAULT_SOCKET))),
+        }
+    }
+
+    pub fn parse(host: &str) -> Result<Self, Error> {
+        if let Some(path) = host.strip_prefix("unix://") {
+            Ok(Endpoint::Unix(PathBuf::from(path)))
+        } else if let Some(address) = host.strip_prefix("tcp://") {
+            Ok(Endpoint::Tcp(address.trim_end_matches('/').to_string()))
+        } else {
+            Err(Error::Unsupported(format!("DOCKER_HOST `{}`", host)))
+        }
+    }
+
+    /// Whether there is anything to connect to, without connecting.
+    pub fn is_reachable(&self) -> bool {
+        match self {
+            Endpoint::Unix(path) => path.exists(),
+            Endpoint::Tcp(_) => true,
+        }
+    }
+
+    fn connect(&self) -> std::io::Result<Box<dyn Stream>> {
+        match self {
+            Endpoint::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+            Endpoint::Tcp(address) => Ok(Box::new(std::net::TcpStream::connect(address)?)),
+        }
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(&self) -> Result<serde_json::Value, Error> {
+        serde_json::from_slice(&self.body).map_err(|err| Error::Protocol(err.to_string()))
+    }
+
+    /// The `message` the engine puts into every error body.
+    pub fn message(&self) -> String {
+        self.json().ok()
+            .and_then(|body| body["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| String::from_utf8_lossy(&self.body).trim().to_string())
+    }
+}
+
+/// Sends a single request on a fresh connection and reads the whole response.
+pub fn request(endpoint: &Endpoint, method: &str, path: &str, body: Option<&serde_json::Value>) -> Result<Response, Error> {
+    let body = body.map(serde_json::Value::to_string).unwrap_or_default();
+    let mut stream = endpoint.connect()?;
+
+    write!(stream, "{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n", method, path)?;
+    if !body.is_empty() {
+        write!(stream, "Content-Type: application/json\r\n")?;
+    }
+    write!(stream, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    stream.flush()?;
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw)?;
+    parse_response(&raw)
+}
+
+/// Sends a request asking the engine to hijack the connection, returning it once the protocol is switched.
+pub fn upgrade(endpoint: &Endpoint, path: &str, body: &serde_json::Value) -> Result<Box<dyn Stream>, Error> {
+    let body = body.to_string();
+    let mut stream = endpoint.connect()?;
+
+    write!(
+        stream, "POST {} HTTP/1.1\r\nHost: docker\r\nConnection: Upgrade\r\nUpgrade: tcp\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path, body.len(), body
+    )?;
+    stream.flush()?;
+
+    // The head is read byte by byte so nothing of the hijacked stream after it is consumed.
+    let mut raw = Vec::new();
+    let mut byte = [0];
+    while !raw.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte)? == 0 {
+            return Err(Error::Protocol("connection closed before the upgrade".to_string()))
+        }
+        raw.push(byte[0]);
+    }
+
+    match parse_response(&raw)?.status {
+        101 | 200 => Ok(stream),
+        status => {
+            stream.read_to_end(&mut raw)?;
+            Err(Error::Engine { status, message: parse_response(&raw)?.message() })
+        },
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, Error> {
+    let split = raw.windows(4).position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| Error::Protocol("missing end of headers".to_string()))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let body = &raw[split + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines.next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| Error::Protocol(format!("invalid status line in `{}`", head)))?;
+    let chunked = lines
+        .filter_map(|line| line.split_once(':'))
+        .any(|(name, value)| name.eq_ignore_ascii_case("transfer-encoding") && value.trim().eq_ignore_ascii_case("chunked"));
+
+    Ok(Response {
+        status,
+        body: if chunked { decode_chunked(body)? } else { body.to_vec() }
+    })
+}
+
+fn decode_chunked(mut raw: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = raw.windows(2).position(|window| window == b"\r\n")
+            .ok_or_else(|| Error::Protocol("truncated chunk size".to_string()))?;
+        let size = String::from_utf8_lossy(&raw[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16)
+            .map_err(|_| Error::Protocol(format!("invalid chunk size `{}`", size)))?;
+        raw = &raw[line_end + 2..];
+        if size == 0 {
+            return Ok(body)
+        }
+        if raw.len() < size {
+            return Err(Error::Protocol("truncated chunk".to_string()))
+        }
+        body.extend_from_slice(&raw[..size]);
+        raw = raw.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Percent-encodes a query string value.
+pub fn encode(value: &str) -> String {
+    value.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        byte => format!("%{:02X}", byte),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, encode, Endpoint};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\n{}").ok().unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, b"{}");
+    }
+
+    #[test]
+    fn test_parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n6\r\npedia \r\n0\r\n\r\n";
+        let response = parse_response(raw).ok().unwrap();
+        assert_eq!(response.body, b"Wikipedia ");
+    }
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(Endpoint::parse("unix:///run/user/1000/docker.sock").ok(), Some(Endpoint::Unix(PathBuf::from("/run/user/1000/docker.sock"))));
+        assert_eq!(Endpoint::parse("tcp://127.0.0.1:2375").ok(), Some(Endpoint::Tcp("127.0.0.1:2375".to_string())));
+        assert!(Endpoint::parse("ssh://host").is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("registry:5000/ubuntu"), "registry%3A5000%2Fubuntu");
+    }
+}
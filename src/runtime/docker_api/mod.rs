@@ -0,0 +1,395 @@
+mod http;
+#[cfg(test)]
+mod tests;
+
+use serde_json::{json, Map, Value};
+use crate::parser::{Container, Network, Manifest, Argument};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use super::{ContainerRuntime, Error, State, terminal, environment, volume_source, argv};
+pub use http::Endpoint;
+
+/// Talks to the Docker Engine HTTP API directly, without the `docker` binary.
+///
+/// It only runs containers from images, those built from a Dockerfile need the `docker` or `podman` runtime.
+pub struct DockerApi {
+    endpoint: Endpoint,
+    directory: PathBuf,
+}
+
+impl DockerApi {
+    pub fn new(endpoint: Endpoint) -> Self {
+        DockerApi {
+            endpoint,
+            directory: PathBuf::new()
+        }
+    }
+
+    /// Resolves relative volume sources and env files against `directory` instead of the current one.
+    pub fn in_directory<P: AsRef<Path>>(mut self, directory: P) -> Self {
+        self.directory = directory.as_ref().to_path_buf();
+        self
+    }
+
+    /// Connects to `DOCKER_HOST`, or to `/var/run/docker.sock` when it is not set.
+    pub fn from_env() -> Result<Self, Error> {
+        Endpoint::from_env().map(Self::new)
+    }
+
+    pub fn pull(&self, image: &str) -> Result<(), Error> {
+        let (image, tag) = split_image(image);
+        let path = format!("/images/create?fromImage={}&tag={}", http::encode(image), http::encode(tag));
+        let response = http::request(&self.endpoint, "POST", &path, None)?;
+        if response.status != 200 {
+            return Err(Error::Engine { status: response.status, message: response.message() })
+        }
+
+        // Pull failures arrive as a progress message in an otherwise successful response.
+        let error = response.body.split(|chr| *chr == b'\n')
+            .filter_map(|line| serde_json::from_slice::<Value>(line).ok())
+            .find_map(|progress| progress["error"].as_str().map(str::to_string));
+        match error {
+            Some(message) => Err(Error::Engine { status: response.status, message }),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds the body of `POST /containers/create` from the container's manifest and arguments.
+    ///
+    /// The container is created on its first network only, [`ContainerRuntime::create`] connects it to the others.
+    pub fn container_config(&self, container: &Container) -> Result<Value, Error> {
+        let image = match container.manifest() {
+            Manifest::Image(image) => String::from_utf8_lossy(image),
+            Manifest::File(file) => return Err(Error::Unsupported(format!(
+                "building `{}` through the engine API, run containers built from a Dockerfile with `--runtime docker` or `podman`",
+                String::from_utf8_lossy(file)
+            ))),
+        };
+
+        let mut exposed = Map::new();
+        let mut bindings = Map::new();
+        let mut binds = Vec::new();
+        let mut volumes_from = Vec::new();
+        let mut process = Map::new();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        for (index, argument) in container.arguments().iter().enumerate() {
+            match argument {
+                // The engine takes ranges port by port.
+                Argument::PublishPort { host, outer, inner, protocol } => for (outer, inner) in outer.ports().zip(inner.ports()) {
+                    let port = format!("{}/{}", inner, protocol);
+                    exposed.insert(port.clone(), json!({}));
+                    let binding = match host {
+                        Some(host) => json!({ "HostIp": host.to_string(), "HostPort": outer.to_string() }),
+                        None => json!({ "HostPort": outer.to_string() }),
+                    };
+                    if let Value::Array(hosts) = bindings.entry(port).or_insert_with(|| json!([])) {
+                        hosts.push(binding);
+                    }
+                },
+                Argument::ExposePort { port, protocol } => for port in port.ports() {
+                    exposed.insert(format!("{}/{}", port, protocol), json!({}));
+                },
+                Argument::Volume { source, mount, options } => {
+                    let source = volume_source(source, &self.directory.join(container.directory(index)), home.as_deref());
+                    binds.push(match options.is_empty() {
+                        true => format!("{}:{}", source, String::from_utf8_lossy(mount)),
+                        false => format!("{}:{}:{}", source, String::from_utf8_lossy(mount), String::from_utf8_lossy(options)),
+                    })
+                },
+                Argument::VolumeFrom { name } => volumes_from.push(String::from_utf8_lossy(name).into_owned()),
+                Argument::Command { args } => {
+                    process.insert("Cmd".to_string(), json!(argv(args)));
+                },
+                Argument::Entrypoint { args } => {
+                    process.insert("Entrypoint".to_string(), json!(argv(args)));
+                },
+                Argument::Workdir { path } => {
+                    process.insert("WorkingDir".to_string(), json!(String::from_utf8_lossy(path)));
+                },
+                Argument::User { user } => {
+                    process.insert("User".to_string(), json!(String::from_utf8_lossy(user)));
+                },
+                Argument::Env { .. } | Argument::EnvFile { .. } | Argument::Profiles { .. } |
+                Argument::Network { .. } | Argument::Alias { .. } => {},
+            }
+        }
+        let env = environment(container, &self.directory, home.as_deref())?.into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+
+        let mut config = json!({
+            "Image": image,
+            "Env": env,
+            "ExposedPorts": exposed,
+            "HostConfig": {
+                "PortBindings": bindings,
+                "Binds": binds,
+                "VolumesFrom": volumes_from,
+            }
+        });
+        // Left out unless set, the engine then keeps what the image says.
+        if let Value::Object(config) = &mut config {
+            config.extend(process);
+        }
+        if let Some(network) = container.networks().next().map(String::from_utf8_lossy) {
+            config["HostConfig"]["NetworkMode"] = json!(network);
+            config["NetworkingConfig"] = json!({ "EndpointsConfig": { network: Self::endpoint_config(container) } });
+        }
+        Ok(config)
+    }
+
+    /// The settings of the container on each of its networks, the names it is reached by besides its own.
+    fn endpoint_config(container: &Container) -> Value {
+        json!({ "Aliases": container.aliases().map(String::from_utf8_lossy).collect::<Vec<_>>() })
+    }
+
+    /// Connects the current terminal to the exec session `id` until its process exits or crab is told to stop.
+    fn session(&self, id: &str) -> Result<(), Error> {
+        let start = json!({ "Detach": false, "Tty": true });
+        let mut stream = http::upgrade(&self.endpoint, &format!("/exec/{}/start", id), &start)?;
+        let mut output = stream.try_clone()?;
+        let _raw = terminal::RawMode::enable(libc::STDIN_FILENO)?;
+        let signals = terminal::Signals::catch();
+        self.resize(id);
+
+        let printer = std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            let mut stdout = std::io::stdout();
+            while let Ok(read @ 1..) = output.read(&mut buffer) {
+                if stdout.write_all(&buffer[..read]).and_then(|_| stdout.flush()).is_err() {
+                    break
+                }
+            }
+        });
+
+        let mut input = true;
+        let mut buffer = [0; 4096];
+        while !printer.is_finished() {
+            for signal in signals.take() {
+                match signal {
+                    libc::SIGWINCH => self.resize(id),
+                    // The terminal inside the container turns these back into the signals they stand for.
+                    libc::SIGINT => stream.write_all(b"\x03")?,
+                    libc::SIGQUIT => stream.write_all(b"\x1c")?,
+                    _ => {
+                        stream.shutdown()?;
+                        input = false;
+                    },
+                }
+            }
+
+            if !input {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                continue
+            }
+            let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut poll, 1, 100) } <= 0 {
+                continue
+            }
+            match unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) } {
+                read if read > 0 => stream.write_all(&buffer[..read as usize])?,
+                _ => input = false,
+            }
+        }
+
+        let _ = printer.join();
+        Ok(())
+    }
+
+    /// Tells the engine the size of crab's terminal, so full-screen programs in the container fit it.
+    fn resize(&self, id: &str) {
+        if let Some((rows, columns)) = terminal::size(libc::STDOUT_FILENO) {
+            let path = format!("/exec/{}/resize?h={}&w={}", id, rows, columns);
+            let _ = http::request(&self.endpoint, "POST", &path, None);
+        }
+    }
+
+    fn expect_no_content(&self, method: &str, name: &str, path: &str) -> Result<(), Error> {
+        let response = http::request(&self.endpoint, method, path, None)?;
+        match response.status {
+            200..=299 | 304 => Ok(()),
+            404 => Err(Error::NoSuchContainer(name.to_string())),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+}
+
+impl ContainerRuntime for DockerApi {
+    fn name(&self) -> &str {
+        "docker-api"
+    }
+
+    /// Pulls the image first when the engine does not have it yet, like `docker run` does.
+    fn create(&self, container: &Container) -> Result<String, Error> {
+        let name = String::from_utf8_lossy(container.name());
+        let config = self.container_config(container)?;
+        let path = format!("/containers/create?name={}", http::encode(&name));
+
+        let mut response = http::request(&self.endpoint, "POST", &path, Some(&config))?;
+        if response.status == 404 {
+            self.pull(config["Image"].as_str().unwrap_or_default())?;
+            response = http::request(&self.endpoint, "POST", &path, Some(&config))?;
+        }
+
+        let id = match response.status {
+            200 | 201 => response.json()?["Id"].as_str()
+                .map(str::to_string)
+                .ok_or_else(|| Error::Protocol("missing container id".to_string()))?,
+            status => return Err(Error::Engine { status, message: response.message() }),
+        };
+
+        for network in container.networks().skip(1).map(String::from_utf8_lossy) {
+            let connect = json!({ "Container": id, "EndpointConfig": Self::endpoint_config(container) });
+            let response = http::request(&self.endpoint, "POST", &format!("/networks/{}/connect", http::encode(&network)), Some(&connect))?;
+            match response.status {
+                200 => {},
+                404 => return Err(Error::NoSuchNetwork(network.into_owned())),
+                status => return Err(Error::Engine { status, message: response.message() }),
+            }
+        }
+        Ok(id)
+    }
+
+    fn start(&self, name: &str) -> Result<(), Error> {
+        self.expect_no_content("POST", name, &format!("/containers/{}/start", http::encode(name)))
+    }
+
+    fn stop(&self, name: &str) -> Result<(), Error> {
+        self.expect_no_content("POST", name, &format!("/containers/{}/stop", http::encode(name)))
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Error> {
+        self.expect_no_content("DELETE", name, &format!("/containers/{}", http::encode(name)))
+    }
+
+    fn inspect(&self, name: &str) -> Result<Option<State>, Error> {
+        let response = http::request(&self.endpoint, "GET", &format!("/containers/{}/json", http::encode(name)), None)?;
+        match response.status {
+            200 => {
+                let body = response.json()?;
+                Ok(Some(State {
+                    id: body["Id"].as_str().unwrap_or_default().to_string(),
+                    status: body["State"]["Status"].as_str().unwrap_or_default().to_string(),
+                    running: body["State"]["Running"].as_bool().unwrap_or_default(),
+                }))
+            },
+            404 => Ok(None),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+
+    fn exec(&self, name: &str, command: &[String], tty: bool) -> Result<i32, Error> {
+        let config = json!({ "AttachStdin": tty, "AttachStdout": true, "AttachStderr": true, "Tty": tty, "Cmd": command });
+        let response = http::request(&self.endpoint, "POST", &format!("/containers/{}/exec", http::encode(name)), Some(&config))?;
+        let id = match response.status {
+            201 => response.json()?["Id"].as_str().unwrap_or_default().to_string(),
+            404 => return Err(Error::NoSuchContainer(name.to_string())),
+            status => return Err(Error::Engine { status, message: response.message() }),
+        };
+
+        if tty {
+            self.session(&id)?;
+        } else {
+            let start = json!({ "Detach": false, "Tty": false });
+            let response = http::request(&self.endpoint, "POST", &format!("/exec/{}/start", id), Some(&start))?;
+            if response.status != 200 {
+                return Err(Error::Engine { status: response.status, message: response.message() })
+            }
+            for (stream, payload) in demultiplex(&response.body) {
+                match stream {
+                    2 => std::io::stderr().write_all(payload)?,
+                    _ => std::io::stdout().write_all(payload)?,
+                }
+            }
+        }
+
+        let response = http::request(&self.endpoint, "GET", &format!("/exec/{}/json", id), None)?;
+        response.json()?["ExitCode"].as_i64()
+            .map(|code| code as i32)
+            .ok_or_else(|| Error::Protocol("missing exit code".to_string()))
+    }
+
+    fn attach(&self, _name: &str) -> Result<i32, Error> {
+        Err(Error::Unsupported("attaching through the engine API".to_string()))
+    }
+
+    fn logs(&self, name: &str) -> Result<String, Error> {
+        let path = format!("/containers/{}/logs?stdout=true&stderr=true", http::encode(name));
+        let response = http::request(&self.endpoint, "GET", &path, None)?;
+        match response.status {
+            200 => Ok(demultiplex(&response.body).into_iter()
+                .map(|(_, payload)| String::from_utf8_lossy(payload).into_owned())
+                .collect()),
+            404 => Err(Error::NoSuchContainer(name.to_string())),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+
+    fn create_network(&self, network: &Network) -> Result<(), Error> {
+        let mut config = json!({
+            "Name": String::from_utf8_lossy(network.name()),
+            "Internal": network.internal(),
+            "CheckDuplicate": true,
+        });
+        if let Some(driver) = network.driver() {
+            config["Driver"] = json!(String::from_utf8_lossy(driver));
+        }
+        if let Some(subnet) = network.subnet() {
+            config["IPAM"] = json!({ "Config": [{ "Subnet": subnet.to_string() }] });
+        }
+
+        let response = http::request(&self.endpoint, "POST", "/networks/create", Some(&config))?;
+        match response.status {
+            200 | 201 => Ok(()),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+
+    fn remove_network(&self, name: &str) -> Result<(), Error> {
+        let response = http::request(&self.endpoint, "DELETE", &format!("/networks/{}", http::encode(name)), None)?;
+        match response.status {
+            200..=299 => Ok(()),
+            404 => Err(Error::NoSuchNetwork(name.to_string())),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+
+    fn has_network(&self, name: &str) -> Result<bool, Error> {
+        let response = http::request(&self.endpoint, "GET", &format!("/networks/{}", http::encode(name)), None)?;
+        match response.status {
+            200 => Ok(true),
+            404 => Ok(false),
+            status => Err(Error::Engine { status, message: response.message() }),
+        }
+    }
+}
+
+/// Splits the engine's multiplexed output into `(stream, payload)` frames.
+///
+/// Containers running with a terminal send their output as is, that comes back as a single stdout frame.
+fn demultiplex(mut raw: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut frames = Vec::new();
+    while raw.len() >= 8 && raw[0] <= 2 && raw[1..4] == [0, 0, 0] {
+        let size = u32::from_be_bytes([raw[4], raw[5], raw[6], raw[7]]) as usize;
+        let end = (8 + size).min(raw.len());
+        frames.push((raw[0], &raw[8..end]));
+        raw = &raw[end..];
+    }
+    if !raw.is_empty() {
+        frames.push((1, raw));
+    }
+    frames
+}
+
+/// Splits `registry:5000/ubuntu:focal` into `registry:5000/ubuntu` and `focal`. A digest, `ubuntu@sha256:<hex>`,
+/// takes the place of the tag, which the engine accepts as well.
+fn split_image(image: &str) -> (&str, &str) {
+    if let Some((name, digest)) = image.split_once('@') {
+        let name = name.rfind(':').filter(|index| !name[*index..].contains('/')).map_or(name, |index| &name[..index]);
+        return (name, digest)
+    }
+    match image.rfind(':') {
+        Some(index) if !image[index..].contains('/') => (&image[..index], &image[index + 1..]),
+        _ => (image, "latest"),
+    }
+}
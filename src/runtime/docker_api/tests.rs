@@ -0,0 +1,353 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+use serde_json::{json, Value};
+use crate::parser::Parser;
+use crate::runtime::{ContainerRuntime, Error};
+use super::{DockerApi, Endpoint, http, split_image, demultiplex};
+
+struct Request {
+    method: String,
+    path: String,
+    body: Option<Value>,
+}
+
+/// Answers one connection per scripted response on a Unix socket, the way the Docker daemon would.
+struct FakeDaemon {
+    socket: PathBuf,
+    handle: JoinHandle<Vec<Request>>,
+}
+
+impl FakeDaemon {
+    fn serve(name: &str, responses: Vec<(u16, &'static str)>) -> Self {
+        let socket = std::env::temp_dir().join(format!("crab-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            responses.into_iter().map(|(status, body)| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = Self::read_request(&mut stream);
+                write!(
+                    stream, "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status, body.len(), body
+                ).unwrap();
+                request
+            }).collect()
+        });
+
+        FakeDaemon {
+            socket,
+            handle
+        }
+    }
+
+    fn read_request(stream: &mut impl Read) -> Request {
+        let mut raw = Vec::new();
+        let mut buffer = [0; 1024];
+        while !raw.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            raw.extend_from_slice(&buffer[..read]);
+        }
+
+        let split = raw.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&raw[..split]).into_owned();
+        let length: usize = head.lines()
+            .filter_map(|line| line.strip_prefix("Content-Length: "))
+            .map(|length| length.parse().unwrap())
+            .next()
+            .unwrap_or_default();
+        let mut body = raw[split + 4..].to_vec();
+        while body.len() < length {
+            let read = stream.read(&mut buffer).unwrap();
+            body.extend_from_slice(&buffer[..read]);
+        }
+
+        let mut request_line = head.lines().next().unwrap().split(' ');
+        Request {
+            method: request_line.next().unwrap().to_string(),
+            path: request_line.next().unwrap().to_string(),
+            body: serde_json::from_slice(&body).ok(),
+        }
+    }
+
+    fn api(&self) -> DockerApi {
+        DockerApi::new(Endpoint::Unix(self.socket.clone()))
+    }
+
+    fn requests(self) -> Vec<Request> {
+        let requests = self.handle.join().unwrap();
+        let _ = std::fs::remove_file(&self.socket);
+        requests
+    }
+}
+
+const CRABFILE: &str = indoc::indoc! {"
+    @ubuntu:
+        from: ubuntu:latest
+        port: 80:8080
+        port: 443:8443
+        port: 127.0.0.1:6000-6001:7000-7001/udp
+        volume: /usr/lib/:/usr/share/lib
+        volume: ./data:/data:ro
+        volume: cache:/cache
+        volume-from: cache_container
+        env: RUST_LOG=debug
+        expose: 443
+    "};
+
+#[test]
+fn test_container_config() {
+    let parser = Parser::parse(CRABFILE.as_bytes()).unwrap();
+
+    let api = DockerApi::new(Endpoint::Unix(PathBuf::from("/var/run/docker.sock"))).in_directory("/srv/app");
+    let config = api.container_config(parser.container("ubuntu").unwrap()).unwrap();
+
+    assert_eq!(config, json!({
+        "Image": "ubuntu:latest",
+        "Env": ["RUST_LOG=debug"],
+        "ExposedPorts": { "8080/tcp": {}, "8443/tcp": {}, "7000/udp": {}, "7001/udp": {}, "443/tcp": {} },
+        "HostConfig": {
+            "PortBindings": {
+                "8080/tcp": [{ "HostPort": "80" }],
+                "8443/tcp": [{ "HostPort": "443" }],
+                "7000/udp": [{ "HostIp": "127.0.0.1", "HostPort": "6000" }],
+                "7001/udp": [{ "HostIp": "127.0.0.1", "HostPort": "6001" }]
+            },
+            "Binds": ["/usr/lib/:/usr/share/lib", "/srv/app/data:/data:ro", "cache:/cache"],
+            "VolumesFrom": ["cache_container"]
+        }
+    }));
+}
+
+#[test]
+fn test_container_config_with_process() {
+    let input = "@toolchain:\n    from: rust:1\n    command: sleep infinity\n    entrypoint: ''\n    workdir: /workspace\n    user: dev\n";
+    let parser = Parser::parse(input.as_bytes()).unwrap();
+
+    let api = DockerApi::new(Endpoint::Unix(PathBuf::from("/var/run/docker.sock")));
+    let config = api.container_config(parser.container("toolchain").unwrap()).unwrap();
+
+    assert_eq!(config["Cmd"], json!(["sleep", "infinity"]));
+    assert_eq!(config["Entrypoint"], json!([""]));
+    assert_eq!(config["WorkingDir"], "/workspace");
+    assert_eq!(config["User"], "dev");
+}
+
+#[test]
+fn test_container_config_from_dockerfile() {
+    let parser = Parser::parse(b"@ubuntu:\n    from: Dockerfile.ubuntu\n").unwrap();
+
+    let result = DockerApi::new(Endpoint::Unix(PathBuf::from("/var/run/docker.sock"))).container_config(parser.container("ubuntu").unwrap());
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "not supported by this runtime: building `Dockerfile.ubuntu` through the engine API, \
+         run containers built from a Dockerfile with `--runtime docker` or `podman`"
+    );
+}
+
+#[test]
+fn test_create() {
+    let parser = Parser::parse(CRABFILE.as_bytes()).unwrap();
+    let daemon = FakeDaemon::serve("create", vec![(201, r#"{"Id": "4fa6e0f0c678", "Warnings": []}"#)]);
+
+    let id = daemon.api().create(parser.container("ubuntu").unwrap()).unwrap();
+
+    assert_eq!(id, "4fa6e0f0c678");
+    let requests = daemon.requests();
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/containers/create?name=ubuntu");
+    assert_eq!(requests[0].body.as_ref().unwrap()["Image"], "ubuntu:latest");
+}
+
+#[test]
+fn test_create_pulls_missing_image() {
+    let parser = Parser::parse(CRABFILE.as_bytes()).unwrap();
+    let daemon = FakeDaemon::serve("pull", vec![
+        (404, r#"{"message": "No such image: ubuntu:latest"}"#),
+        (200, "{\"status\": \"Pulling from library/ubuntu\"}\n{\"status\": \"Downloaded newer image\"}\n"),
+        (201, r#"{"Id": "4fa6e0f0c678", "Warnings": []}"#),
+    ]);
+
+    let id = daemon.api().create(parser.container("ubuntu").unwrap()).unwrap();
+
+    assert_eq!(id, "4fa6e0f0c678");
+    let paths: Vec<_> = daemon.requests().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, vec![
+        "/containers/create?name=ubuntu",
+        "/images/create?fromImage=ubuntu&tag=latest",
+        "/containers/create?name=ubuntu",
+    ]);
+}
+
+#[test]
+fn test_create_on_networks() {
+    let input = indoc::indoc! {"
+        @network frontend:
+        @network backend:
+
+        @api:
+            from: alpine:latest
+            network: frontend
+            network: backend
+            alias: api-v1
+    "};
+    let parser = Parser::parse(input.as_bytes()).unwrap();
+    let daemon = FakeDaemon::serve("create-networks", vec![(201, r#"{"Id": "4fa6e0f0c678", "Warnings": []}"#), (200, "")]);
+
+    daemon.api().create(parser.container("api").unwrap()).unwrap();
+
+    let requests = daemon.requests();
+    let config = requests[0].body.as_ref().unwrap();
+    assert_eq!(config["HostConfig"]["NetworkMode"], "frontend");
+    assert_eq!(config["NetworkingConfig"], json!({ "EndpointsConfig": { "frontend": { "Aliases": ["api-v1"] } } }));
+    assert_eq!(requests[1].path, "/networks/backend/connect");
+    assert_eq!(requests[1].body, Some(json!({ "Container": "4fa6e0f0c678", "EndpointConfig": { "Aliases": ["api-v1"] } })));
+}
+
+#[test]
+fn test_networks() {
+    let parser = Parser::parse(b"@network backend:\n    driver: bridge\n    subnet: 172.20.0.0/16\n    internal: true\n").unwrap();
+    let daemon = FakeDaemon::serve("networks", vec![
+        (201, r#"{"Id": "22be93d5babb", "Warning": ""}"#),
+        (200, r#"{"Name": "backend"}"#),
+        (404, r#"{"message": "network frontend not found"}"#),
+        (204, ""),
+        (404, r#"{"message": "network backend not found"}"#),
+    ]);
+    let api = daemon.api();
+
+    api.create_network(parser.network("backend").unwrap()).unwrap();
+    assert!(api.has_network("backend").unwrap());
+    assert!(!api.has_network("frontend").unwrap());
+    api.remove_network("backend").unwrap();
+    assert!(matches!(api.remove_network("backend"), Err(Error::NoSuchNetwork(name)) if name == "backend"));
+
+    let requests = daemon.requests();
+    assert_eq!(requests[0].path, "/networks/create");
+    assert_eq!(requests[0].body, Some(json!({
+        "Name": "backend",
+        "Driver": "bridge",
+        "Internal": true,
+        "CheckDuplicate": true,
+        "IPAM": { "Config": [{ "Subnet": "172.20.0.0/16" }] }
+    })));
+    let requests: Vec<_> = requests.into_iter().skip(1).map(|request| (request.method, request.path)).collect();
+    assert_eq!(requests, vec![
+        ("GET".to_string(), "/networks/backend".to_string()),
+        ("GET".to_string(), "/networks/frontend".to_string()),
+        ("DELETE".to_string(), "/networks/backend".to_string()),
+        ("DELETE".to_string(), "/networks/backend".to_string()),
+    ]);
+}
+
+#[test]
+fn test_pull_error_in_progress() {
+    let daemon = FakeDaemon::serve("pull-error", vec![
+        (200, "{\"error\": \"manifest for ubuntu:nope not found\"}\n"),
+    ]);
+
+    let result = daemon.api().pull("ubuntu:nope");
+
+    assert!(matches!(result, Err(Error::Engine { message, .. }) if message == "manifest for ubuntu:nope not found"));
+    daemon.requests();
+}
+
+#[test]
+fn test_start_stop_remove() {
+    let daemon = FakeDaemon::serve("lifecycle", vec![(204, ""), (304, ""), (204, ""), (404, r#"{"message": "No such container: ubuntu"}"#)]);
+    let api = daemon.api();
+
+    assert!(api.start("ubuntu").is_ok());
+    assert!(api.start("ubuntu").is_ok());
+    assert!(api.stop("ubuntu").is_ok());
+    assert!(matches!(api.remove("ubuntu"), Err(Error::NoSuchContainer(name)) if name == "ubuntu"));
+
+    let requests: Vec<_> = daemon.requests().into_iter().map(|request| (request.method, request.path)).collect();
+    assert_eq!(requests, vec![
+        ("POST".to_string(), "/containers/ubuntu/start".to_string()),
+        ("POST".to_string(), "/containers/ubuntu/start".to_string()),
+        ("POST".to_string(), "/containers/ubuntu/stop".to_string()),
+        ("DELETE".to_string(), "/containers/ubuntu".to_string()),
+    ]);
+}
+
+#[test]
+fn test_inspect() {
+    let daemon = FakeDaemon::serve("inspect", vec![
+        (200, r#"{"Id": "4fa6e0f0c678", "State": {"Status": "running", "Running": true}}"#),
+        (404, r#"{"message": "No such container: missing"}"#),
+        (500, r#"{"message": "server error"}"#),
+    ]);
+    let api = daemon.api();
+
+    let state = api.inspect("ubuntu").unwrap().unwrap();
+    assert_eq!(state.id, "4fa6e0f0c678");
+    assert_eq!(state.status, "running");
+    assert!(state.running);
+    assert!(api.inspect("missing").unwrap().is_none());
+    assert!(matches!(api.inspect("ubuntu"), Err(Error::Engine { status: 500, message }) if message == "server error"));
+    daemon.requests();
+}
+
+#[test]
+fn test_exec() {
+    let daemon = FakeDaemon::serve("exec", vec![
+        (201, r#"{"Id": "e90e34656806"}"#),
+        (200, ""),
+        (200, r#"{"ExitCode": 3, "Running": false}"#),
+    ]);
+
+    let code = daemon.api().exec("ubuntu", &["false".to_string()], false).unwrap();
+
+    assert_eq!(code, 3);
+    let requests = daemon.requests();
+    assert_eq!(requests[0].path, "/containers/ubuntu/exec");
+    assert_eq!(requests[0].body.as_ref().unwrap()["Cmd"], json!(["false"]));
+    assert_eq!(requests[1].path, "/exec/e90e34656806/start");
+    assert_eq!(requests[2].path, "/exec/e90e34656806/json");
+}
+
+#[test]
+fn test_upgrade() {
+    let daemon = FakeDaemon::serve("upgrade", vec![
+        (101, "root@4fa6e0f0c678:/# "),
+        (409, r#"{"message": "container 4fa6e0f0c678 is not running"}"#),
+    ]);
+    let endpoint = Endpoint::Unix(daemon.socket.clone());
+    let start = json!({ "Detach": false, "Tty": true });
+
+    let mut stream = http::upgrade(&endpoint, "/exec/e90e34656806/start", &start).ok().unwrap();
+    let mut prompt = String::new();
+    stream.read_to_string(&mut prompt).unwrap();
+    assert_eq!(prompt, "root@4fa6e0f0c678:/# ");
+
+    let error = http::upgrade(&endpoint, "/exec/e90e34656806/start", &start).err().unwrap();
+    assert!(matches!(error, Error::Engine { status: 409, message } if message.contains("is not running")));
+    assert_eq!(daemon.requests()[0].body, Some(start));
+}
+
+#[test]
+fn test_logs() {
+    let daemon = FakeDaemon::serve("logs", vec![(200, "\u{1}\0\0\0\0\0\0\u{6}hello\n\u{2}\0\0\0\0\0\0\u{6}oops!\n")]);
+
+    assert_eq!(daemon.api().logs("ubuntu").unwrap(), "hello\noops!\n");
+    assert_eq!(daemon.requests()[0].path, "/containers/ubuntu/logs?stdout=true&stderr=true");
+}
+
+#[test]
+fn test_demultiplex_tty_output() {
+    assert_eq!(demultiplex(b"plain output"), vec![(1, &b"plain output"[..])]);
+}
+
+#[test]
+fn test_split_image() {
+    assert_eq!(split_image("ubuntu:focal"), ("ubuntu", "focal"));
+    assert_eq!(split_image("ubuntu"), ("ubuntu", "latest"));
+    assert_eq!(split_image("registry:5000/ubuntu"), ("registry:5000/ubuntu", "latest"));
+    assert_eq!(split_image("registry:5000/ubuntu:focal"), ("registry:5000/ubuntu", "focal"));
+    assert_eq!(split_image("ubuntu@sha256:45b23dee08af"), ("ubuntu", "sha256:45b23dee08af"));
+    assert_eq!(split_image("registry:5000/ubuntu:focal@sha256:45b23dee08af"), ("registry:5000/ubuntu", "sha256:45b23dee08af"));
+    assert_eq!(split_image("registry:5000/ubuntu@sha256:45b23dee08af"), ("registry:5000/ubuntu", "sha256:45b23dee08af"));
+}
@@ -0,0 +1,229 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use crate::parser::{Container, Network};
+use super::{ContainerRuntime, Error, State, environment};
+
+/// A step the dry-run runtime was asked to take.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Create(String),
+    Start(String),
+    Stop(String),
+    Remove(String),
+    Exec(String, Vec<String>),
+    Attach(String),
+    CreateNetwork(String),
+    RemoveNetwork(String),
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Create(name) => write!(f, "create {}", name),
+            Operation::Start(name) => write!(f, "start {}", name),
+            Operation::Stop(name) => write!(f, "stop {}", name),
+            Operation::Remove(name) => write!(f, "remove {}", name),
+            Operation::Exec(name, command) => write!(f, "exec {} {}", name, command.join(" ")),
+            Operation::Attach(name) => write!(f, "attach {}", name),
+            Operation::CreateNetwork(name) => write!(f, "create network {}", name),
+            Operation::RemoveNetwork(name) => write!(f, "remove network {}", name),
+        }
+    }
+}
+
+/// Keeps containers in memory and records what would have been done to a real engine.
+#[derive(Default)]
+pub struct DryRun {
+    containers: RefCell<Vec<State>>,
+    networks: RefCell<Vec<String>>,
+    operations: RefCell<Vec<Operation>>,
+    directory: PathBuf,
+}
+
+impl DryRun {
+    /// Reads env files relative to `directory` instead of the current one.
+    pub fn in_directory<P: AsRef<Path>>(mut self, directory: P) -> Self {
+        self.directory = directory.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn operations(&self) -> Vec<Operation> {
+        self.operations.borrow().clone()
+    }
+
+    fn record(&self, operation: Operation) {
+        self.operations.borrow_mut().push(operation);
+    }
+
+    fn update(&self, name: &str, status: &str) -> Result<(), Error> {
+        let mut containers = self.containers.borrow_mut();
+        let state = containers.iter_mut()
+            .find(|state| state.id == name)
+            .ok_or_else(|| Error::NoSuchContainer(name.to_string()))?;
+        state.status = status.to_string();
+        state.running = status == "running";
+        Ok(())
+    }
+}
+
+impl ContainerRuntime for DryRun {
+    fn name(&self) -> &str {
+        "dry-run"
+    }
+
+    fn create(&self, container: &Container) -> Result<String, Error> {
+        let name = String::from_utf8_lossy(container.name()).into_owned();
+        environment(container, &self.directory, std::env::var_os("HOME").map(PathBuf::from).as_deref())?;
+        if self.inspect(&name)?.is_some() {
+            return Err(Error::Engine { status: 409, message: format!("container name `{}` is already in use", name) })
+        }
+        if let Some(network) = container.networks().map(String::from_utf8_lossy).find(|network| !self.networks.borrow().contains(&network.to_string())) {
+            return Err(Error::NoSuchNetwork(network.into_owned()))
+        }
+
+        self.containers.borrow_mut().push(State {
+            id: name.clone(),
+            status: "created".to_string(),
+            running: false
+        });
+        self.record(Operation::Create(name.clone()));
+        Ok(name)
+    }
+
+    fn start(&self, name: &str) -> Result<(), Error> {
+        self.update(name, "running")?;
+        self.record(Operation::Start(name.to_string()));
+        Ok(())
+    }
+
+    fn stop(&self, name: &str) -> Result<(), Error> {
+        self.update(name, "exited")?;
+        self.record(Operation::Stop(name.to_string()));
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Error> {
+        let mut containers = self.containers.borrow_mut();
+        let index = containers.iter()
+            .position(|state| state.id == name)
+            .ok_or_else(|| Error::NoSuchContainer(name.to_string()))?;
+        containers.remove(index);
+        self.record(Operation::Remove(name.to_string()));
+        Ok(())
+    }
+
+    fn inspect(&self, name: &str) -> Result<Option<State>, Error> {
+        Ok(self.containers.borrow().iter().find(|state| state.id == name).cloned())
+    }
+
+    fn exec(&self, name: &str, command: &[String], _tty: bool) -> Result<i32, Error> {
+        match self.inspect(name)? {
+            Some(state) if state.running => {
+                self.record(Operation::Exec(name.to_string(), command.to_vec()));
+                Ok(0)
+            },
+            Some(_) => Err(Error::Engine { status: 409, message: format!("container `{}` is not running", name) }),
+            None => Err(Error::NoSuchContainer(name.to_string())),
+        }
+    }
+
+    fn attach(&self, name: &str) -> Result<i32, Error> {
+        self.inspect(name)?.ok_or_else(|| Error::NoSuchContainer(name.to_string()))?;
+        self.record(Operation::Attach(name.to_string()));
+        Ok(0)
+    }
+
+    fn logs(&self, name: &str) -> Result<String, Error> {
+        self.inspect(name)?.ok_or_else(|| Error::NoSuchContainer(name.to_string()))?;
+        Ok(String::new())
+    }
+
+    fn create_network(&self, network: &Network) -> Result<(), Error> {
+        let name = String::from_utf8_lossy(network.name()).into_owned();
+        if self.has_network(&name)? {
+            return Err(Error::Engine { status: 409, message: format!("network with name {} already exists", name) })
+        }
+        self.networks.borrow_mut().push(name.clone());
+        self.record(Operation::CreateNetwork(name));
+        Ok(())
+    }
+
+    fn remove_network(&self, name: &str) -> Result<(), Error> {
+        let mut networks = self.networks.borrow_mut();
+        let index = networks.iter()
+            .position(|network| network == name)
+            .ok_or_else(|| Error::NoSuchNetwork(name.to_string()))?;
+        networks.remove(index);
+        self.record(Operation::RemoveNetwork(name.to_string()));
+        Ok(())
+    }
+
+    fn has_network(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.networks.borrow().iter().any(|network| network == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::runtime::{ContainerRuntime, Error};
+    use super::{DryRun, Operation};
+
+    #[test]
+    fn test_lifecycle() {
+        let parser = Parser::parse(b"@ubuntu:\n    from: ubuntu:latest\n").unwrap();
+        let runtime = DryRun::default();
+
+        runtime.create(parser.container("ubuntu").unwrap()).unwrap();
+        assert_eq!(runtime.inspect("ubuntu").unwrap().unwrap().status, "created");
+        runtime.start("ubuntu").unwrap();
+        assert!(runtime.inspect("ubuntu").unwrap().unwrap().running);
+        assert_eq!(runtime.exec("ubuntu", &["/bin/bash".to_string()], true).unwrap(), 0);
+        runtime.stop("ubuntu").unwrap();
+        runtime.remove("ubuntu").unwrap();
+        assert!(runtime.inspect("ubuntu").unwrap().is_none());
+
+        assert_eq!(runtime.operations(), vec![
+            Operation::Create("ubuntu".to_string()),
+            Operation::Start("ubuntu".to_string()),
+            Operation::Exec("ubuntu".to_string(), vec!["/bin/bash".to_string()]),
+            Operation::Stop("ubuntu".to_string()),
+            Operation::Remove("ubuntu".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let parser = Parser::parse(b"@ubuntu:\n    from: ubuntu:latest\n").unwrap();
+        let runtime = DryRun::default();
+
+        assert!(matches!(runtime.start("ubuntu"), Err(Error::NoSuchContainer(_))));
+        runtime.create(parser.container("ubuntu").unwrap()).unwrap();
+        assert!(matches!(runtime.create(parser.container("ubuntu").unwrap()), Err(Error::Engine { status: 409, .. })));
+        assert!(matches!(runtime.exec("ubuntu", &[], false), Err(Error::Engine { status: 409, .. })));
+    }
+
+    #[test]
+    fn test_networks() {
+        let parser = Parser::parse(b"@network backend:
+
+@api:
+    from: alpine:latest
+    network: backend
+").unwrap();
+        let runtime = DryRun::default();
+
+        assert!(matches!(runtime.create(parser.container("api").unwrap()), Err(Error::NoSuchNetwork(name)) if name == "backend"));
+        runtime.create_network(parser.network("backend").unwrap()).unwrap();
+        assert!(runtime.has_network("backend").unwrap());
+        assert!(matches!(runtime.create_network(parser.network("backend").unwrap()), Err(Error::Engine { status: 409, .. })));
+        runtime.create(parser.container("api").unwrap()).unwrap();
+        runtime.remove("api").unwrap();
+        runtime.remove_network("backend").unwrap();
+        assert!(matches!(runtime.remove_network("backend"), Err(Error::NoSuchNetwork(_))));
+
+        assert_eq!(runtime.operations().iter().map(ToString::to_string).collect::<Vec<_>>(), vec![
+            "create network backend", "create api", "remove api", "remove network backend",
+        ]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+use crate::parser::{Container, Argument, dotenv};
+use super::host_path;
+
+/// Where a variable of a container's environment was defined.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// An `env:` argument, with its `KEY=VALUE`.
+    Argument(String),
+    File {
+        path: String,
+        line: usize
+    },
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Argument(variable) => write!(f, "`env: {}`", variable),
+            Source::File { path, line } => write!(f, "{}:{}", path, line),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EnvError {
+    Read {
+        path: String,
+        error: std::io::Error
+    },
+    Syntax {
+        path: String,
+        error: dotenv::Error
+    },
+    DuplicateKey {
+        container: String,
+        key: String,
+        first: Source,
+        second: Source
+    },
+}
+
+impl std::fmt::Display for EnvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvError::Read { path, error } => write!(f, "could not read env file {}: {}", path, error),
+            EnvError::Syntax { path, error } => write!(f, "invalid env file {}, {}", path, error),
+            EnvError::DuplicateKey { container, key, first, second } => write!(
+                f, "`{}` is set more than once in container `{}`, by {} and by {}", key, container, first, second
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// The `KEY=VALUE` pairs of the container's `env:` and `env-file:` arguments, in the order they are given.
+///
+/// Env files are read relative to the file they are declared in, `directory` being the one of the Crabfile, or
+/// to `home` for `~`, like volumes. A key may only be set once across all of them.
+pub fn environment(container: &Container, directory: &Path, home: Option<&Path>) -> Result<Vec<(String, String)>, EnvError> {
+    let mut variables: Vec<(String, String, Source)> = Vec::new();
+    let mut add = |key: String, value: String, source: Source| match variables.iter().find(|(other, ..)| *other == key) {
+        Some((_, _, first)) => Err(EnvError::DuplicateKey {
+            container: String::from_utf8_lossy(container.name()).into_owned(),
+            key,
+            first: first.clone(),
+            second: source
+        }),
+        None => {
+            variables.push((key, value, source));
+            Ok(())
+        },
+    };
+
+    for (index, argument) in container.arguments().iter().enumerate() {
+        match argument {
+            Argument::Env { key, value } => {
+                let (key, value) = (String::from_utf8_lossy(key).into_owned(), String::from_utf8_lossy(value).into_owned());
+                let source = Source::Argument(format!("{}={}", key, value));
+                add(key, value, source)?
+            },
+            Argument::EnvFile { path } => {
+                let path = host_path(&String::from_utf8_lossy(path), &directory.join(container.directory(index)), home);
+                let source = std::fs::read_to_string(&path)
+                    .map_err(|error| EnvError::Read { path: path.clone(), error })?;
+                let file = dotenv::parse(&source)
+                    .map_err(|error| EnvError::Syntax { path: path.clone(), error })?;
+                for variable in file {
+                    add(variable.key, variable.value, Source::File { path: path.clone(), line: variable.line })?;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(variables.into_iter().map(|(key, value, _)| (key, value)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::parser::{Parser, Sources, Expanded};
+    use crate::runtime::TempDir;
+    use super::{environment, EnvError, Source};
+
+    #[test]
+    fn test_environment() {
+        let directory = TempDir::new("environment");
+        std::fs::write(directory.path().join("app.env"), "# shared\nDATABASE_URL=postgres://db/crab\nexport RUST_LOG='debug'\n").unwrap();
+        let parser = Parser::parse(b"@app:\n    from: ubuntu:latest\n    env: PROFILE=dev build\n    env-file: app.env\n").unwrap();
+
+        assert_eq!(environment(parser.container("app").unwrap(), directory.path(), None).unwrap(), vec![
+            ("PROFILE".to_string(), "dev build".to_string()),
+            ("DATABASE_URL".to_string(), "postgres://db/crab".to_string()),
+            ("RUST_LOG".to_string(), "debug".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_included_env_file() {
+        let directory = TempDir::new("included-env");
+        std::fs::create_dir_all(directory.path().join("shared")).unwrap();
+        std::fs::write(directory.path().join("Crabfile"), "@include: shared/db.crab\n\n@app:\n    extends: db\n").unwrap();
+        std::fs::write(directory.path().join("shared/db.crab"), "@db:\n    from: postgres:16\n    env-file: db.env\n").unwrap();
+        std::fs::write(directory.path().join("shared/db.env"), "POSTGRES_DB=crab\n").unwrap();
+
+        let sources = Sources::load(&directory.path().join("Crabfile")).unwrap();
+        let expanded = Expanded::from_env(sources.text()).unwrap();
+        let parser = Parser::parse_sources(&sources, &expanded).unwrap();
+        for name in ["db", "app"] {
+            assert_eq!(environment(parser.container(name).unwrap(), directory.path(), None).unwrap(), vec![
+                ("POSTGRES_DB".to_string(), "crab".to_string()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn test_home_env_file() {
+        let home = TempDir::new("home-env");
+        std::fs::write(home.path().join("crab.env"), "TOKEN=secret\n").unwrap();
+        let parser = Parser::parse(b"@app:\n    from: ubuntu:latest\n    env-file: ~/crab.env\n").unwrap();
+
+        assert_eq!(environment(parser.container("app").unwrap(), Path::new("project"), Some(home.path())).unwrap(), vec![
+            ("TOKEN".to_string(), "secret".to_string()),
+        ]);
+        assert!(matches!(
+            environment(parser.container("app").unwrap(), Path::new("project"), None),
+            Err(EnvError::Read { path, .. }) if path == "~/crab.env"
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let directory = TempDir::new("duplicate-env");
+        std::fs::write(directory.path().join("app.env"), "PORT=80\nRUST_LOG=info\n").unwrap();
+        let parser = Parser::parse(b"@app:\n    from: ubuntu:latest\n    env: RUST_LOG=debug\n    env-file: app.env\n").unwrap();
+
+        match environment(parser.container("app").unwrap(), directory.path(), None) {
+            Err(EnvError::DuplicateKey { key, first, second, .. }) => {
+                assert_eq!(key, "RUST_LOG");
+                assert_eq!(first, Source::Argument("RUST_LOG=debug".to_string()));
+                assert_eq!(second, Source::File { path: directory.path().join("app.env").to_string_lossy().into_owned(), line: 2 });
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let parser = Parser::parse(b"@app:\n    from: ubuntu:latest\n    env: A=1\n    env: A=2\n").unwrap();
+        let error = environment(parser.container("app").unwrap(), Path::new(""), None).unwrap_err();
+        assert_eq!(error.to_string(), "`A` is set more than once in container `app`, by `env: A=1` and by `env: A=2`");
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let parser = Parser::parse(b"@app:\n    from: ubuntu:latest\n    env-file: ./does-not-exist.env\n").unwrap();
+
+        assert!(matches!(environment(parser.container("app").unwrap(), Path::new(""), None), Err(EnvError::Read { .. })));
+    }
+}
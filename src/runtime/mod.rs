@@ -0,0 +1,244 @@
+pub mod docker_api;
+mod cli;
+mod dry_run;
+mod terminal;
+mod environment;
+
+pub use docker_api::DockerApi;
+pub use cli::Cli;
+pub use dry_run::{DryRun, Operation};
+pub use terminal::stdin_is_terminal;
+pub use environment::{environment, EnvError, Source};
+
+use std::path::{Component, Path, PathBuf};
+use crate::parser::{Container, Network, VolumeKind, split_argv};
+
+/// A container engine crab can drive.
+///
+/// Containers are addressed by the name they have in the Crabfile.
+pub trait ContainerRuntime {
+    fn name(&self) -> &str;
+
+    /// Creates the container without starting it, returning the engine's id for it.
+    fn create(&self, container: &Container) -> Result<String, Error>;
+
+    fn start(&self, name: &str) -> Result<(), Error>;
+
+    fn stop(&self, name: &str) -> Result<(), Error>;
+
+    fn remove(&self, name: &str) -> Result<(), Error>;
+
+    /// Returns `None` when there is no container with the given name.
+    fn inspect(&self, name: &str) -> Result<Option<State>, Error>;
+
+    /// Runs `command` in the running container on the current terminal, returning its exit code.
+    fn exec(&self, name: &str, command: &[String], tty: bool) -> Result<i32, Error>;
+
+    /// Connects the current terminal to the container's main process, returning its exit code.
+    fn attach(&self, name: &str) -> Result<i32, Error>;
+
+    fn logs(&self, name: &str) -> Result<String, Error>;
+
+    /// Creates the network of a `@network` block, the containers on it reach each other by name.
+    fn create_network(&self, network: &Network) -> Result<(), Error>;
+
+    fn remove_network(&self, name: &str) -> Result<(), Error>;
+
+    /// Whether the engine has a network with the given name.
+    fn has_network(&self, name: &str) -> Result<bool, Error>;
+}
+
+/// What the engine reports about an existing container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub id: String,
+    pub status: String,
+    pub running: bool,
+}
+
+/// The runtimes `--runtime` can choose from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Docker,
+    Podman,
+    DockerApi,
+    DryRun,
+}
+
+impl Kind {
+    pub const NAMES: &'static [&'static str] = &["docker", "podman", "docker-api", "dry-run"];
+
+    /// Prefers the `docker` binary, then `podman`, then a reachable Docker Engine socket.
+    pub fn detect() -> Option<Self> {
+        if cli::in_path("docker") {
+            Some(Kind::Docker)
+        } else if cli::in_path("podman") {
+            Some(Kind::Podman)
+        } else {
+            docker_api::Endpoint::from_env().ok()
+                .filter(docker_api::Endpoint::is_reachable)
+                .map(|_| Kind::DockerApi)
+        }
+    }
+
+    /// Connects to the engine, bind mounts of the Crabfile resolved against `directory`.
+    pub fn connect(self, directory: &Path) -> Result<Box<dyn ContainerRuntime>, Error> {
+        Ok(match self {
+            Kind::Docker => Box::new(Cli::docker().in_directory(directory)),
+            Kind::Podman => Box::new(Cli::podman().in_directory(directory)),
+            Kind::DockerApi => Box::new(DockerApi::from_env()?.in_directory(directory)),
+            Kind::DryRun => Box::new(DryRun::default().in_directory(directory)),
+        })
+    }
+}
+
+impl std::str::FromStr for Kind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "docker" => Ok(Kind::Docker),
+            "podman" => Ok(Kind::Podman),
+            "docker-api" => Ok(Kind::DockerApi),
+            "dry-run" => Ok(Kind::DryRun),
+            other => Err(format!("unknown runtime `{}`, expected one of {}", other, Kind::NAMES.join(", "))),
+        }
+    }
+}
+
+/// The host side of a volume as the engine takes it: named volumes are kept as written, other sources are
+/// resolved by [`host_path`].
+pub fn volume_source(source: &[u8], directory: &Path, home: Option<&Path>) -> String {
+    let source = String::from_utf8_lossy(source);
+    match VolumeKind::of(source.as_bytes()) {
+        VolumeKind::Named => source.into_owned(),
+        _ => host_path(&source, directory, home),
+    }
+}
+
+/// A path on the host relative to `directory`, the one of the file declaring it, or to `home` for `~`, made
+/// absolute. Absolute paths and `~` without a home directory are kept as written.
+fn host_path(path: &str, directory: &Path, home: Option<&Path>) -> String {
+    let path = match path.strip_prefix('~') {
+        Some(rest) => match home {
+            Some(home) => home.join(rest.trim_start_matches('/')),
+            None => return path.to_string(),
+        },
+        None if path.starts_with('/') => return path.to_string(),
+        None => std::env::current_dir().unwrap_or_default().join(directory).join(path),
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => { resolved.pop(); },
+            component => resolved.push(component),
+        }
+    }
+    resolved.to_string_lossy().into_owned()
+}
+
+/// The arguments of a `command:` or `entrypoint:`, which the parser made sure split.
+fn argv(args: &[u8]) -> Vec<String> {
+    split_argv(args).expect("the parser only accepts arguments that split")
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Engine {
+        status: u16,
+        message: String
+    },
+    Command {
+        command: String,
+        message: String
+    },
+    NoSuchContainer(String),
+    NoSuchNetwork(String),
+    Environment(EnvError),
+    Unsupported(String),
+    Protocol(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not reach the container engine: {}", err),
+            Error::Engine { status, message } => write!(f, "container engine error ({}): {}", status, message),
+            Error::Command { command, message } => write!(f, "`{}` failed: {}", command, message),
+            Error::NoSuchContainer(name) => write!(f, "container `{}` does not exist", name),
+            Error::NoSuchNetwork(name) => write!(f, "network `{}` does not exist", name),
+            Error::Environment(err) => write!(f, "{}", err),
+            Error::Unsupported(what) => write!(f, "not supported by this runtime: {}", what),
+            Error::Protocol(message) => write!(f, "unexpected response from the container engine: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<EnvError> for Error {
+    fn from(err: EnvError) -> Self {
+        Error::Environment(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// A directory of its own for a test, removed with everything in it when dropped.
+#[cfg(test)]
+pub(crate) struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Self {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("crab-{}-{}-{}", name, std::process::id(), count));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{Kind, volume_source};
+
+    #[test]
+    fn test_kind_from_str() {
+        assert_eq!("podman".parse::<Kind>().ok(), Some(Kind::Podman));
+        assert_eq!("dry-run".parse::<Kind>().ok(), Some(Kind::DryRun));
+        assert!("containerd".parse::<Kind>().is_err());
+    }
+
+    #[test]
+    fn test_volume_source() {
+        let (directory, home) = (Path::new("/srv/app"), Some(Path::new("/home/crab")));
+        assert_eq!(volume_source(b"./src", directory, home), "/srv/app/src");
+        assert_eq!(volume_source(b"../shared/./data", directory, home), "/srv/shared/data");
+        assert_eq!(volume_source(b"/usr/lib/", directory, home), "/usr/lib/");
+        assert_eq!(volume_source(b"pgdata", directory, home), "pgdata");
+
+        assert_eq!(volume_source(b"~/.cargo", directory, home), "/home/crab/.cargo");
+        assert_eq!(volume_source(b"~", directory, home), "/home/crab");
+        assert_eq!(volume_source(b"~/.cargo", directory, None), "~/.cargo");
+    }
+}
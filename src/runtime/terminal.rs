@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Signals caught while a [`Signals`] guard is alive, one bit per signal number.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// Signals an interactive session reacts to instead of dying on them.
+pub const FORWARDED: &[libc::c_int] = &[libc::SIGWINCH, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM, libc::SIGHUP];
+
+pub fn is_terminal(fd: libc::c_int) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+/// Whether crab's standard input is a terminal, and so whether a shell gets one.
+pub fn stdin_is_terminal() -> bool {
+    is_terminal(libc::STDIN_FILENO)
+}
+
+/// The `(rows, columns)` of the terminal at `fd`.
+pub fn size(fd: libc::c_int) -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_row > 0 && size.ws_col > 0 => Some((size.ws_row, size.ws_col)),
+        _ => None,
+    }
+}
+
+/// Puts a terminal into raw mode, restoring the original settings when dropped.
+pub struct RawMode {
+    fd: libc::c_int,
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// Returns `None` when `fd` is not a terminal.
+    pub fn enable(fd: libc::c_int) -> std::io::Result<Option<Self>> {
+        if !is_terminal(fd) {
+            return Ok(None)
+        }
+
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(std::io::Error::last_os_error())
+        }
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error())
+        }
+        Ok(Some(RawMode { fd, original }))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.original) };
+    }
+}
+
+extern "C" fn record(signal: libc::c_int) {
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+/// Catches [`FORWARDED`] signals until dropped, so they can be passed on to the container.
+pub struct Signals {
+    previous: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+impl Signals {
+    pub fn catch() -> Self {
+        PENDING.store(0, Ordering::SeqCst);
+        let handler = record as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        Signals {
+            previous: FORWARDED.iter()
+                .map(|signal| (*signal, unsafe { libc::signal(*signal, handler) }))
+                .collect()
+        }
+    }
+
+    /// The signals caught since the last call.
+    pub fn take(&self) -> Vec<libc::c_int> {
+        let pending = PENDING.swap(0, Ordering::SeqCst);
+        FORWARDED.iter().copied().filter(|signal| pending & (1 << signal) != 0).collect()
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        for (signal, handler) in &self.previous {
+            unsafe { libc::signal(*signal, *handler) };
+        }
+    }
+}
+